@@ -22,10 +22,19 @@
 //! Dynamically typed value.
 
 use capability::FromClientHook;
+use message::{self, TypedReader};
 use private::capability::{ClientHook, PipelineHook, PipelineOp};
-use private::layout::{PointerReader, PointerBuilder};
-use traits::{FromPointerReader, FromPointerBuilder, SetPointerBuilder};
-use Result;
+use private::layout::{PointerReader, PointerBuilder, StructReader};
+use serialize::OwnedSpaceMessageReader;
+use traits::{FromPointerReader, FromPointerBuilder, SetPointerBuilder, Owned as OwnedTrait};
+use {MessageSize, Result};
+
+pub struct Owned;
+
+impl <'a> OwnedTrait<'a> for Owned {
+    type Reader = Reader<'a>;
+    type Builder = Builder<'a>;
+}
 
 #[derive(Copy, Clone)]
 pub struct Reader<'a> {
@@ -52,6 +61,34 @@ impl <'a> Reader<'a> {
         Ok(FromClientHook::new(try!(self.reader.get_capability())))
     }
 
+    /// Reads the pointer as text, without checking that the text is UTF-8.
+    pub fn get_text_as_bytes(&self) -> Result<&'a [u8]> {
+        self.reader.get_text_as_bytes(::std::ptr::null(), 0)
+    }
+
+    /// Reads the pointer as text, replacing any invalid UTF-8 sequences with U+FFFD.
+    pub fn get_text_lossy(&self) -> Result<::std::borrow::Cow<'a, str>> {
+        self.reader.get_text_lossy(::std::ptr::null(), 0)
+    }
+
+    /// The number of words and capabilities reachable from this pointer.
+    pub fn target_size(&self) -> Result<MessageSize> {
+        self.reader.total_size()
+    }
+
+    /// Deep-copies the pointed-to value into a new single-segment message, so that it can outlive
+    /// the message it was read from.
+    pub fn copy_to_owned<T>(&self) -> Result<TypedReader<OwnedSpaceMessageReader, T>>
+        where T : for<'b> OwnedTrait<'b>
+    {
+        let size = try!(self.reader.total_size());
+        let reader = self.reader;
+        let message = try!(message::new_owned_reader(size.word_count, |root| {
+            root.builder.copy_from(reader)
+        }));
+        Ok(TypedReader::new(message))
+    }
+
     //# Used by RPC system to implement pipelining. Applications
     //# generally shouldn't use this directly.
     pub fn get_pipelined_cap(&self, ops : &[PipelineOp]) -> Result<Box<ClientHook+Send>> {
@@ -70,6 +107,12 @@ impl <'a> Reader<'a> {
     }
 }
 
+impl <'a> FromPointerReader<'a> for Reader<'a> {
+    fn get_from_pointer(reader : &PointerReader<'a>) -> Result<Reader<'a>> {
+        Ok(Reader { reader : *reader })
+    }
+}
+
 pub struct Builder<'a> {
     builder : PointerBuilder<'a>
 }
@@ -96,6 +139,10 @@ impl <'a> Builder<'a> {
         SetPointerBuilder::<To>::set_pointer_builder(self.builder, value)
     }
 
+    pub fn set_struct(self, value : &StructReader) -> Result<()> {
+        self.builder.set_struct(value)
+    }
+
     // XXX value should be a user client.
     pub fn set_as_capability(&self, value : Box<ClientHook+Send>) {
         self.builder.set_capability(value);
@@ -112,6 +159,23 @@ impl <'a> Builder<'a> {
     }
 }
 
+impl <'a> FromPointerBuilder<'a> for Builder<'a> {
+    fn init_pointer(builder : PointerBuilder<'a>, _size : u32) -> Builder<'a> {
+        let mut result = Builder { builder : builder };
+        result.clear();
+        result
+    }
+    fn get_from_pointer(builder : PointerBuilder<'a>) -> Result<Builder<'a>> {
+        Ok(Builder { builder : builder })
+    }
+}
+
+impl <'a, 'b> SetPointerBuilder<Builder<'a>> for Reader<'b> {
+    fn set_pointer_builder<'c>(pointer : PointerBuilder<'c>, value : Reader<'b>) -> Result<()> {
+        pointer.copy_from(value.reader)
+    }
+}
+
 pub struct Pipeline {
     hook : Box<PipelineHook+Send>,
     ops : Vec<PipelineOp>,
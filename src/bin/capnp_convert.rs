@@ -0,0 +1,396 @@
+// Copyright (c) 2013-2015 Sandstorm Development Group, Inc. and contributors
+// Licensed under the MIT License:
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Converts a stream of messages from one encoding to another.
+//!
+//! Usage: `capnp-convert FROM:TO [--schema FILE --root TYPE] < INPUT > OUTPUT`
+//!
+//! The encodings are:
+//!
+//!  * `flat`: the standard framing written by `serialize::write_message`.
+//!  * `packed`: the packed framing written by `serialize_packed::write_packed_message`.
+//!  * `canonical`: the single-segment canonical form of `message::canonicalize`, without framing.
+//!    A stream of them can still be split, since each one's length follows from its root.
+//!  * `text` and `json`: one message per line, as written by `stringify` and `json`. These need
+//!    the schema of the root struct. FILE is a `CodeGeneratorRequest` in the flat encoding, such
+//!    as `capnp compile -o-` writes, and TYPE is the name of a struct in it (e.g. `Foo` or
+//!    `foo.capnp:Foo`) or its id in hex.
+//!
+//! Conversions between `flat` and `packed` keep the segments of each message as they are.
+
+extern crate capnp;
+
+use std::io::{Read, Write};
+
+use capnp::io::{ArrayInputStream, BufferedOutputStreamWrapper, OutputStream};
+use capnp::message::{self, MallocMessageBuilder, MessageBuilder, MessageReader, ReaderOptions,
+                     SegmentArrayMessageReader};
+use capnp::schema::{SchemaLoader, StructSchema};
+use capnp::{json, parse_text, serialize, serialize_packed, stringify, Word};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format { Flat, Packed, Canonical, Text, Json }
+
+fn parse_format(name : &str) -> Option<Format> {
+    match name {
+        "flat" => Some(Format::Flat),
+        "packed" => Some(Format::Packed),
+        "canonical" => Some(Format::Canonical),
+        "text" => Some(Format::Text),
+        "json" => Some(Format::Json),
+        _ => None,
+    }
+}
+
+fn usage() -> ! {
+    let _ = writeln!(::std::io::stderr(),
+                     "usage: capnp-convert FROM:TO [--schema FILE --root TYPE] < INPUT > OUTPUT\n\
+                      formats: flat, packed, canonical, text, json");
+    ::std::process::exit(2);
+}
+
+fn fail<T>(message : String) -> T {
+    let _ = writeln!(::std::io::stderr(), "capnp-convert: {}", message);
+    ::std::process::exit(1);
+}
+
+fn read_file(path : &str) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    if let Err(e) = ::std::fs::File::open(path).and_then(|mut f| f.read_to_end(&mut bytes)) {
+        fail::<()>(format!("{}: {}", path, e));
+    }
+    bytes
+}
+
+fn find_struct<'a>(loader : &'a SchemaLoader, name : &str) -> Option<StructSchema<'a>> {
+    let id = if name.starts_with("0x") { u64::from_str_radix(&name[2..], 16).ok() } else { None };
+    loader.get_all_loaded().into_iter()
+        .filter(|schema| {
+            let display_name = &schema.get_proto().display_name[..];
+            Some(schema.get_id()) == id || display_name == name ||
+                display_name.split(':').last() == Some(name)
+        })
+        .filter_map(|schema| schema.as_struct())
+        .next()
+}
+
+struct Converter<'a, W : Write> {
+    to : Format,
+    schema : Option<StructSchema<'a>>,
+    options : ReaderOptions,
+    out : W,
+}
+
+impl <'a, W : Write> Converter<'a, W> {
+    fn schema(&self) -> StructSchema<'a> {
+        match self.schema {
+            Some(schema) => schema,
+            None => fail("the text and json formats need --schema and --root".to_string()),
+        }
+    }
+
+    fn write(&mut self, segments : &[&[Word]]) -> Result<(), String> {
+        let message = SegmentArrayMessageReader::new(segments, self.options);
+        match self.to {
+            Format::Flat =>
+                serialize::write_message_segments(&mut self.out, segments).map_err(|e| e.to_string()),
+            Format::Packed => {
+                let mut buffered = BufferedOutputStreamWrapper::new(&mut self.out);
+                try!(serialize_packed::write_packed_message_segments(&mut buffered, segments)
+                     .map_err(|e| e.to_string()));
+                OutputStream::flush(&mut buffered).map_err(|e| e.to_string())
+            }
+            Format::Canonical => {
+                let words = try!(message::canonicalize(&message).map_err(|e| e.to_string()));
+                self.out.write_all(Word::words_to_bytes(&words)).map_err(|e| e.to_string())
+            }
+            Format::Text => {
+                // Nothing may be elided, since the output is meant to be parsed back.
+                let mut options = stringify::PrintOptions::new();
+                options.max_depth(::std::u32::MAX);
+                let text = try!(stringify::message_to_string(&message, self.schema(), &options)
+                                .map_err(|e| e.to_string()));
+                writeln!(self.out, "{}", text).map_err(|e| e.to_string())
+            }
+            Format::Json => {
+                let text = try!(json::encode_message(&message, self.schema(), &json::JsonOptions::new())
+                                .map_err(|e| e.to_string()));
+                writeln!(self.out, "{}", text).map_err(|e| e.to_string())
+            }
+        }
+    }
+
+    fn write_reader<M : MessageReader>(&mut self, message : &M) -> Result<(), String> {
+        let segments : Vec<&[Word]> =
+            (0..message.get_segment_count()).map(|id| message.get_segment(id)).collect();
+        self.write(&segments)
+    }
+
+    fn write_builder(&mut self, mut message : MallocMessageBuilder) -> Result<(), String> {
+        self.write(message.get_segments_for_output())
+    }
+
+    fn convert(&mut self, from : Format, input : &[u8]) -> Result<(), String> {
+        match from {
+            Format::Flat => {
+                let mut input = input;
+                while !input.is_empty() {
+                    let message = try!(serialize::read_message_from_bytes(&mut input, self.options)
+                                       .map_err(|e| e.to_string()));
+                    try!(self.write_reader(&message));
+                }
+            }
+            Format::Packed => {
+                let mut input = ArrayInputStream::new(input);
+                while !input.is_empty() {
+                    let message = try!(serialize_packed::new_reader(&mut input, self.options)
+                                       .map_err(|e| e.to_string()));
+                    try!(self.write_reader(&message));
+                }
+            }
+            Format::Canonical => {
+                if input.len() % 8 != 0 {
+                    return Err("canonical input is not a whole number of words".to_string());
+                }
+                let mut words = Word::allocate_zeroed_vec(input.len() / 8);
+                unsafe {
+                    ::std::ptr::copy_nonoverlapping(input.as_ptr(), words.as_mut_ptr() as *mut u8,
+                                                    input.len());
+                }
+                let mut words = &words[..];
+                while !words.is_empty() {
+                    // A canonical message is its root pointer followed by exactly the words it
+                    // reaches, which gives its length. Canonicalizing it again must then give
+                    // back the same bytes; anything else is not canonical input.
+                    let size = {
+                        let segments = [words];
+                        let message = SegmentArrayMessageReader::new(&segments, self.options);
+                        let root = try!(message.get_root_internal().map_err(|e| e.to_string()));
+                        1 + try!(root.target_size().map_err(|e| e.to_string())).word_count as usize
+                    };
+                    if size > words.len() {
+                        return Err("canonical input ends in the middle of a message".to_string());
+                    }
+                    let segments = [&words[..size]];
+                    let message = SegmentArrayMessageReader::new(&segments, self.options);
+                    let canonical = try!(message::canonicalize(&message).map_err(|e| e.to_string()));
+                    if Word::words_to_bytes(&canonical) != Word::words_to_bytes(segments[0]) {
+                        return Err("input message is not in canonical form".to_string());
+                    }
+                    try!(self.write(&segments));
+                    words = &words[size..];
+                }
+            }
+            Format::Text | Format::Json => {
+                let schema = self.schema();
+                let input = try!(::std::str::from_utf8(input).map_err(|e| e.to_string()));
+                for (number, line) in input.lines().enumerate() {
+                    if line.trim().is_empty() { continue; }
+                    let mut message = MallocMessageBuilder::new_default();
+                    let result = if from == Format::Text {
+                        parse_text::parse_message(line, schema, &mut message).map_err(|e| e.to_string())
+                    } else {
+                        json::decode_message(line, schema, &mut message, &json::JsonOptions::new())
+                            .map_err(|e| e.to_string())
+                    };
+                    try!(result.map_err(|e| format!("line {}: {}", number + 1, e)));
+                    try!(self.write_builder(message));
+                }
+            }
+        }
+        self.out.flush().map_err(|e| e.to_string())
+    }
+}
+
+fn main() {
+    let args : Vec<String> = ::std::env::args().skip(1).collect();
+    let mut formats = None;
+    let mut schema_path = None;
+    let mut root_name = None;
+    let mut i = 0;
+    while i < args.len() {
+        match &args[i][..] {
+            "--schema" if i + 1 < args.len() => { schema_path = Some(args[i + 1].clone()); i += 1; }
+            "--root" if i + 1 < args.len() => { root_name = Some(args[i + 1].clone()); i += 1; }
+            arg if formats.is_none() && arg.contains(':') => {
+                let mut parts = arg.splitn(2, ':');
+                match (parts.next().and_then(parse_format), parts.next().and_then(parse_format)) {
+                    (Some(from), Some(to)) => formats = Some((from, to)),
+                    _ => usage(),
+                }
+            }
+            _ => usage(),
+        }
+        i += 1;
+    }
+    let (from, to) = match formats { Some(f) => f, None => usage() };
+
+    let mut loader = SchemaLoader::new();
+    let schema = match (schema_path, root_name) {
+        (Some(path), Some(name)) => {
+            let bytes = read_file(&path);
+            let request = serialize::read_message_from_bytes(&mut &bytes[..], ReaderOptions::new())
+                .and_then(|request| loader.load_code_generator_request(&request));
+            if let Err(e) = request {
+                fail::<()>(format!("{}: {}", path, e));
+            }
+            match find_struct(&loader, &name) {
+                Some(schema) => Some(schema),
+                None => fail(format!("no struct named {} in {}", name, path)),
+            }
+        }
+        (None, None) => None,
+        _ => usage(),
+    };
+
+    let mut input = Vec::new();
+    if let Err(e) = ::std::io::stdin().read_to_end(&mut input) {
+        fail::<()>(e.to_string());
+    }
+
+    let stdout = ::std::io::stdout();
+    let mut converter = Converter {
+        to : to,
+        schema : schema,
+        options : ReaderOptions::new(),
+        out : ::std::io::BufWriter::new(stdout.lock()),
+    };
+    if let Err(e) = converter.convert(from, &input) {
+        fail::<()>(e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use capnp::message::{MallocMessageBuilder, MessageBuilder, ReaderOptions};
+    use capnp::private::layout::{PointerBuilder, StructBuilder, StructSize};
+    use capnp::schema::SchemaLoader;
+    use capnp::traits::FromPointerBuilder;
+    use super::{Converter, Format};
+
+    struct NodeBuilder<'a>(StructBuilder<'a>);
+
+    impl <'a> FromPointerBuilder<'a> for NodeBuilder<'a> {
+        fn init_pointer(builder : PointerBuilder<'a>, _size : u32) -> NodeBuilder<'a> {
+            NodeBuilder(builder.init_struct(StructSize { data : 5, pointers : 6 }))
+        }
+        fn get_from_pointer(_builder : PointerBuilder<'a>) -> ::capnp::Result<NodeBuilder<'a>> {
+            unimplemented!()
+        }
+    }
+
+    // Loads `struct Pair @0x1 { n @0 :Int64; s @1 :Text; next @2 :Pair; }`, whose fields are at
+    // data word 0 and pointers 0 and 1.
+    fn pair_schema() -> SchemaLoader {
+        let mut message = MallocMessageBuilder::new_default();
+        {
+            let node : NodeBuilder = message.get_root_internal().init_as();
+            let node = node.0;
+            node.set_data_field::<u64>(0, 1);
+            node.get_pointer_field(0).set_text("test.capnp:Pair");
+            node.set_data_field::<u32>(2, 11);
+            node.set_data_field::<u16>(6, 1);
+            node.set_data_field::<u16>(7, 1);
+            node.set_data_field::<u16>(12, 2);
+            let fields = node.get_pointer_field(3).init_struct_list(
+                3, StructSize { data : 3, pointers : 4 });
+            let defs = [("n", 0, 5), ("s", 0, 12), ("next", 1, 16)];
+            for (i, &(name, offset, type_which)) in defs.iter().enumerate() {
+                let field = fields.get_struct_element(i as u32);
+                field.get_pointer_field(0).set_text(name);
+                field.set_data_field::<u16>(0, i as u16);
+                field.set_data_field_mask::<u16>(1, 0xffff, 0xffff);
+                field.set_data_field::<u32>(1, offset);
+                field.set_data_field::<u16>(5, 1);
+                field.set_data_field::<u16>(6, i as u16);
+                let type_ = field.get_pointer_field(2).init_struct(
+                    StructSize { data : 3, pointers : 1 });
+                type_.set_data_field::<u16>(0, type_which);
+                if type_which == 16 { type_.set_data_field::<u64>(1, 1); }
+                let value = field.get_pointer_field(3).init_struct(
+                    StructSize { data : 2, pointers : 1 });
+                value.set_data_field::<u16>(0, type_which);
+            }
+        }
+        let mut loader = SchemaLoader::new();
+        loader.load_encoded_node(message.get_segments_for_output()[0]).unwrap();
+        loader
+    }
+
+    fn convert(loader : &SchemaLoader, from : Format, to : Format,
+               input : &[u8]) -> Result<Vec<u8>, String> {
+        let mut converter = Converter {
+            to : to,
+            schema : Some(loader.get_struct_schema(1).unwrap()),
+            options : ReaderOptions::new(),
+            out : Vec::new(),
+        };
+        try!(converter.convert(from, input));
+        Ok(converter.out)
+    }
+
+    const TEXT : &'static str =
+        "(n = 1, s = \"one\")\n(n = -2, next = (s = \"two\", next = (n = 3)))\n";
+
+    #[test]
+    fn text_and_json_round_trip() {
+        let loader = pair_schema();
+        let text = convert(&loader, Format::Text, Format::Text, TEXT.as_bytes()).unwrap();
+        assert_eq!(::std::str::from_utf8(&text).unwrap().lines().count(), 2);
+
+        let json = convert(&loader, Format::Text, Format::Json, &text).unwrap();
+        assert_eq!(::std::str::from_utf8(&json).unwrap().lines().count(), 2);
+        assert_eq!(convert(&loader, Format::Json, Format::Text, &json).unwrap(), text);
+        assert_eq!(convert(&loader, Format::Json, Format::Json, &json).unwrap(), json);
+    }
+
+    #[test]
+    fn flat_and_canonical_round_trip() {
+        let loader = pair_schema();
+        let text = convert(&loader, Format::Text, Format::Text, TEXT.as_bytes()).unwrap();
+        let flat = convert(&loader, Format::Text, Format::Flat, &text).unwrap();
+
+        let canonical = convert(&loader, Format::Flat, Format::Canonical, &flat).unwrap();
+        assert_eq!(convert(&loader, Format::Canonical, Format::Canonical, &canonical).unwrap(),
+                   canonical);
+        let flat_again = convert(&loader, Format::Canonical, Format::Flat, &canonical).unwrap();
+        assert_eq!(convert(&loader, Format::Flat, Format::Canonical, &flat_again).unwrap(),
+                   canonical);
+        assert_eq!(convert(&loader, Format::Flat, Format::Text, &flat_again).unwrap(), text);
+
+        // A truncated stream is an error rather than a shorter one.
+        assert!(convert(&loader, Format::Canonical, Format::Flat,
+                        &canonical[..canonical.len() - 8]).is_err());
+    }
+
+    #[test]
+    fn text_output_fails_on_bad_field() {
+        let loader = pair_schema();
+        let flat : &[u8] = &[
+            0, 0, 0, 0, 4, 0, 0, 0,          // one segment of four words
+            0, 0, 0, 0, 1, 0, 2, 0,          // root: struct with one data word and two pointers
+            7, 0, 0, 0, 0, 0, 0, 0,          // n = 7
+            0x91, 1, 0, 0, 0x1a, 0, 0, 0,    // s: byte list far past the end of the segment
+            0, 0, 0, 0, 0, 0, 0, 0];         // next = null
+        assert!(convert(&loader, Format::Flat, Format::Text, flat).is_err());
+        assert!(convert(&loader, Format::Flat, Format::Flat, flat).is_ok());
+    }
+}
@@ -0,0 +1,107 @@
+// Copyright (c) 2013-2015 Sandstorm Development Group, Inc. and contributors
+// Licensed under the MIT License:
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Prints the wire structure of serialized messages, without needing their schema.
+//!
+//! Usage: `capnp-dump [--packed] [FILE]`
+//!
+//! Reads every message in FILE, or in standard input if no file is given, and prints its
+//! segment table and pointers followed by a breakdown of where its words go. See
+//! `capnp::inspect` for the output format.
+
+extern crate capnp;
+
+use std::io::{Read, Write};
+
+use capnp::io::ArrayInputStream;
+use capnp::message::{MessageReader, ReaderOptions};
+use capnp::{inspect, serialize, serialize_packed, Word};
+
+fn usage() -> ! {
+    let _ = writeln!(::std::io::stderr(), "usage: capnp-dump [--packed] [FILE]");
+    ::std::process::exit(2);
+}
+
+fn dump_message<M : MessageReader>(index : usize, message : &M) {
+    let segments : Vec<&[Word]> =
+        (0..message.get_segment_count()).map(|id| message.get_segment(id)).collect();
+    let mut text = String::new();
+    let _ = inspect::dump(&segments, *message.get_options(), &mut text);
+    println!("message {}:", index);
+    print!("{}", text);
+    println!("{}", inspect::analyze(&segments, *message.get_options()));
+}
+
+fn main() {
+    let mut packed = false;
+    let mut path = None;
+    for arg in ::std::env::args().skip(1) {
+        match &arg[..] {
+            "--packed" => packed = true,
+            "-h" | "--help" => usage(),
+            _ if path.is_none() && !arg.starts_with("--") => path = Some(arg),
+            _ => usage(),
+        }
+    }
+
+    let mut input = Vec::new();
+    let read = match path {
+        Some(ref path) => ::std::fs::File::open(path).and_then(|mut f| f.read_to_end(&mut input)),
+        None => ::std::io::stdin().read_to_end(&mut input),
+    };
+    if let Err(e) = read {
+        let _ = writeln!(::std::io::stderr(), "capnp-dump: {}", e);
+        ::std::process::exit(1);
+    }
+
+    let options = ReaderOptions::new();
+    let mut index = 0;
+    let result = if packed {
+        let mut stream = ArrayInputStream::new(&input);
+        let mut result = Ok(());
+        while !stream.is_empty() {
+            match serialize_packed::new_reader(&mut stream, options) {
+                Ok(message) => dump_message(index, &message),
+                Err(e) => { result = Err(e); break; }
+            }
+            index += 1;
+        }
+        result
+    } else {
+        let mut stream = &input[..];
+        let mut result = Ok(());
+        while !stream.is_empty() {
+            match serialize::new_reader(&mut stream, options) {
+                Ok(message) => dump_message(index, &message),
+                Err(e) => { result = Err(e); break; }
+            }
+            index += 1;
+        }
+        result
+    };
+
+    if let Err(e) = result {
+        // Framing errors leave no way to find the start of the next message.
+        println!("message {}:", index);
+        println!("error: {}", e);
+        ::std::process::exit(1);
+    }
+}
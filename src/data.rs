@@ -21,8 +21,15 @@
 
 //! Sequence of bytes.
 
-use private::layout::{PointerBuilder, PointerReader};
-use Result;
+use private::layout::{Byte, ListBuilder, PointerBuilder, PointerReader};
+use {Error, Result};
+
+pub struct Owned;
+
+impl <'a> ::traits::Owned<'a> for Owned {
+    type Reader = Reader<'a>;
+    type Builder = Builder<'a>;
+}
 
 pub type Reader<'a> = &'a [u8];
 
@@ -58,3 +65,119 @@ impl <'a> ::traits::SetPointerBuilder<Builder<'a>> for Reader<'a> {
     }
 }
 
+
+/// A data blob that can be appended to through `std::io::Write`. The blob grows inside the
+/// message as it is written, in place if it is the last thing allocated in its segment and by
+/// moving it otherwise, so a payload can be streamed into a message without buffering it first.
+pub struct Writer<'a> {
+    list : ListBuilder<'a>,
+
+    // Length of the blob. Less than `list.len()` if the list has spare capacity, which is kept
+    // zeroed and is trimmed on drop.
+    len : u32,
+}
+
+impl <'a> Writer<'a> {
+    pub fn new(list : ListBuilder<'a>) -> Writer<'a> {
+        let len = list.len();
+        Writer { list : list, len : len }
+    }
+
+    pub fn len(&self) -> u32 { self.len }
+
+    pub fn as_slice<'b>(&'b self) -> &'b [u8] {
+        &self.list.as_reader().into_raw_bytes()[..self.len as usize]
+    }
+
+    pub fn as_mut_slice<'b>(&'b mut self) -> &'b mut [u8] {
+        &mut self.list.into_raw_bytes()[..self.len as usize]
+    }
+
+    /// Shrinks the blob to `new_len` bytes.
+    pub fn truncate(&mut self, new_len : u32) -> Result<()> {
+        assert!(new_len <= self.len, "Cannot truncate a blob to a larger size.");
+        {
+            let dropped = &mut self.as_mut_slice()[new_len as usize..];
+            for ii in 0..dropped.len() {
+                dropped[ii] = 0;
+            }
+        }
+        self.len = new_len;
+        Ok(())
+    }
+
+    /// Extends the blob with zeros to `new_len` bytes.
+    pub fn grow(&mut self, new_len : u32) -> Result<()> {
+        assert!(new_len >= self.len, "Cannot grow a blob to a smaller size.");
+        try!(self.reserve(new_len as usize));
+        self.len = new_len;
+        Ok(())
+    }
+
+    /// Appends `bytes` to the end of the blob.
+    pub fn append(&mut self, bytes : &[u8]) -> Result<()> {
+        let old_len = self.len as usize;
+        let new_len = old_len + bytes.len();
+        try!(self.reserve(new_len));
+        self.len = new_len as u32;
+        let dst = &mut self.as_mut_slice()[old_len..];
+        for ii in 0..bytes.len() {
+            dst[ii] = bytes[ii];
+        }
+        Ok(())
+    }
+
+    /// Trims any spare capacity and returns the blob.
+    pub fn into_builder(mut self) -> Builder<'a> {
+        self.trim();
+        let len = self.len as usize;
+        &mut self.list.into_raw_bytes()[..len]
+    }
+
+    // Makes sure that the list has room for `len` bytes, at least doubling its capacity when it
+    // has to grow so that a blob built by many small appends is copied a bounded number of times.
+    fn reserve(&mut self, len : usize) -> Result<()> {
+        if len <= self.list.len() as usize { return Ok(()); }
+        if len >= 1 << 29 {
+            return Err(Error::new_decode_error("Data is too long.", None));
+        }
+        let capacity = ::std::cmp::min(::std::cmp::max(len, 2 * self.list.len() as usize),
+                                       (1 << 29) - 1);
+        unsafe { self.list.grow(capacity as u32) }
+    }
+
+    fn trim(&mut self) {
+        if self.list.len() > self.len {
+            // The spare capacity is all zero, so this only fails if the message is already
+            // broken, and there is nowhere to report that from here.
+            let _ = unsafe { self.list.truncate(self.len) };
+        }
+    }
+}
+
+impl <'a> Drop for Writer<'a> {
+    fn drop(&mut self) {
+        self.trim();
+    }
+}
+
+impl <'a> ::std::io::Write for Writer<'a> {
+    fn write(&mut self, buf : &[u8]) -> ::std::io::Result<usize> {
+        match self.append(buf) {
+            Ok(()) => Ok(buf.len()),
+            Err(Error::Io(e)) => Err(e),
+            Err(e) => Err(::std::io::Error::new(::std::io::ErrorKind::Other, e)),
+        }
+    }
+
+    fn flush(&mut self) -> ::std::io::Result<()> { Ok(()) }
+}
+
+impl <'a> ::traits::FromPointerBuilder<'a> for Writer<'a> {
+    fn init_pointer(builder : PointerBuilder<'a>, size : u32) -> Writer<'a> {
+        Writer::new(builder.init_list(Byte, size))
+    }
+    fn get_from_pointer(builder : PointerBuilder<'a>) -> Result<Writer<'a>> {
+        Ok(Writer::new(try!(builder.get_list(Byte, ::std::ptr::null()))))
+    }
+}
@@ -21,10 +21,17 @@
 
 //! List of sequences of bytes.
 
-use traits::{FromPointerReader, FromPointerBuilder};
+use traits::{FromPointerReader, FromPointerBuilder, IndexMove, ListIter, ListAppender, ResizableList};
 use private::layout::*;
 use Result;
 
+pub struct Owned;
+
+impl <'a> ::traits::Owned<'a> for Owned {
+    type Reader = Reader<'a>;
+    type Builder = Builder<'a>;
+}
+
 #[derive(Clone, Copy)]
 pub struct Reader<'a> {
     pub reader : ListReader<'a>
@@ -36,6 +43,11 @@ impl <'a> Reader<'a> {
     }
 
     pub fn len(&self) -> u32 { self.reader.len() }
+
+    pub fn iter(self) -> ListIter<Reader<'a>, Result<::data::Reader<'a>>> {
+        let l = self.len();
+        ListIter::new(self, l)
+    }
 }
 
 impl <'a> FromPointerReader<'a> for Reader<'a> {
@@ -44,11 +56,47 @@ impl <'a> FromPointerReader<'a> for Reader<'a> {
     }
 }
 
+impl <'a>  IndexMove<u32, Result<::data::Reader<'a>>> for Reader<'a>{
+    fn index_move(&self, index : u32) -> Result<::data::Reader<'a>> {
+        self.get(index)
+    }
+}
+
+impl <'a> ::std::ops::Index<u32> for Reader<'a> {
+    type Output = [u8];
+
+    /// Like `get()`, but panics if the element cannot be read.
+    fn index<'b>(&'b self, index : u32) -> &'b [u8] {
+        match self.get(index) {
+            Ok(v) => v,
+            Err(e) => panic!("{}", e),
+        }
+    }
+}
+
 impl <'a> Reader<'a> {
     pub fn get(self, index : u32) -> Result<::data::Reader<'a>> {
         assert!(index <  self.len());
         self.reader.get_pointer_element(index).get_data(::std::ptr::null(), 0)
     }
+
+    /// Like `get()`, but returns `None` instead of panicking if `index` is out of bounds.
+    pub fn try_get(self, index : u32) -> Option<Result<::data::Reader<'a>>> {
+        if index < self.len() {
+            Some(self.get(index))
+        } else {
+            None
+        }
+    }
+}
+
+impl <'a> ::std::iter::IntoIterator for Reader<'a> {
+    type Item = Result<::data::Reader<'a>>;
+    type IntoIter = ListIter<Reader<'a>, Self::Item>;
+
+    fn into_iter(self) -> ListIter<Reader<'a>, Self::Item> {
+        self.iter()
+    }
 }
 
 pub struct Builder<'a> {
@@ -70,6 +118,21 @@ impl <'a> Builder<'a> {
     pub fn borrow<'b>(&'b mut self) -> Builder<'b> {
         Builder {builder : self.builder}
     }
+
+    /// Shrinks the list to `new_len` elements. See `ListBuilder::truncate()`.
+    pub fn truncate(&mut self, new_len : u32) -> Result<()> {
+        unsafe { self.builder.truncate(new_len) }
+    }
+
+    /// Extends the list to `new_len` elements. See `ListBuilder::grow()`.
+    pub fn grow(&mut self, new_len : u32) -> Result<()> {
+        unsafe { self.builder.grow(new_len) }
+    }
+
+    pub fn iter(self) -> ListIter<Builder<'a>, ElementBuilder<'a>> {
+        let l = self.len();
+        ListIter::new(self, l)
+    }
 }
 
 
@@ -91,6 +154,65 @@ impl <'a> Builder<'a> {
         assert!(index < self.len());
         self.builder.get_pointer_element(index).get_data(::std::ptr::null(), 0)
     }
+
+    /// Like `get()`, but returns `None` instead of panicking if `index` is out of bounds.
+    pub fn try_get(self, index : u32) -> Option<Result<::data::Builder<'a>>> {
+        if index < self.len() {
+            Some(self.get(index))
+        } else {
+            None
+        }
+    }
+}
+
+impl <'a>  IndexMove<u32, ElementBuilder<'a>> for Builder<'a> {
+    fn index_move(&self, index : u32) -> ElementBuilder<'a> {
+        assert!(index < self.len());
+        ::traits::ElementBuilder::new(Builder { builder : self.builder }, index)
+    }
+}
+
+impl <'a> ::std::iter::IntoIterator for Builder<'a> {
+    type Item = ElementBuilder<'a>;
+    type IntoIter = ListIter<Builder<'a>, ElementBuilder<'a>>;
+
+    fn into_iter(self) -> ListIter<Builder<'a>, ElementBuilder<'a>> {
+        self.iter()
+    }
+}
+
+impl <'a> ResizableList for Builder<'a> {
+    fn len(&self) -> u32 { self.builder.len() }
+    fn truncate(&mut self, new_len : u32) -> Result<()> {
+        unsafe { self.builder.truncate(new_len) }
+    }
+    fn grow(&mut self, new_len : u32) -> Result<()> {
+        unsafe { self.builder.grow(new_len) }
+    }
+}
+
+impl <'a> ListAppender<Builder<'a>> {
+    pub fn push(&mut self, value : ::data::Reader) -> Result<()> {
+        let index = try!(self.next_index());
+        self.get_list().set(index, value);
+        Ok(())
+    }
+}
+
+pub type ElementBuilder<'a> = ::traits::ElementBuilder<Builder<'a>>;
+
+impl <'a> ::traits::ElementBuilder<Builder<'a>> {
+    pub fn get(self) -> Result<::data::Builder<'a>> {
+        self.get_list().builder.get_pointer_element(self.index()).get_data(::std::ptr::null(), 0)
+    }
+
+    pub fn set(&mut self, value : ::data::Reader) {
+        self.get_list().builder.get_pointer_element(self.index()).set_data(value);
+    }
+
+    pub fn init(self, size : u32) -> ::data::Builder<'a> {
+        self.get_list().builder.get_pointer_element(self.index()).init_data(size)
+    }
 }
 
 
@@ -0,0 +1,366 @@
+// Copyright (c) 2013-2015 Sandstorm Development Group, Inc. and contributors
+// Licensed under the MIT License:
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Field-by-field comparison of two messages that share a schema.
+//!
+//! `diff()` walks both messages in parallel and returns one `Change` for each leaf that
+//! differs, addressed by its path from the root. Nested structs are compared recursively rather
+//! than reported as a whole. In lists, unchanged elements at either end are matched up first, so
+//! a single insertion or removal is reported as just that instead of as a change to every later
+//! element.
+//!
+//! `AnyPointer` fields are opaque without a schema, so they are only reported when one side is
+//! null and the other is not.
+
+use std::fmt;
+
+use dynamic_list;
+use dynamic_struct;
+use dynamic_value;
+use schema::Field;
+use {Error, Result};
+
+/// One step of the path from the root struct to a changed value.
+#[derive(Clone, Copy)]
+pub enum PathElement<'a> {
+    Field(Field<'a>),
+    Index(u32),
+}
+
+#[derive(Clone, Copy)]
+pub enum ChangeKind<'a> {
+    /// The value is present only in the new message. For a list element, the path holds its
+    /// index in the new list.
+    Added(dynamic_value::Reader<'a>),
+
+    /// The value is present only in the old message. For a list element, the path holds its
+    /// index in the old list.
+    Removed(dynamic_value::Reader<'a>),
+
+    /// The value is present in both messages, with different contents.
+    Modified(dynamic_value::Reader<'a>, dynamic_value::Reader<'a>),
+}
+
+#[derive(Clone)]
+pub struct Change<'a> {
+    pub path : Vec<PathElement<'a>>,
+    pub kind : ChangeKind<'a>,
+}
+
+impl <'a> Change<'a> {
+    /// The path in the form `items[2].label`.
+    pub fn get_path_string(&self) -> String {
+        let mut result = String::new();
+        for element in self.path.iter() {
+            match *element {
+                PathElement::Field(field) => {
+                    if !result.is_empty() { result.push('.'); }
+                    result.push_str(field.get_name());
+                }
+                PathElement::Index(index) => {
+                    result.push_str(&format!("[{}]", index));
+                }
+            }
+        }
+        result
+    }
+}
+
+impl <'a> fmt::Display for Change<'a> {
+    fn fmt(&self, fmt : &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            ChangeKind::Added(value) => write!(fmt, "{}: added {}", self.get_path_string(), value),
+            ChangeKind::Removed(value) => write!(fmt, "{}: removed {}", self.get_path_string(), value),
+            ChangeKind::Modified(old, new) =>
+                write!(fmt, "{}: {} -> {}", self.get_path_string(), old, new),
+        }
+    }
+}
+
+/// Lists the differences between `old` and `new`, which must have the same schema.
+pub fn diff<'a>(old : dynamic_struct::Reader<'a>, new : dynamic_struct::Reader<'a>)
+                -> Result<Vec<Change<'a>>>
+{
+    if old.get_schema().get_proto().id != new.get_schema().get_proto().id {
+        return Err(Error::new_decode_error("Cannot diff structs of different types.", None));
+    }
+    let mut differ = Differ { path : Vec::new(), changes : Vec::new(), stop_at_first : false };
+    try!(differ.diff_struct(old, new));
+    Ok(differ.changes)
+}
+
+/// Returns true if `diff()` would find no changes between `a` and `b`.
+pub fn equal<'a>(a : dynamic_value::Reader<'a>, b : dynamic_value::Reader<'a>) -> Result<bool> {
+    let mut differ = Differ { path : Vec::new(), changes : Vec::new(), stop_at_first : true };
+    try!(differ.diff_value(a, b));
+    Ok(differ.changes.is_empty())
+}
+
+struct Differ<'a> {
+    path : Vec<PathElement<'a>>,
+    changes : Vec<Change<'a>>,
+
+    /// If true, only whether there are any changes matters, so the walk ends at the first one.
+    stop_at_first : bool,
+}
+
+impl <'a> Differ<'a> {
+    fn push(&mut self, kind : ChangeKind<'a>) {
+        self.changes.push(Change { path : self.path.clone(), kind : kind });
+    }
+
+    fn done(&self) -> bool {
+        self.stop_at_first && !self.changes.is_empty()
+    }
+
+    /// Diffs one pair of list elements and returns the changes instead of recording them, so
+    /// that the caller can decide whether the pairing holds.
+    fn diff_element(&mut self, index : u32, old : dynamic_value::Reader<'a>,
+                    new : dynamic_value::Reader<'a>) -> Result<Vec<Change<'a>>>
+    {
+        let mark = self.changes.len();
+        self.path.push(PathElement::Index(index));
+        let result = self.diff_value(old, new);
+        self.path.pop();
+        try!(result);
+        Ok(self.changes.split_off(mark))
+    }
+
+    fn diff_struct(&mut self, old : dynamic_struct::Reader<'a>, new : dynamic_struct::Reader<'a>)
+                   -> Result<()>
+    {
+        for field in old.get_schema().get_fields_in_code_order().into_iter() {
+            if self.done() { break; }
+            self.path.push(PathElement::Field(field));
+            match (try!(old.has(field)), try!(new.has(field))) {
+                (false, false) => {}
+                (true, false) => self.push(ChangeKind::Removed(try!(old.get(field)))),
+                (false, true) => self.push(ChangeKind::Added(try!(new.get(field)))),
+                (true, true) => try!(self.diff_value(try!(old.get(field)), try!(new.get(field)))),
+            }
+            self.path.pop();
+        }
+        Ok(())
+    }
+
+    fn diff_list(&mut self, old : dynamic_list::Reader<'a>, new : dynamic_list::Reader<'a>)
+                 -> Result<()>
+    {
+        let old_len = old.len();
+        let new_len = new.len();
+        let common = ::std::cmp::min(old_len, new_len);
+
+        // Match up elements from each end. The first mismatch at either end is kept, so that it
+        // need not be diffed again if those two elements end up paired below.
+        let mut prefix = 0;
+        let mut prefix_changes = Vec::new();
+        while prefix < common {
+            prefix_changes = try!(self.diff_element(prefix, try!(old.get(prefix)),
+                                                    try!(new.get(prefix))));
+            if !prefix_changes.is_empty() { break; }
+            prefix += 1;
+        }
+        if self.stop_at_first && !prefix_changes.is_empty() {
+            self.changes.append(&mut prefix_changes);
+            return Ok(());
+        }
+
+        // With equal lengths, the element pair that stopped the prefix would be compared again
+        // as the last candidate for the suffix, and it is already known to differ.
+        let suffix_limit = if old_len == new_len && prefix < common {
+            common - prefix - 1
+        } else {
+            common - prefix
+        };
+        let mut suffix = 0;
+        let mut suffix_changes = Vec::new();
+        while suffix < suffix_limit {
+            suffix_changes = try!(self.diff_element(old_len - 1 - suffix,
+                                                    try!(old.get(old_len - 1 - suffix)),
+                                                    try!(new.get(new_len - 1 - suffix))));
+            if !suffix_changes.is_empty() { break; }
+            suffix += 1;
+        }
+
+        // Whatever is left in the middle is paired up by position, with any excess on one side
+        // counted as removals or additions.
+        let old_end = old_len - suffix;
+        let new_end = new_len - suffix;
+        let paired = ::std::cmp::min(old_end, new_end) - prefix;
+        for i in prefix..(prefix + paired) {
+            if i == prefix && prefix < common {
+                self.changes.append(&mut prefix_changes);
+            } else if i + 1 == old_end && old_len == new_len && suffix < suffix_limit {
+                self.changes.append(&mut suffix_changes);
+            } else {
+                let mut changes = try!(self.diff_element(i, try!(old.get(i)), try!(new.get(i))));
+                self.changes.append(&mut changes);
+            }
+        }
+        for i in (prefix + paired)..old_end {
+            if self.done() { break; }
+            self.path.push(PathElement::Index(i));
+            self.push(ChangeKind::Removed(try!(old.get(i))));
+            self.path.pop();
+        }
+        for i in (prefix + paired)..new_end {
+            if self.done() { break; }
+            self.path.push(PathElement::Index(i));
+            self.push(ChangeKind::Added(try!(new.get(i))));
+            self.path.pop();
+        }
+        Ok(())
+    }
+
+    fn diff_value(&mut self, old : dynamic_value::Reader<'a>, new : dynamic_value::Reader<'a>)
+                  -> Result<()>
+    {
+        use dynamic_value::Reader::*;
+        let same = match (old, new) {
+            (Struct(a), Struct(b)) => return self.diff_struct(a, b),
+            (List(a), List(b)) => return self.diff_list(a, b),
+            (Void, Void) => true,
+            (Bool(a), Bool(b)) => a == b,
+            (Int8(a), Int8(b)) => a == b,
+            (Int16(a), Int16(b)) => a == b,
+            (Int32(a), Int32(b)) => a == b,
+            (Int64(a), Int64(b)) => a == b,
+            (UInt8(a), UInt8(b)) => a == b,
+            (UInt16(a), UInt16(b)) => a == b,
+            (UInt32(a), UInt32(b)) => a == b,
+            (UInt64(a), UInt64(b)) => a == b,
+            // Compare the bits so that a NaN is not reported as changed on every diff.
+            (Float32(a), Float32(b)) => a.to_bits() == b.to_bits(),
+            (Float64(a), Float64(b)) => a.to_bits() == b.to_bits(),
+            (Enum(a), Enum(b)) => a.get_value() == b.get_value(),
+            (Text(a), Text(b)) => a == b,
+            (Data(a), Data(b)) => a == b,
+            (AnyPointer(a), AnyPointer(b)) => a.is_null() == b.is_null(),
+            _ => false,
+        };
+        if !same {
+            self.push(ChangeKind::Modified(old, new));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dynamic_struct;
+    use dynamic_value;
+    use message::{MallocMessageBuilder, MessageBuilder, MessageReader, ReaderOptions,
+                  SegmentArrayMessageReader};
+    use parse_text::parse_message;
+    use private::layout::PointerReader;
+    use private::test_schema::{test_schema, FOO};
+    use schema::SchemaLoader;
+    use Word;
+    use super::{diff, equal};
+
+    fn build(loader : &SchemaLoader, text : &str) -> Vec<Word> {
+        let mut message = MallocMessageBuilder::new_default();
+        parse_message(text, loader.get_struct_schema(FOO).unwrap(), &mut message).unwrap();
+        let segments = message.get_segments_for_output();
+        assert_eq!(segments.len(), 1);
+        segments[0].to_vec()
+    }
+
+    fn root<'a>(loader : &'a SchemaLoader, reader : &'a SegmentArrayMessageReader<'a>)
+                -> dynamic_struct::Reader<'a> {
+        let root : PointerReader = reader.get_root().unwrap();
+        dynamic_struct::Reader::new(root.get_struct(::std::ptr::null()).unwrap(),
+                                    loader.get_struct_schema(FOO).unwrap())
+    }
+
+    fn changes(old : &str, new : &str) -> Vec<String> {
+        let loader = test_schema();
+        let (old, new) = (build(&loader, old), build(&loader, new));
+        let (old, new) = ([&old[..]], [&new[..]]);
+        let old = SegmentArrayMessageReader::new(&old, ReaderOptions::new());
+        let new = SegmentArrayMessageReader::new(&new, ReaderOptions::new());
+        let result : Vec<String> = diff(root(&loader, &old), root(&loader, &new)).unwrap()
+            .iter().map(|change| change.to_string()).collect();
+        assert_eq!(equal(dynamic_value::Reader::Struct(root(&loader, &old)),
+                         dynamic_value::Reader::Struct(root(&loader, &new))).unwrap(),
+                   result.is_empty());
+        result
+    }
+
+    #[test]
+    fn list_insertions_and_removals() {
+        assert_eq!(changes("nums = [1, 2, 3]", "nums = [0, 1, 2, 3]"), ["nums[0]: added 0"]);
+        assert_eq!(changes("nums = [1, 2, 3]", "nums = [1, 9, 2, 3]"), ["nums[1]: added 9"]);
+        assert_eq!(changes("nums = [1, 2, 3]", "nums = [1, 2, 3, 4]"), ["nums[3]: added 4"]);
+        assert_eq!(changes("nums = [1, 2, 3]", "nums = [1, 3]"), ["nums[1]: removed 2"]);
+        assert_eq!(changes("nums = [1, 2, 3]", "nums = [2, 3]"), ["nums[0]: removed 1"]);
+        assert_eq!(changes("nums = [1, 2]", "nums = [1, 2, 3, 4]"),
+                   ["nums[2]: added 3", "nums[3]: added 4"]);
+        assert_eq!(changes("nums = [1, 2, 3, 4]", "nums = [1, 2]"),
+                   ["nums[2]: removed 3", "nums[3]: removed 4"]);
+        assert_eq!(changes("nums = []", "nums = [5]"), ["nums[0]: added 5"]);
+    }
+
+    #[test]
+    fn equal_length_lists() {
+        assert!(changes("nums = [1, 2, 3]", "nums = [1, 2, 3]").is_empty());
+        assert_eq!(changes("nums = [1, 2, 3]", "nums = [0, 2, 3]"), ["nums[0]: 1 -> 0"]);
+        assert_eq!(changes("nums = [1, 2, 3]", "nums = [1, 0, 3]"), ["nums[1]: 2 -> 0"]);
+        assert_eq!(changes("nums = [1, 2, 3]", "nums = [1, 2, 0]"), ["nums[2]: 3 -> 0"]);
+        assert_eq!(changes("nums = [1]", "nums = [2]"), ["nums[0]: 1 -> 2"]);
+        assert_eq!(changes("nums = [1, 2, 3]", "nums = [4, 2, 5]"),
+                   ["nums[0]: 1 -> 4", "nums[2]: 3 -> 5"]);
+        assert_eq!(changes("nums = [1, 2, 3, 4]", "nums = [1, 5, 6, 4]"),
+                   ["nums[1]: 2 -> 5", "nums[2]: 3 -> 6"]);
+        assert_eq!(changes("items = [(id = 1, label = \"a\"), (id = 2)]",
+                           "items = [(id = 7, label = \"b\"), (id = 8)]"),
+                   ["items[0].id: 1 -> 7", "items[0].label: \"a\" -> \"b\"", "items[1].id: 2 -> 8"]);
+    }
+
+    #[test]
+    fn union_switch() {
+        assert_eq!(changes("uInt = 3", "uText = \"x\""),
+                   ["uInt: removed 3", "uText: added \"x\""]);
+        assert_eq!(changes("uInt = 3", "uInt = 4"), ["uInt: 3 -> 4"]);
+    }
+
+    #[test]
+    fn equal_stops_at_first_difference() {
+        let loader = test_schema();
+        let old = build(&loader, "a = 1, items = [(id = 1)]");
+        let mut new = build(&loader, "a = 2, items = [(id = 1)]");
+        // Point `items` (the second pointer, after the root pointer and four data words) far
+        // outside the segment, so that reading it fails.
+        let bytes : &mut [u8] = unsafe {
+            ::std::slice::from_raw_parts_mut(new.as_mut_ptr() as *mut u8, new.len() * 8)
+        };
+        for (i, b) in [0xfd, 0xff, 0x00, 0x00, 0x0f, 0x00, 0x00, 0x00].iter().enumerate() {
+            bytes[6 * 8 + i] = *b;
+        }
+
+        let (old, new) = ([&old[..]], [&new[..]]);
+        let old = SegmentArrayMessageReader::new(&old, ReaderOptions::new());
+        let new = SegmentArrayMessageReader::new(&new, ReaderOptions::new());
+        assert!(diff(root(&loader, &old), root(&loader, &new)).is_err());
+        assert!(!equal(dynamic_value::Reader::Struct(root(&loader, &old)),
+                       dynamic_value::Reader::Struct(root(&loader, &new))).unwrap());
+    }
+}
@@ -0,0 +1,221 @@
+// Copyright (c) 2013-2015 Sandstorm Development Group, Inc. and contributors
+// Licensed under the MIT License:
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Lists whose element type is only known at runtime.
+
+use any_pointer;
+use dynamic_struct;
+use dynamic_value::{self, Enum};
+use private::layout::{ListBuilder, ListReader, PrimitiveElement};
+use schema::{SchemaLoader, Type};
+use traits::{IndexMove, IntoInternalStructReader, ListIter};
+use {Error, Result};
+
+#[derive(Clone, Copy)]
+pub struct Reader<'a> {
+    loader : &'a SchemaLoader,
+    element_type : &'a Type,
+    reader : ListReader<'a>,
+}
+
+impl <'a> Reader<'a> {
+    pub fn new(reader : ListReader<'a>, element_type : &'a Type,
+               loader : &'a SchemaLoader) -> Reader<'a> {
+        Reader { loader : loader, element_type : element_type, reader : reader }
+    }
+
+    pub fn len(&self) -> u32 { self.reader.len() }
+
+    pub fn get_element_type(&self) -> &'a Type { self.element_type }
+
+    pub fn into_internal_list_reader(self) -> ListReader<'a> { self.reader }
+
+    pub fn get(&self, index : u32) -> Result<dynamic_value::Reader<'a>> {
+        assert!(index < self.len());
+        let reader = &self.reader;
+        Ok(match *self.element_type {
+            Type::Void => dynamic_value::Reader::Void,
+            Type::Bool => dynamic_value::Reader::Bool(PrimitiveElement::get(reader, index)),
+            Type::Int8 => dynamic_value::Reader::Int8(PrimitiveElement::get(reader, index)),
+            Type::Int16 => dynamic_value::Reader::Int16(PrimitiveElement::get(reader, index)),
+            Type::Int32 => dynamic_value::Reader::Int32(PrimitiveElement::get(reader, index)),
+            Type::Int64 => dynamic_value::Reader::Int64(PrimitiveElement::get(reader, index)),
+            Type::UInt8 => dynamic_value::Reader::UInt8(PrimitiveElement::get(reader, index)),
+            Type::UInt16 => dynamic_value::Reader::UInt16(PrimitiveElement::get(reader, index)),
+            Type::UInt32 => dynamic_value::Reader::UInt32(PrimitiveElement::get(reader, index)),
+            Type::UInt64 => dynamic_value::Reader::UInt64(PrimitiveElement::get(reader, index)),
+            Type::Float32 => dynamic_value::Reader::Float32(PrimitiveElement::get(reader, index)),
+            Type::Float64 => dynamic_value::Reader::Float64(PrimitiveElement::get(reader, index)),
+            Type::Enum(id) => dynamic_value::Reader::Enum(Enum::new(
+                PrimitiveElement::get(reader, index), try!(self.loader.get_enum_schema(id)))),
+            Type::Struct(id) => dynamic_value::Reader::Struct(dynamic_struct::Reader::new(
+                reader.get_struct_element(index), try!(self.loader.get_struct_schema(id)))),
+            ref t => try!(dynamic_value::read_pointer(
+                self.loader, t, reader.get_pointer_element(index), None)),
+        })
+    }
+
+    pub fn iter(self) -> ListIter<Reader<'a>, Result<dynamic_value::Reader<'a>>> {
+        let l = self.len();
+        ListIter::new(self, l)
+    }
+}
+
+impl <'a> IndexMove<u32, Result<dynamic_value::Reader<'a>>> for Reader<'a> {
+    fn index_move(&self, index : u32) -> Result<dynamic_value::Reader<'a>> {
+        self.get(index)
+    }
+}
+
+impl <'a> ::std::iter::IntoIterator for Reader<'a> {
+    type Item = Result<dynamic_value::Reader<'a>>;
+    type IntoIter = ListIter<Reader<'a>, Self::Item>;
+
+    fn into_iter(self) -> ListIter<Reader<'a>, Self::Item> {
+        self.iter()
+    }
+}
+
+pub struct Builder<'a> {
+    loader : &'a SchemaLoader,
+    element_type : &'a Type,
+    builder : ListBuilder<'a>,
+}
+
+macro_rules! set_element(
+    ($builder:expr, $index:expr, $element_type:expr, $value:expr, $converted:expr) => (
+        match $converted {
+            Some(v) => PrimitiveElement::set($builder, $index, v),
+            None => return Err(dynamic_value::type_mismatch($element_type, &$value)),
+        }
+    )
+);
+
+impl <'a> Builder<'a> {
+    pub fn new(builder : ListBuilder<'a>, element_type : &'a Type,
+               loader : &'a SchemaLoader) -> Builder<'a> {
+        Builder { loader : loader, element_type : element_type, builder : builder }
+    }
+
+    pub fn len(&self) -> u32 { self.builder.len() }
+
+    pub fn get_element_type(&self) -> &'a Type { self.element_type }
+
+    pub fn as_reader(&self) -> Reader<'a> {
+        Reader::new(self.builder.as_reader(), self.element_type, self.loader)
+    }
+
+    /// Shrinks the list to `new_len` elements. See `ListBuilder::truncate()`.
+    ///
+    /// Unsafe because `dynamic_struct::Builder` hands out any number of handles on the same list,
+    /// and none of them, nor any reader or element obtained through them, may be used afterwards.
+    pub unsafe fn truncate(&mut self, new_len : u32) -> Result<()> {
+        self.builder.truncate(new_len)
+    }
+
+    /// Extends the list to `new_len` elements. See `ListBuilder::grow()`. Unsafe for the same
+    /// reason as `truncate()`.
+    pub unsafe fn grow(&mut self, new_len : u32) -> Result<()> {
+        self.builder.grow(new_len)
+    }
+
+    pub fn get<'b>(&'b self, index : u32) -> Result<dynamic_value::Builder<'b>> {
+        assert!(index < self.len());
+        Ok(match *self.element_type {
+            Type::Struct(id) => dynamic_value::Builder::Struct(dynamic_struct::Builder::new(
+                self.builder.get_struct_element(index), try!(self.loader.get_struct_schema(id)))),
+            ref t if t.is_pointer() => try!(dynamic_value::get_pointer(
+                self.loader, t, self.builder.get_pointer_element(index), None)),
+            _ => match try!(self.as_reader().get(index)) {
+                dynamic_value::Reader::Void => dynamic_value::Builder::Void,
+                dynamic_value::Reader::Bool(v) => dynamic_value::Builder::Bool(v),
+                dynamic_value::Reader::Int8(v) => dynamic_value::Builder::Int8(v),
+                dynamic_value::Reader::Int16(v) => dynamic_value::Builder::Int16(v),
+                dynamic_value::Reader::Int32(v) => dynamic_value::Builder::Int32(v),
+                dynamic_value::Reader::Int64(v) => dynamic_value::Builder::Int64(v),
+                dynamic_value::Reader::UInt8(v) => dynamic_value::Builder::UInt8(v),
+                dynamic_value::Reader::UInt16(v) => dynamic_value::Builder::UInt16(v),
+                dynamic_value::Reader::UInt32(v) => dynamic_value::Builder::UInt32(v),
+                dynamic_value::Reader::UInt64(v) => dynamic_value::Builder::UInt64(v),
+                dynamic_value::Reader::Float32(v) => dynamic_value::Builder::Float32(v),
+                dynamic_value::Reader::Float64(v) => dynamic_value::Builder::Float64(v),
+                dynamic_value::Reader::Enum(v) => dynamic_value::Builder::Enum(v),
+                _ => unreachable!(),
+            },
+        })
+    }
+
+    /// Sets an element, converting numbers to the element type if they fit. Struct elements are
+    /// overwritten with a copy of `value`, truncating any fields that do not fit.
+    pub fn set(&self, index : u32, value : dynamic_value::Reader) -> Result<()> {
+        assert!(index < self.len());
+        let builder = &self.builder;
+        match *self.element_type {
+            Type::Void => match value {
+                dynamic_value::Reader::Void => (),
+                _ => return Err(dynamic_value::type_mismatch(self.element_type, &value)),
+            },
+            Type::Bool => match value {
+                dynamic_value::Reader::Bool(b) => PrimitiveElement::set(builder, index, b),
+                _ => return Err(dynamic_value::type_mismatch(self.element_type, &value)),
+            },
+            Type::Int8 => set_element!(builder, index, self.element_type, value, value.to_i8()),
+            Type::Int16 => set_element!(builder, index, self.element_type, value, value.to_i16()),
+            Type::Int32 => set_element!(builder, index, self.element_type, value, value.to_i32()),
+            Type::Int64 => set_element!(builder, index, self.element_type, value, value.as_i64()),
+            Type::UInt8 => set_element!(builder, index, self.element_type, value, value.to_u8()),
+            Type::UInt16 => set_element!(builder, index, self.element_type, value, value.to_u16()),
+            Type::UInt32 => set_element!(builder, index, self.element_type, value, value.to_u32()),
+            Type::UInt64 => set_element!(builder, index, self.element_type, value, value.as_u64()),
+            Type::Float32 => set_element!(builder, index, self.element_type, value,
+                                          value.as_f64().map(|v| v as f32)),
+            Type::Float64 => set_element!(builder, index, self.element_type, value, value.as_f64()),
+            Type::Enum(id) => PrimitiveElement::set(
+                builder, index, try!(dynamic_value::enum_value(self.loader, id, value))),
+            Type::Struct(id) => match value {
+                dynamic_value::Reader::Struct(s) if s.get_schema().get_schema().get_id() == id =>
+                    try!(builder.get_struct_element(index).copy_content_from(
+                        &s.into_internal_struct_reader())),
+                _ => return Err(dynamic_value::type_mismatch(self.element_type, &value)),
+            },
+            ref t => try!(dynamic_value::set_pointer(t, builder.get_pointer_element(index), value)),
+        }
+        Ok(())
+    }
+
+    /// Initializes a list, text or data element to `size` elements or bytes.
+    pub fn init<'b>(&'b self, index : u32, size : u32) -> Result<dynamic_value::Builder<'b>> {
+        assert!(index < self.len());
+        match *self.element_type {
+            Type::List(_) | Type::Text | Type::Data | Type::AnyPointer =>
+                dynamic_value::init_pointer(self.loader, self.element_type,
+                                            self.builder.get_pointer_element(index), size),
+            _ => Err(Error::new_decode_error("init() requires a list of lists, text or data.",
+                                             None)),
+        }
+    }
+
+    /// Gets an AnyPointer element.
+    pub fn get_any_pointer<'b>(&'b self, index : u32) -> any_pointer::Builder<'b> {
+        assert!(index < self.len());
+        any_pointer::Builder::new(self.builder.get_pointer_element(index))
+    }
+}
@@ -0,0 +1,452 @@
+// Copyright (c) 2013-2015 Sandstorm Development Group, Inc. and contributors
+// Licensed under the MIT License:
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Structs whose type is only known at runtime, similar to `DynamicStruct` in the C++
+//! implementation.
+
+use dynamic_value;
+use private::layout::{StructBuilder, StructReader};
+use schema::{Field, StructSchema, Type};
+use schema::proto::FieldKind;
+use traits::IntoInternalStructReader;
+use {Error, NotInSchema, Result};
+
+fn check_field(schema : StructSchema, field : Field) -> Result<()> {
+    if field.get_containing_struct().get_proto().id == schema.get_proto().id {
+        Ok(())
+    } else {
+        Err(Error::new_decode_error("Field is not a member of this struct.",
+                                    Some(field.get_name().to_string())))
+    }
+}
+
+fn find_field<'a>(schema : StructSchema<'a>, name : &str) -> Result<Field<'a>> {
+    match schema.find_field_by_name(name) {
+        Some(field) => Ok(field),
+        None => Err(Error::new_decode_error("No such field.", Some(name.to_string()))),
+    }
+}
+
+fn which<'a>(schema : StructSchema<'a>, reader : &StructReader) -> Result<Option<Field<'a>>> {
+    if schema.get_discriminant_count() == 0 {
+        return Ok(None);
+    }
+    let discriminant = reader.get_data_field::<u16>(schema.get_discriminant_offset() as usize);
+    match schema.get_field_by_discriminant(discriminant) {
+        Some(field) => Ok(Some(field)),
+        None => Err(::std::convert::From::from(NotInSchema(discriminant))),
+    }
+}
+
+fn is_set_in_union(schema : StructSchema, reader : &StructReader, field : Field) -> bool {
+    match field.get_discriminant_value() {
+        None => true,
+        Some(d) => reader.get_data_field::<u16>(schema.get_discriminant_offset() as usize) == d,
+    }
+}
+
+fn check_active(schema : StructSchema, reader : &StructReader, field : Field) -> Result<()> {
+    try!(check_field(schema, field));
+    if is_set_in_union(schema, reader, field) {
+        Ok(())
+    } else {
+        Err(Error::new_decode_error("Field is not the active member of its union.",
+                                    Some(field.get_name().to_string())))
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Reader<'a> {
+    schema : StructSchema<'a>,
+    reader : StructReader<'a>,
+}
+
+impl <'a> Reader<'a> {
+    pub fn new(reader : StructReader<'a>, schema : StructSchema<'a>) -> Reader<'a> {
+        Reader { schema : schema, reader : reader }
+    }
+
+    pub fn get_schema(&self) -> StructSchema<'a> { self.schema }
+
+    /// The active member of the unnamed union, or `None` if the struct has no union.
+    pub fn which(&self) -> Result<Option<Field<'a>>> {
+        which(self.schema, &self.reader)
+    }
+
+    /// Returns false if `field` is an inactive union member or a null pointer.
+    pub fn has(&self, field : Field<'a>) -> Result<bool> {
+        try!(check_field(self.schema, field));
+        if !is_set_in_union(self.schema, &self.reader, field) {
+            return Ok(false);
+        }
+        match field.get_proto().kind {
+            FieldKind::Slot { offset, ref type_, .. } if type_.is_pointer() =>
+                Ok(!self.reader.get_pointer_field(offset as usize).is_null()),
+            _ => Ok(true),
+        }
+    }
+
+    /// The fields for which `has()` returns true, in the order in which they were declared.
+    pub fn get_present_fields(&self) -> Result<Vec<Field<'a>>> {
+        let mut fields = Vec::new();
+        for field in self.schema.get_fields_in_code_order().into_iter() {
+            if try!(self.has(field)) {
+                fields.push(field);
+            }
+        }
+        Ok(fields)
+    }
+
+    pub fn get(&self, field : Field<'a>) -> Result<dynamic_value::Reader<'a>> {
+        try!(check_active(self.schema, &self.reader, field));
+        let loader = self.schema.get_schema().get_loader();
+        match field.get_proto().kind {
+            FieldKind::Slot { offset, ref type_, ref default_value, .. } => {
+                if type_.is_pointer() {
+                    dynamic_value::read_pointer(loader, type_,
+                                                self.reader.get_pointer_field(offset as usize),
+                                                Some(default_value))
+                } else {
+                    dynamic_value::read_data(loader, type_, &self.reader, offset,
+                                             Some(default_value))
+                }
+            }
+            FieldKind::Group { type_id } => {
+                let group = try!(loader.get_struct_schema(type_id));
+                Ok(dynamic_value::Reader::Struct(Reader::new(self.reader, group)))
+            }
+        }
+    }
+
+    pub fn get_named(&self, name : &str) -> Result<dynamic_value::Reader<'a>> {
+        self.get(try!(find_field(self.schema, name)))
+    }
+}
+
+impl <'a> IntoInternalStructReader<'a> for Reader<'a> {
+    fn into_internal_struct_reader(self) -> StructReader<'a> {
+        self.reader
+    }
+}
+
+pub struct Builder<'a> {
+    schema : StructSchema<'a>,
+    builder : StructBuilder<'a>,
+}
+
+impl <'a> Builder<'a> {
+    pub fn new(builder : StructBuilder<'a>, schema : StructSchema<'a>) -> Builder<'a> {
+        Builder { schema : schema, builder : builder }
+    }
+
+    pub fn get_schema(&self) -> StructSchema<'a> { self.schema }
+
+    pub fn as_reader(&self) -> Reader<'a> {
+        Reader::new(self.builder.as_reader(), self.schema)
+    }
+
+    pub fn which(&self) -> Result<Option<Field<'a>>> {
+        which(self.schema, &self.builder.as_reader())
+    }
+
+    pub fn has(&self, field : Field<'a>) -> Result<bool> {
+        self.as_reader().has(field)
+    }
+
+    fn set_in_union(&self, field : Field<'a>) {
+        if let Some(d) = field.get_discriminant_value() {
+            self.builder.set_data_field::<u16>(self.schema.get_discriminant_offset() as usize, d);
+        }
+    }
+
+    pub fn get(&self, field : Field<'a>) -> Result<dynamic_value::Builder<'a>> {
+        try!(check_active(self.schema, &self.builder.as_reader(), field));
+        let loader = self.schema.get_schema().get_loader();
+        match field.get_proto().kind {
+            FieldKind::Slot { offset, ref type_, ref default_value, .. } => {
+                if type_.is_pointer() {
+                    dynamic_value::get_pointer(loader, type_,
+                                               self.builder.get_pointer_field(offset as usize),
+                                               Some(default_value))
+                } else {
+                    dynamic_value::get_data(loader, type_, &self.builder, offset,
+                                            Some(default_value))
+                }
+            }
+            FieldKind::Group { type_id } => {
+                let group = try!(loader.get_struct_schema(type_id));
+                Ok(dynamic_value::Builder::Struct(Builder::new(self.builder, group)))
+            }
+        }
+    }
+
+    pub fn get_named(&self, name : &str) -> Result<dynamic_value::Builder<'a>> {
+        self.get(try!(find_field(self.schema, name)))
+    }
+
+    /// Sets a field, making it the active member of its union. Numbers are converted to the
+    /// field's type if they fit, and enum fields also accept enumerant names as text.
+    pub fn set(&self, field : Field<'a>, value : dynamic_value::Reader) -> Result<()> {
+        try!(check_field(self.schema, field));
+        let loader = self.schema.get_schema().get_loader();
+        match field.get_proto().kind {
+            FieldKind::Slot { offset, ref type_, ref default_value, .. } => {
+                if type_.is_pointer() {
+                    try!(dynamic_value::set_pointer(
+                        type_, self.builder.get_pointer_field(offset as usize), value));
+                } else {
+                    try!(dynamic_value::write_data(loader, type_, &self.builder, offset,
+                                                   Some(default_value), value));
+                }
+            }
+            FieldKind::Group { .. } => {
+                return Err(Error::new_decode_error("Cannot set() a group; use init() instead.",
+                                                   Some(field.get_name().to_string())));
+            }
+        }
+        self.set_in_union(field);
+        Ok(())
+    }
+
+    pub fn set_named(&self, name : &str, value : dynamic_value::Reader) -> Result<()> {
+        self.set(try!(find_field(self.schema, name)), value)
+    }
+
+    /// Initializes a struct, group or AnyPointer field, making it the active member of its
+    /// union. Groups are cleared.
+    pub fn init(&self, field : Field<'a>) -> Result<dynamic_value::Builder<'a>> {
+        try!(check_field(self.schema, field));
+        let loader = self.schema.get_schema().get_loader();
+        let result = match field.get_proto().kind {
+            FieldKind::Slot { offset, ref type_, .. } => match *type_ {
+                Type::Struct(_) | Type::AnyPointer => try!(dynamic_value::init_pointer(
+                    loader, type_, self.builder.get_pointer_field(offset as usize), 0)),
+                _ => return Err(Error::new_decode_error(
+                    "init() requires a struct, group or AnyPointer field.",
+                    Some(field.get_name().to_string()))),
+            },
+            FieldKind::Group { type_id } => {
+                let group = try!(loader.get_struct_schema(type_id));
+                try!(clear_group(&self.builder, group));
+                dynamic_value::Builder::Struct(Builder::new(self.builder, group))
+            }
+        };
+        self.set_in_union(field);
+        Ok(result)
+    }
+
+    pub fn init_named(&self, name : &str) -> Result<dynamic_value::Builder<'a>> {
+        self.init(try!(find_field(self.schema, name)))
+    }
+
+    /// Initializes a list, text or data field to `size` elements or bytes, making it the active
+    /// member of its union.
+    pub fn init_sized(&self, field : Field<'a>, size : u32) -> Result<dynamic_value::Builder<'a>> {
+        try!(check_field(self.schema, field));
+        let loader = self.schema.get_schema().get_loader();
+        let result = match field.get_proto().kind {
+            FieldKind::Slot { offset, ref type_, .. } => match *type_ {
+                Type::List(_) | Type::Text | Type::Data => try!(dynamic_value::init_pointer(
+                    loader, type_, self.builder.get_pointer_field(offset as usize), size)),
+                _ => return Err(Error::new_decode_error(
+                    "init_sized() requires a list, text or data field.",
+                    Some(field.get_name().to_string()))),
+            },
+            FieldKind::Group { .. } => return Err(Error::new_decode_error(
+                "init_sized() requires a list, text or data field.",
+                Some(field.get_name().to_string()))),
+        };
+        self.set_in_union(field);
+        Ok(result)
+    }
+
+    /// Resets a field to its default value, making it the active member of its union.
+    pub fn clear(&self, field : Field<'a>) -> Result<()> {
+        try!(check_field(self.schema, field));
+        try!(clear_field(&self.builder, field));
+        self.set_in_union(field);
+        Ok(())
+    }
+}
+
+fn clear_field(builder : &StructBuilder, field : Field) -> Result<()> {
+    match field.get_proto().kind {
+        FieldKind::Slot { offset, ref type_, .. } => {
+            let offset = offset as usize;
+            match *type_ {
+                Type::Void => (),
+                Type::Bool => builder.set_bool_field(offset, false),
+                Type::Int8 | Type::UInt8 => builder.set_data_field::<u8>(offset, 0),
+                Type::Int16 | Type::UInt16 | Type::Enum(_) => builder.set_data_field::<u16>(offset, 0),
+                Type::Int32 | Type::UInt32 | Type::Float32 => builder.set_data_field::<u32>(offset, 0),
+                Type::Int64 | Type::UInt64 | Type::Float64 => builder.set_data_field::<u64>(offset, 0),
+                _ => builder.get_pointer_field(offset).clear(),
+            }
+        }
+        FieldKind::Group { type_id } => {
+            let loader = field.get_containing_struct().get_schema().get_loader();
+            try!(clear_group(builder, try!(loader.get_struct_schema(type_id))));
+        }
+    }
+    Ok(())
+}
+
+fn clear_group(builder : &StructBuilder, group : StructSchema) -> Result<()> {
+    for field in group.get_fields().into_iter() {
+        try!(clear_field(builder, field));
+    }
+    if group.get_discriminant_count() > 0 {
+        builder.set_data_field::<u16>(group.get_discriminant_offset() as usize, 0);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use dynamic_value;
+    use message::{MallocMessageBuilder, MessageBuilder};
+    use private::layout::PointerBuilder;
+    use private::test_schema::{test_schema, FOO};
+    use traits::IntoInternalStructReader;
+    use super::Builder;
+
+    fn init_root<'a>(message : &'a mut MallocMessageBuilder,
+                     schema : ::schema::StructSchema<'a>) -> Builder<'a> {
+        let root : PointerBuilder = message.get_root_internal().init_as();
+        Builder::new(root.init_struct(dynamic_value::struct_size(schema)), schema)
+    }
+
+    fn show(builder : &Builder, name : &str) -> String {
+        format!("{}", builder.as_reader().get_named(name).unwrap())
+    }
+
+    #[test]
+    fn get_and_set_by_name() {
+        let loader = test_schema();
+        let schema = loader.get_struct_schema(FOO).unwrap();
+        let mut message = MallocMessageBuilder::new_default();
+        let foo = init_root(&mut message, schema);
+
+        foo.set_named("b", dynamic_value::Reader::Bool(true)).unwrap();
+        foo.set_named("nums", dynamic_value::Reader::Text("ignored")).unwrap_err();
+        match foo.init_sized(schema.find_field_by_name("nums").unwrap(), 2).unwrap() {
+            dynamic_value::Builder::List(list) => {
+                list.set(0, dynamic_value::Reader::UInt8(3)).unwrap();
+                list.set(1, dynamic_value::Reader::Int64(-4)).unwrap();
+                assert!(list.set(1, dynamic_value::Reader::Int64(1 << 20)).is_err());
+            }
+            _ => panic!("expected a list"),
+        }
+        assert_eq!(show(&foo, "b"), "true");
+        assert_eq!(show(&foo, "nums"), "[3, -4]");
+        assert!(foo.get_named("nonexistent").is_err());
+        assert!(foo.set_named("a", dynamic_value::Reader::Int32(-1)).is_err());
+    }
+
+    #[test]
+    fn defaults_are_xored() {
+        let loader = test_schema();
+        let schema = loader.get_struct_schema(FOO).unwrap();
+        let mut message = MallocMessageBuilder::new_default();
+        let foo = init_root(&mut message, schema);
+
+        // A zeroed struct reads as the defaults.
+        assert_eq!(show(&foo, "a"), "5");
+        assert_eq!(show(&foo, "name"), "\"anon\"");
+
+        foo.set_named("a", dynamic_value::Reader::UInt32(7)).unwrap();
+        assert_eq!(show(&foo, "a"), "7");
+        assert_eq!(foo.as_reader().into_internal_struct_reader().get_data_field::<u32>(0), 7 ^ 5);
+        foo.set_named("a", dynamic_value::Reader::UInt32(5)).unwrap();
+        assert_eq!(foo.as_reader().into_internal_struct_reader().get_data_field::<u32>(0), 0);
+    }
+
+    #[test]
+    fn union_members_switch() {
+        let loader = test_schema();
+        let schema = loader.get_struct_schema(FOO).unwrap();
+        let mut message = MallocMessageBuilder::new_default();
+        let foo = init_root(&mut message, schema);
+        let u_int = schema.find_field_by_name("uInt").unwrap();
+        let u_text = schema.find_field_by_name("uText").unwrap();
+
+        foo.set(u_int, dynamic_value::Reader::Int64(3)).unwrap();
+        assert_eq!(foo.which().unwrap().unwrap().get_name(), "uInt");
+
+        match foo.init_sized(u_text, 2).unwrap() {
+            dynamic_value::Builder::Text(mut text) => text.push_str("hi"),
+            _ => panic!("expected text"),
+        }
+        assert_eq!(foo.which().unwrap().unwrap().get_name(), "uText");
+        assert!(!foo.has(u_int).unwrap());
+        assert!(foo.get(u_int).is_err());
+        assert_eq!(show(&foo, "uText"), "\"hi\"");
+
+        foo.set(u_int, dynamic_value::Reader::Int64(4)).unwrap();
+        assert_eq!(foo.which().unwrap().unwrap().get_name(), "uInt");
+        assert!(foo.as_reader().get(u_text).is_err());
+    }
+
+    #[test]
+    fn init_group_clears_it() {
+        let loader = test_schema();
+        let schema = loader.get_struct_schema(FOO).unwrap();
+        let mut message = MallocMessageBuilder::new_default();
+        let foo = init_root(&mut message, schema);
+
+        match foo.get_named("grp").unwrap() {
+            dynamic_value::Builder::Struct(grp) => {
+                grp.set_named("x", dynamic_value::Reader::Int8(-3)).unwrap();
+                grp.set_named("y", dynamic_value::Reader::Float64(0.5)).unwrap();
+            }
+            _ => panic!("expected a group"),
+        }
+        foo.set_named("a", dynamic_value::Reader::UInt32(9)).unwrap();
+        assert_eq!(show(&foo, "grp"), "(x = -3, y = 0.5)");
+
+        match foo.init_named("grp").unwrap() {
+            dynamic_value::Builder::Struct(grp) =>
+                assert_eq!(format!("{}", grp.as_reader()), "(x = 0, y = 0)"),
+            _ => panic!("expected a group"),
+        }
+        assert_eq!(show(&foo, "grp"), "(x = 0, y = 0)");
+        // Fields outside the group are untouched.
+        assert_eq!(show(&foo, "a"), "9");
+    }
+
+    #[test]
+    fn enums_by_name() {
+        let loader = test_schema();
+        let schema = loader.get_struct_schema(FOO).unwrap();
+        let mut message = MallocMessageBuilder::new_default();
+        let foo = init_root(&mut message, schema);
+
+        assert_eq!(show(&foo, "e"), "red");
+        foo.set_named("e", dynamic_value::Reader::Text("blue")).unwrap();
+        match foo.as_reader().get_named("e").unwrap() {
+            dynamic_value::Reader::Enum(e) => assert_eq!(e.get_value(), 2),
+            _ => panic!("expected an enum"),
+        }
+        assert!(foo.set_named("e", dynamic_value::Reader::Text("purple")).is_err());
+        foo.set_named("e", dynamic_value::Reader::UInt16(1)).unwrap();
+        assert_eq!(show(&foo, "e"), "green");
+    }
+}
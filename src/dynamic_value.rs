@@ -0,0 +1,502 @@
+// Copyright (c) 2013-2015 Sandstorm Development Group, Inc. and contributors
+// Licensed under the MIT License:
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Values whose type is only known at runtime.
+//!
+//! Together with `dynamic_struct` and `dynamic_list`, this module allows messages to be read and
+//! built using a `schema::SchemaLoader` instead of generated code.
+
+use any_pointer;
+use data;
+use dynamic_list;
+use dynamic_struct;
+use private::layout::{ElementSize, PointerBuilder, PointerReader, StructBuilder, StructReader,
+                      StructSize};
+use private::layout::{Void, Bit, Byte, TwoBytes, FourBytes, EightBytes, Pointer, InlineComposite};
+use schema::{EnumSchema, Enumerant, SchemaLoader, StructSchema, Type};
+use schema;
+use text;
+use {Error, Result};
+
+/// An enum value together with its schema.
+#[derive(Clone, Copy)]
+pub struct Enum<'a> {
+    value : u16,
+    schema : EnumSchema<'a>,
+}
+
+impl <'a> Enum<'a> {
+    pub fn new(value : u16, schema : EnumSchema<'a>) -> Enum<'a> {
+        Enum { value : value, schema : schema }
+    }
+
+    pub fn get_value(&self) -> u16 { self.value }
+
+    pub fn get_schema(&self) -> EnumSchema<'a> { self.schema }
+
+    /// Returns `None` if the value is not in the schema, e.g. because it was written by a newer
+    /// version of the protocol.
+    pub fn get_enumerant(&self) -> Option<Enumerant<'a>> {
+        self.schema.get_enumerant(self.value)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Reader<'a> {
+    Void,
+    Bool(bool),
+    Int8(i8),
+    Int16(i16),
+    Int32(i32),
+    Int64(i64),
+    UInt8(u8),
+    UInt16(u16),
+    UInt32(u32),
+    UInt64(u64),
+    Float32(f32),
+    Float64(f64),
+    Enum(Enum<'a>),
+    Text(text::Reader<'a>),
+    Data(data::Reader<'a>),
+    List(dynamic_list::Reader<'a>),
+    Struct(dynamic_struct::Reader<'a>),
+    AnyPointer(any_pointer::Reader<'a>),
+}
+
+macro_rules! to_signed(
+    ($name:ident, $t:ident) => (
+        pub fn $name(&self) -> Option<$t> {
+            self.as_i64().and_then(|v| {
+                if v >= ::std::$t::MIN as i64 && v <= ::std::$t::MAX as i64 { Some(v as $t) } else { None }
+            })
+        }
+    )
+);
+
+macro_rules! to_unsigned(
+    ($name:ident, $t:ident) => (
+        pub fn $name(&self) -> Option<$t> {
+            self.as_u64().and_then(|v| {
+                if v <= ::std::$t::MAX as u64 { Some(v as $t) } else { None }
+            })
+        }
+    )
+);
+
+impl <'a> Reader<'a> {
+    /// The value as an `i64`, if it is an integer that fits.
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Reader::Int8(v) => Some(v as i64),
+            Reader::Int16(v) => Some(v as i64),
+            Reader::Int32(v) => Some(v as i64),
+            Reader::Int64(v) => Some(v),
+            Reader::UInt8(v) => Some(v as i64),
+            Reader::UInt16(v) => Some(v as i64),
+            Reader::UInt32(v) => Some(v as i64),
+            Reader::UInt64(v) if v <= ::std::i64::MAX as u64 => Some(v as i64),
+            _ => None,
+        }
+    }
+
+    /// The value as a `u64`, if it is a non-negative integer.
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            Reader::UInt8(v) => Some(v as u64),
+            Reader::UInt16(v) => Some(v as u64),
+            Reader::UInt32(v) => Some(v as u64),
+            Reader::UInt64(v) => Some(v),
+            _ => match self.as_i64() {
+                Some(v) if v >= 0 => Some(v as u64),
+                _ => None,
+            },
+        }
+    }
+
+    /// The value as an `f64`, if it is a number. Integers are converted, possibly losing
+    /// precision.
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Reader::Float32(v) => Some(v as f64),
+            Reader::Float64(v) => Some(v),
+            Reader::UInt64(v) => Some(v as f64),
+            _ => self.as_i64().map(|v| v as f64),
+        }
+    }
+
+    to_signed!(to_i8, i8);
+    to_signed!(to_i16, i16);
+    to_signed!(to_i32, i32);
+    to_unsigned!(to_u8, u8);
+    to_unsigned!(to_u16, u16);
+    to_unsigned!(to_u32, u32);
+
+    /// A short name for the kind of value, for use in error messages.
+    pub fn kind_name(&self) -> &'static str {
+        match *self {
+            Reader::Void => "void",
+            Reader::Bool(_) => "bool",
+            Reader::Int8(_) | Reader::Int16(_) | Reader::Int32(_) | Reader::Int64(_) |
+            Reader::UInt8(_) | Reader::UInt16(_) | Reader::UInt32(_) | Reader::UInt64(_) => "integer",
+            Reader::Float32(_) | Reader::Float64(_) => "float",
+            Reader::Enum(_) => "enum",
+            Reader::Text(_) => "text",
+            Reader::Data(_) => "data",
+            Reader::List(_) => "list",
+            Reader::Struct(_) => "struct",
+            Reader::AnyPointer(_) => "AnyPointer",
+        }
+    }
+}
+
+macro_rules! from_primitive(
+    ($t:ty, $variant:ident) => (
+        impl <'a> ::std::convert::From<$t> for Reader<'a> {
+            fn from(value : $t) -> Reader<'a> { Reader::$variant(value) }
+        }
+    )
+);
+
+from_primitive!(bool, Bool);
+from_primitive!(i8, Int8);
+from_primitive!(i16, Int16);
+from_primitive!(i32, Int32);
+from_primitive!(i64, Int64);
+from_primitive!(u8, UInt8);
+from_primitive!(u16, UInt16);
+from_primitive!(u32, UInt32);
+from_primitive!(u64, UInt64);
+from_primitive!(f32, Float32);
+from_primitive!(f64, Float64);
+from_primitive!(Enum<'a>, Enum);
+from_primitive!(&'a str, Text);
+from_primitive!(&'a [u8], Data);
+from_primitive!(dynamic_list::Reader<'a>, List);
+from_primitive!(dynamic_struct::Reader<'a>, Struct);
+from_primitive!(any_pointer::Reader<'a>, AnyPointer);
+
+pub enum Builder<'a> {
+    Void,
+    Bool(bool),
+    Int8(i8),
+    Int16(i16),
+    Int32(i32),
+    Int64(i64),
+    UInt8(u8),
+    UInt16(u16),
+    UInt32(u32),
+    UInt64(u64),
+    Float32(f32),
+    Float64(f64),
+    Enum(Enum<'a>),
+    Text(text::Builder<'a>),
+    Data(data::Builder<'a>),
+    List(dynamic_list::Builder<'a>),
+    Struct(dynamic_struct::Builder<'a>),
+    AnyPointer(any_pointer::Builder<'a>),
+}
+
+impl <'a> Builder<'a> {
+    /// Converts a value that `get()` returned for a non-pointer type.
+    fn from_data(value : Reader<'a>) -> Builder<'a> {
+        match value {
+            Reader::Void => Builder::Void,
+            Reader::Bool(v) => Builder::Bool(v),
+            Reader::Int8(v) => Builder::Int8(v),
+            Reader::Int16(v) => Builder::Int16(v),
+            Reader::Int32(v) => Builder::Int32(v),
+            Reader::Int64(v) => Builder::Int64(v),
+            Reader::UInt8(v) => Builder::UInt8(v),
+            Reader::UInt16(v) => Builder::UInt16(v),
+            Reader::UInt32(v) => Builder::UInt32(v),
+            Reader::UInt64(v) => Builder::UInt64(v),
+            Reader::Float32(v) => Builder::Float32(v),
+            Reader::Float64(v) => Builder::Float64(v),
+            Reader::Enum(v) => Builder::Enum(v),
+            _ => unreachable!(),
+        }
+    }
+}
+
+pub fn type_mismatch(expected : &Type, found : &Reader) -> Error {
+    Error::new_decode_error("Value does not match the expected type.",
+                            Some(format!("expected {:?}, found {}", expected, found.kind_name())))
+}
+
+/// The encoding of a list whose elements have the given type.
+pub fn element_size(element_type : &Type) -> ElementSize {
+    match *element_type {
+        Type::Void => Void,
+        Type::Bool => Bit,
+        Type::Int8 | Type::UInt8 => Byte,
+        Type::Int16 | Type::UInt16 | Type::Enum(_) => TwoBytes,
+        Type::Int32 | Type::UInt32 | Type::Float32 => FourBytes,
+        Type::Int64 | Type::UInt64 | Type::Float64 => EightBytes,
+        Type::Struct(_) => InlineComposite,
+        Type::Text | Type::Data | Type::List(_) | Type::Interface(_) | Type::AnyPointer => Pointer,
+    }
+}
+
+pub fn struct_size(schema : StructSchema) -> StructSize {
+    let (data, pointers) = schema.get_struct_size();
+    StructSize { data : data, pointers : pointers }
+}
+
+/// Converts `value` to the number of an enumerant of the enum `id`. Accepts enum values of
+/// the same type, enumerant names, and integers.
+pub fn enum_value(loader : &SchemaLoader, id : u64, value : Reader) -> Result<u16> {
+    match value {
+        Reader::Enum(e) if e.get_schema().get_schema().get_id() == id => Ok(e.get_value()),
+        Reader::Text(name) => {
+            match try!(loader.get_enum_schema(id)).find_enumerant_by_name(name) {
+                Some(e) => Ok(e.get_ordinal()),
+                None => Err(Error::new_decode_error("Enumerant not found.",
+                                                    Some(name.to_string()))),
+            }
+        }
+        _ => match value.to_u16() {
+            Some(v) => Ok(v),
+            None => Err(type_mismatch(&Type::Enum(id), &value)),
+        },
+    }
+}
+
+/// The raw bits of a non-pointer default value, which are XORed with the stored value.
+fn default_bits(default : Option<&schema::Value>) -> u64 {
+    match default {
+        Some(&schema::Value::Bool(v)) => v as u64,
+        Some(&schema::Value::Int8(v)) => v as u8 as u64,
+        Some(&schema::Value::Int16(v)) => v as u16 as u64,
+        Some(&schema::Value::Int32(v)) => v as u32 as u64,
+        Some(&schema::Value::Int64(v)) => v as u64,
+        Some(&schema::Value::UInt8(v)) => v as u64,
+        Some(&schema::Value::UInt16(v)) => v as u64,
+        Some(&schema::Value::UInt32(v)) => v as u64,
+        Some(&schema::Value::UInt64(v)) => v,
+        Some(&schema::Value::Float32(v)) => unsafe { ::std::mem::transmute::<f32, u32>(v) as u64 },
+        Some(&schema::Value::Float64(v)) => unsafe { ::std::mem::transmute::<f64, u64>(v) },
+        Some(&schema::Value::Enum(v)) => v as u64,
+        _ => 0,
+    }
+}
+
+/// Reads a non-pointer field at `offset`, which is in multiples of the field's size.
+pub fn read_data<'a>(loader : &'a SchemaLoader, type_ : &Type, reader : &StructReader<'a>,
+                     offset : u32, default : Option<&schema::Value>) -> Result<Reader<'a>> {
+    let offset = offset as usize;
+    let bits = default_bits(default);
+    Ok(match *type_ {
+        Type::Void => Reader::Void,
+        Type::Bool => Reader::Bool(reader.get_bool_field_mask(offset, bits != 0)),
+        Type::Int8 => Reader::Int8(reader.get_data_field_mask::<i8>(offset, bits as i8)),
+        Type::Int16 => Reader::Int16(reader.get_data_field_mask::<i16>(offset, bits as i16)),
+        Type::Int32 => Reader::Int32(reader.get_data_field_mask::<i32>(offset, bits as i32)),
+        Type::Int64 => Reader::Int64(reader.get_data_field_mask::<i64>(offset, bits as i64)),
+        Type::UInt8 => Reader::UInt8(reader.get_data_field_mask::<u8>(offset, bits as u8)),
+        Type::UInt16 => Reader::UInt16(reader.get_data_field_mask::<u16>(offset, bits as u16)),
+        Type::UInt32 => Reader::UInt32(reader.get_data_field_mask::<u32>(offset, bits as u32)),
+        Type::UInt64 => Reader::UInt64(reader.get_data_field_mask::<u64>(offset, bits)),
+        Type::Float32 => Reader::Float32(reader.get_data_field_mask::<f32>(offset, bits as u32)),
+        Type::Float64 => Reader::Float64(reader.get_data_field_mask::<f64>(offset, bits)),
+        Type::Enum(id) => Reader::Enum(Enum::new(
+            reader.get_data_field_mask::<u16>(offset, bits as u16),
+            try!(loader.get_enum_schema(id)))),
+        _ => panic!("read_data() called on a pointer type"),
+    })
+}
+
+/// Writes a non-pointer field at `offset`, converting `value` to `type_` if it fits.
+pub fn write_data(loader : &SchemaLoader, type_ : &Type, builder : &StructBuilder, offset : u32,
+                  default : Option<&schema::Value>, value : Reader) -> Result<()> {
+    let offset = offset as usize;
+    let bits = default_bits(default);
+    let mismatch = || type_mismatch(type_, &value);
+    match *type_ {
+        Type::Void => match value {
+            Reader::Void => (),
+            _ => return Err(mismatch()),
+        },
+        Type::Bool => match value {
+            Reader::Bool(b) => builder.set_bool_field_mask(offset, b, bits != 0),
+            _ => return Err(mismatch()),
+        },
+        Type::Int8 => builder.set_data_field_mask::<i8>(
+            offset, try!(value.to_i8().ok_or_else(&mismatch)), bits as i8),
+        Type::Int16 => builder.set_data_field_mask::<i16>(
+            offset, try!(value.to_i16().ok_or_else(&mismatch)), bits as i16),
+        Type::Int32 => builder.set_data_field_mask::<i32>(
+            offset, try!(value.to_i32().ok_or_else(&mismatch)), bits as i32),
+        Type::Int64 => builder.set_data_field_mask::<i64>(
+            offset, try!(value.as_i64().ok_or_else(&mismatch)), bits as i64),
+        Type::UInt8 => builder.set_data_field_mask::<u8>(
+            offset, try!(value.to_u8().ok_or_else(&mismatch)), bits as u8),
+        Type::UInt16 => builder.set_data_field_mask::<u16>(
+            offset, try!(value.to_u16().ok_or_else(&mismatch)), bits as u16),
+        Type::UInt32 => builder.set_data_field_mask::<u32>(
+            offset, try!(value.to_u32().ok_or_else(&mismatch)), bits as u32),
+        Type::UInt64 => builder.set_data_field_mask::<u64>(
+            offset, try!(value.as_u64().ok_or_else(&mismatch)), bits),
+        Type::Float32 => builder.set_data_field_mask::<f32>(
+            offset, try!(value.as_f64().ok_or_else(&mismatch)) as f32, bits as u32),
+        Type::Float64 => builder.set_data_field_mask::<f64>(
+            offset, try!(value.as_f64().ok_or_else(&mismatch)), bits),
+        Type::Enum(id) => builder.set_data_field_mask::<u16>(
+            offset, try!(enum_value(loader, id, value)), bits as u16),
+        _ => panic!("write_data() called on a pointer type"),
+    }
+    Ok(())
+}
+
+/// Like `read_data()`, but returns the value as a builder.
+pub fn get_data<'a>(loader : &'a SchemaLoader, type_ : &Type, builder : &StructBuilder<'a>,
+                    offset : u32, default : Option<&schema::Value>) -> Result<Builder<'a>> {
+    Ok(Builder::from_data(try!(read_data(loader, type_, &builder.as_reader(), offset, default))))
+}
+
+/// Returns the pointer that holds `default` if `pointer` is null and `default` is a pointer value.
+fn pointer_or_default<'a>(pointer : PointerReader<'a>, default : Option<&'a schema::Value>)
+                          -> Result<PointerReader<'a>> {
+    if pointer.is_null() {
+        match default {
+            Some(&schema::Value::List(ref v)) |
+            Some(&schema::Value::Struct(ref v)) |
+            Some(&schema::Value::AnyPointer(ref v)) => return try!(v.get()).get_as(),
+            _ => (),
+        }
+    }
+    Ok(pointer)
+}
+
+/// Reads a pointer of type `type_`, falling back to `default` if it is null.
+pub fn read_pointer<'a>(loader : &'a SchemaLoader, type_ : &'a Type, pointer : PointerReader<'a>,
+                        default : Option<&'a schema::Value>) -> Result<Reader<'a>> {
+    let pointer = try!(pointer_or_default(pointer, default));
+    Ok(match *type_ {
+        Type::Text => match default {
+            Some(&schema::Value::Text(ref t)) if pointer.is_null() => Reader::Text(t),
+            _ => Reader::Text(try!(pointer.get_text(::std::ptr::null(), 0))),
+        },
+        Type::Data => match default {
+            Some(&schema::Value::Data(ref d)) if pointer.is_null() => Reader::Data(d),
+            _ => Reader::Data(try!(pointer.get_data(::std::ptr::null(), 0))),
+        },
+        Type::List(ref element_type) => Reader::List(dynamic_list::Reader::new(
+            try!(pointer.get_list(element_size(element_type), ::std::ptr::null())),
+            element_type, loader)),
+        Type::Struct(id) => Reader::Struct(dynamic_struct::Reader::new(
+            try!(pointer.get_struct(::std::ptr::null())),
+            try!(loader.get_struct_schema(id)))),
+        Type::AnyPointer => Reader::AnyPointer(any_pointer::Reader::new(pointer)),
+        Type::Interface(_) => return Err(Error::new_decode_error(
+            "Interface fields are not supported by dynamic readers.", None)),
+        _ => panic!("read_pointer() called on a non-pointer type"),
+    })
+}
+
+/// Gets a builder for a pointer of type `type_`. If the pointer is null, `default` is copied
+/// into the message first.
+pub fn get_pointer<'a>(loader : &'a SchemaLoader, type_ : &'a Type, pointer : PointerBuilder<'a>,
+                       default : Option<&'a schema::Value>) -> Result<Builder<'a>> {
+    if pointer.is_null() {
+        match default {
+            Some(&schema::Value::Text(ref t)) if !t.is_empty() => pointer.set_text(t),
+            Some(&schema::Value::Data(ref d)) if !d.is_empty() => pointer.set_data(d),
+            Some(&schema::Value::List(ref v)) |
+            Some(&schema::Value::Struct(ref v)) |
+            Some(&schema::Value::AnyPointer(ref v)) => {
+                try!(pointer.copy_from(try!(try!(v.get()).get_as())));
+            }
+            _ => (),
+        }
+    }
+    Ok(match *type_ {
+        Type::Text => Builder::Text(try!(pointer.get_text(::std::ptr::null(), 0))),
+        Type::Data => Builder::Data(try!(pointer.get_data(::std::ptr::null(), 0))),
+        Type::List(ref element_type) => {
+            let list = match **element_type {
+                Type::Struct(id) => try!(pointer.get_struct_list(
+                    struct_size(try!(loader.get_struct_schema(id))), ::std::ptr::null())),
+                ref t => try!(pointer.get_list(element_size(t), ::std::ptr::null())),
+            };
+            Builder::List(dynamic_list::Builder::new(list, element_type, loader))
+        }
+        Type::Struct(id) => {
+            let schema = try!(loader.get_struct_schema(id));
+            Builder::Struct(dynamic_struct::Builder::new(
+                try!(pointer.get_struct(struct_size(schema), ::std::ptr::null())), schema))
+        }
+        Type::AnyPointer => Builder::AnyPointer(any_pointer::Builder::new(pointer)),
+        Type::Interface(_) => return Err(Error::new_decode_error(
+            "Interface fields are not supported by dynamic builders.", None)),
+        _ => panic!("get_pointer() called on a non-pointer type"),
+    })
+}
+
+/// Initializes a pointer of type `type_`. `size` is the element count of a list or the byte
+/// count of a text or data blob, and is ignored for structs.
+pub fn init_pointer<'a>(loader : &'a SchemaLoader, type_ : &'a Type, pointer : PointerBuilder<'a>,
+                        size : u32) -> Result<Builder<'a>> {
+    Ok(match *type_ {
+        Type::Text => Builder::Text(pointer.init_text(size)),
+        Type::Data => Builder::Data(pointer.init_data(size)),
+        Type::List(ref element_type) => {
+            let list = match **element_type {
+                Type::Struct(id) =>
+                    pointer.init_struct_list(size, struct_size(try!(loader.get_struct_schema(id)))),
+                ref t => pointer.init_list(element_size(t), size),
+            };
+            Builder::List(dynamic_list::Builder::new(list, element_type, loader))
+        }
+        Type::Struct(id) => {
+            let schema = try!(loader.get_struct_schema(id));
+            Builder::Struct(dynamic_struct::Builder::new(pointer.init_struct(struct_size(schema)),
+                                                         schema))
+        }
+        Type::AnyPointer => {
+            pointer.clear();
+            Builder::AnyPointer(any_pointer::Builder::new(pointer))
+        }
+        Type::Interface(_) => return Err(Error::new_decode_error(
+            "Interface fields are not supported by dynamic builders.", None)),
+        _ => panic!("init_pointer() called on a non-pointer type"),
+    })
+}
+
+/// Sets a pointer of type `type_` to a deep copy of `value`.
+pub fn set_pointer(type_ : &Type, pointer : PointerBuilder, value : Reader) -> Result<()> {
+    match (type_, value) {
+        (&Type::Text, Reader::Text(t)) | (&Type::AnyPointer, Reader::Text(t)) => pointer.set_text(t),
+        (&Type::Data, Reader::Data(d)) | (&Type::AnyPointer, Reader::Data(d)) => pointer.set_data(d),
+        (&Type::List(ref element_type), Reader::List(l)) if **element_type == *l.get_element_type() =>
+            try!(pointer.set_list(&l.into_internal_list_reader())),
+        (&Type::AnyPointer, Reader::List(l)) =>
+            try!(pointer.set_list(&l.into_internal_list_reader())),
+        (&Type::Struct(id), Reader::Struct(s)) if s.get_schema().get_schema().get_id() == id =>
+            try!(pointer.set_struct(&::traits::IntoInternalStructReader::into_internal_struct_reader(s))),
+        (&Type::AnyPointer, Reader::Struct(s)) =>
+            try!(pointer.set_struct(&::traits::IntoInternalStructReader::into_internal_struct_reader(s))),
+        (&Type::AnyPointer, Reader::AnyPointer(a)) | (&Type::Struct(_), Reader::AnyPointer(a)) |
+        (&Type::List(_), Reader::AnyPointer(a)) =>
+            try!(pointer.copy_from(try!(a.get_as()))),
+        _ => return Err(type_mismatch(type_, &value)),
+    }
+    Ok(())
+}
@@ -21,11 +21,20 @@
 
 //! List of enums.
 
-use traits::{FromPointerReader, FromPointerBuilder, ToU16, FromU16};
+use traits::{FromPointerReader, FromPointerBuilder, ToU16, FromU16, IndexMove, ListIter, ListAppender, ResizableList};
 use private::layout::{ListReader, ListBuilder, PointerReader, PointerBuilder,
                       TwoBytes, PrimitiveElement};
 use {NotInSchema, Result};
 
+pub struct Owned<T> {
+    marker : ::std::marker::PhantomData<T>,
+}
+
+impl <'a, T : FromU16> ::traits::Owned<'a> for Owned<T> {
+    type Reader = Reader<'a, T>;
+    type Builder = Builder<'a, T>;
+}
+
 #[derive(Clone, Copy)]
 pub struct Reader<'a, T> {
     marker : ::std::marker::PhantomData<T>,
@@ -39,6 +48,10 @@ impl <'a, T : FromU16> Reader<'a, T> {
 
     pub fn len(&self) -> u32 { self.reader.len() }
 
+    pub fn iter(self) -> ListIter<Reader<'a, T>, ::std::result::Result<T, NotInSchema>> {
+        let l = self.len();
+        ListIter::new(self, l)
+    }
 }
 
 impl <'a, T : FromU16> FromPointerReader<'a> for Reader<'a, T> {
@@ -48,12 +61,36 @@ impl <'a, T : FromU16> FromPointerReader<'a> for Reader<'a, T> {
     }
 }
 
+impl <'a, T : FromU16>  IndexMove<u32, ::std::result::Result<T, NotInSchema>> for Reader<'a, T> {
+    fn index_move(&self, index : u32) -> ::std::result::Result<T, NotInSchema> {
+        self.get(index)
+    }
+}
+
 impl <'a, T : FromU16> Reader<'a, T> {
     pub fn get(&self, index : u32) -> ::std::result::Result<T, NotInSchema> {
         assert!(index < self.len());
         let result : u16 = PrimitiveElement::get(&self.reader, index);
         FromU16::from_u16(result)
     }
+
+    /// Like `get()`, but returns `None` instead of panicking if `index` is out of bounds.
+    pub fn try_get(&self, index : u32) -> Option<::std::result::Result<T, NotInSchema>> {
+        if index < self.len() {
+            Some(self.get(index))
+        } else {
+            None
+        }
+    }
+}
+
+impl <'a, T : FromU16> ::std::iter::IntoIterator for Reader<'a, T> {
+    type Item = ::std::result::Result<T, NotInSchema>;
+    type IntoIter = ListIter<Reader<'a, T>, Self::Item>;
+
+    fn into_iter(self) -> ListIter<Reader<'a, T>, Self::Item> {
+        self.iter()
+    }
 }
 
 pub struct Builder<'a, T> {
@@ -72,6 +109,21 @@ impl <'a, T : ToU16 + FromU16> Builder<'a, T> {
         assert!(index < self.len());
         PrimitiveElement::set(&self.builder, index, value.to_u16());
     }
+
+    /// Shrinks the list to `new_len` elements. See `ListBuilder::truncate()`.
+    pub fn truncate(&mut self, new_len : u32) -> Result<()> {
+        unsafe { self.builder.truncate(new_len) }
+    }
+
+    /// Extends the list to `new_len` elements. See `ListBuilder::grow()`.
+    pub fn grow(&mut self, new_len : u32) -> Result<()> {
+        unsafe { self.builder.grow(new_len) }
+    }
+
+    pub fn iter(self) -> ListIter<Builder<'a, T>, ElementBuilder<'a, T>> {
+        let l = self.len();
+        ListIter::new(self, l)
+    }
 }
 
 impl <'a, T : FromU16> FromPointerBuilder<'a> for Builder<'a, T> {
@@ -91,6 +143,62 @@ impl <'a, T : ToU16 + FromU16>  Builder<'a, T> {
         let result : u16 = PrimitiveElement::get_from_builder(&self.builder, index);
         FromU16::from_u16(result)
     }
+
+    /// Like `get()`, but returns `None` instead of panicking if `index` is out of bounds.
+    pub fn try_get(&self, index : u32) -> Option<::std::result::Result<T, NotInSchema>> {
+        if index < self.len() {
+            Some(self.get(index))
+        } else {
+            None
+        }
+    }
+}
+
+impl <'a, T : ToU16 + FromU16>  IndexMove<u32, ElementBuilder<'a, T>> for Builder<'a, T> {
+    fn index_move(&self, index : u32) -> ElementBuilder<'a, T> {
+        assert!(index < self.len());
+        ::traits::ElementBuilder::new(Builder { builder : self.builder, marker : ::std::marker::PhantomData }, index)
+    }
+}
+
+impl <'a, T : ToU16 + FromU16> ::std::iter::IntoIterator for Builder<'a, T> {
+    type Item = ElementBuilder<'a, T>;
+    type IntoIter = ListIter<Builder<'a, T>, ElementBuilder<'a, T>>;
+
+    fn into_iter(self) -> ListIter<Builder<'a, T>, ElementBuilder<'a, T>> {
+        self.iter()
+    }
+}
+
+impl <'a, T : ToU16 + FromU16> ResizableList for Builder<'a, T> {
+    fn len(&self) -> u32 { self.builder.len() }
+    fn truncate(&mut self, new_len : u32) -> Result<()> {
+        unsafe { self.builder.truncate(new_len) }
+    }
+    fn grow(&mut self, new_len : u32) -> Result<()> {
+        unsafe { self.builder.grow(new_len) }
+    }
+}
+
+impl <'a, T : ToU16 + FromU16> ListAppender<Builder<'a, T>> {
+    pub fn push(&mut self, value : T) -> Result<()> {
+        let index = try!(self.next_index());
+        self.get_list().set(index, value);
+        Ok(())
+    }
+}
+
+pub type ElementBuilder<'a, T> = ::traits::ElementBuilder<Builder<'a, T>>;
+
+impl <'a, T : ToU16 + FromU16> ::traits::ElementBuilder<Builder<'a, T>> {
+    pub fn get(&self) -> ::std::result::Result<T, NotInSchema> {
+        let result : u16 = PrimitiveElement::get_from_builder(&self.get_list().builder, self.index());
+        FromU16::from_u16(result)
+    }
+
+    pub fn set(&mut self, value : T) {
+        PrimitiveElement::set(&self.get_list().builder, self.index(), value.to_u16());
+    }
 }
 
 impl <'a, T> ::traits::SetPointerBuilder<Builder<'a, T>> for Reader<'a, T> {
@@ -0,0 +1,796 @@
+// Copyright (c) 2013-2015 Sandstorm Development Group, Inc. and contributors
+// Licensed under the MIT License:
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Schema-less inspection of the pointer structure of a message.
+//!
+//! `walk()` visits every pointer reachable from the root. It decodes the pointers directly from
+//! the segment words instead of going through `layout`, so that a damaged message can be
+//! examined past its first error: an invalid pointer is reported to the visitor and skipped, and
+//! the walk carries on with its siblings. `dump()` prints the result of a walk, and `analyze()`
+//! uses one to work out where the space in a message goes. The `capnp-dump` tool shows both.
+
+use std::fmt;
+
+use message::{MessageReader, ReaderOptions, SegmentArrayMessageReader};
+use private::layout::ElementSize;
+use {MessageSize, Word};
+
+/// The position of a word in a message.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Location {
+    pub segment : u32,
+    pub offset : u32,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, fmt : &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}:{}", self.segment, self.offset)
+    }
+}
+
+/// A decoded pointer. Targets are resolved to absolute locations.
+#[derive(Clone, Copy)]
+pub enum Pointer {
+    Null,
+    Struct { target : Location, data_words : u16, pointer_count : u16 },
+
+    /// A list of anything but structs. `word_count` is the size of the list body.
+    List { target : Location, element_size : ElementSize, element_count : u32, word_count : u32 },
+
+    /// An inline-composite list. `target` is the tag word, which is not included in `word_count`.
+    StructList { target : Location, element_count : u32, data_words : u16, pointer_count : u16,
+                 word_count : u32 },
+
+    /// The pointer that the landing pad holds is visited next, one level deeper. For a double-far
+    /// pointer that is the tag in the pad's second word, with its target resolved through the
+    /// pad's first word.
+    Far { landing_pad : Location, double_far : bool },
+
+    Capability { index : u32 },
+}
+
+/// Receives the pointers found by `walk()`.
+pub trait Visitor {
+    /// Called for each valid pointer, in depth-first order. `depth` is zero for the root pointer.
+    fn pointer(&mut self, depth : u32, at : Location, pointer : Pointer);
+
+    /// Called for a pointer that could not be followed. The walk does not descend into it.
+    fn error(&mut self, depth : u32, at : Location, message : &str);
+}
+
+/// Visits the pointers of the message made up of `segments`, starting at the root. The nesting
+/// and traversal limits of `options` bound the walk; when the traversal limit is reached an
+/// error is reported and the walk stops.
+pub fn walk<V : Visitor>(segments : &[&[Word]], options : ReaderOptions, visitor : &mut V) {
+    let mut walker = Walker {
+        segments : segments,
+        visitor : visitor,
+        nesting_limit : options.nesting_limit,
+        words_left : options.traversal_limit_in_words,
+        stopped : false,
+    };
+    walker.pointer(0, Location { segment : 0, offset : 0 }, true);
+}
+
+fn read_word(segment : &[Word], offset : usize) -> u64 {
+    let bytes = Word::words_to_bytes(&segment[offset .. offset + 1]);
+    let mut result = 0u64;
+    for i in 0..8 {
+        result |= (bytes[i] as u64) << (8 * i);
+    }
+    result
+}
+
+fn element_bits(element_size : ElementSize) -> u64 {
+    match element_size {
+        ElementSize::Void => 0,
+        ElementSize::Bit => 1,
+        ElementSize::Byte => 8,
+        ElementSize::TwoBytes => 16,
+        ElementSize::FourBytes => 32,
+        ElementSize::EightBytes | ElementSize::Pointer => 64,
+        ElementSize::InlineComposite => unreachable!(),
+    }
+}
+
+fn element_size_from_bits(bits : u64) -> ElementSize {
+    match bits & 7 {
+        0 => ElementSize::Void,
+        1 => ElementSize::Bit,
+        2 => ElementSize::Byte,
+        3 => ElementSize::TwoBytes,
+        4 => ElementSize::FourBytes,
+        5 => ElementSize::EightBytes,
+        6 => ElementSize::Pointer,
+        _ => ElementSize::InlineComposite,
+    }
+}
+
+struct Walker<'s, 'v, V : 'v> {
+    segments : &'s [&'s [Word]],
+    visitor : &'v mut V,
+    nesting_limit : i32,
+    words_left : u64,
+    stopped : bool,
+}
+
+impl <'s, 'v, V : Visitor> Walker<'s, 'v, V> {
+    /// Returns the word at `at`, or `None` if it lies outside the message.
+    fn read(&self, at : Location) -> Option<u64> {
+        if self.in_bounds(at, 1) {
+            Some(read_word(self.segments[at.segment as usize], at.offset as usize))
+        } else {
+            None
+        }
+    }
+
+    fn in_bounds(&self, start : Location, word_count : u64) -> bool {
+        match self.segments.get(start.segment as usize) {
+            Some(segment) => start.offset as u64 + word_count <= segment.len() as u64,
+            None => false,
+        }
+    }
+
+    /// Charges `word_count` words against the traversal limit.
+    fn charge(&mut self, depth : u32, at : Location, word_count : u64) -> bool {
+        // Like the read limiter in `arena`, count empty objects as one word so that a
+        // message full of pointers to them still runs out.
+        let amount = ::std::cmp::max(word_count, 1);
+        if amount > self.words_left {
+            self.visitor.error(depth, at, "Exceeded traversal limit; stopping.");
+            self.stopped = true;
+            false
+        } else {
+            self.words_left -= amount;
+            true
+        }
+    }
+
+    fn pointer(&mut self, depth : u32, at : Location, allow_far : bool) {
+        if self.stopped { return; }
+        if depth as i64 > self.nesting_limit as i64 {
+            self.visitor.error(depth, at, "Exceeded nesting limit.");
+            return;
+        }
+        let word = match self.read(at) {
+            Some(word) => word,
+            None => {
+                self.visitor.error(depth, at, "Pointer is out of bounds.");
+                return;
+            }
+        };
+        let lower = word as u32;
+        let upper = (word >> 32) as u32;
+        match lower & 3 {
+            0 | 1 => {
+                if word == 0 {
+                    self.visitor.pointer(depth, at, Pointer::Null);
+                    return;
+                }
+                let offset = at.offset as i64 + 1 + ((lower as i32) >> 2) as i64;
+                if offset < 0 || offset > u32::max_value() as i64 {
+                    self.visitor.error(depth, at, &format!("Target offset {} is out of bounds.", offset));
+                    return;
+                }
+                let target = Location { segment : at.segment, offset : offset as u32 };
+                self.object(depth, at, word, target);
+            }
+            2 => {
+                if !allow_far {
+                    self.visitor.error(depth, at, "Far pointer landing pad holds another far pointer.");
+                    return;
+                }
+                let landing_pad = Location { segment : upper, offset : lower >> 3 };
+                let double_far = lower & 4 != 0;
+                self.visitor.pointer(depth, at, Pointer::Far { landing_pad : landing_pad,
+                                                               double_far : double_far });
+                if !double_far {
+                    self.pointer(depth + 1, landing_pad, false);
+                    return;
+                }
+                let (pad, tag) = match (self.read(landing_pad),
+                                        self.read(Location { offset : landing_pad.offset + 1,
+                                                             .. landing_pad })) {
+                    (Some(pad), Some(tag)) => (pad, tag),
+                    _ => {
+                        self.visitor.error(depth + 1, landing_pad,
+                                           "Double-far landing pad is out of bounds.");
+                        return;
+                    }
+                };
+                if pad & 7 != 2 {
+                    self.visitor.error(depth + 1, landing_pad,
+                                       "Double-far landing pad is not a single far pointer.");
+                    return;
+                }
+                let target = Location { segment : (pad >> 32) as u32, offset : (pad as u32) >> 3 };
+                let tag_at = Location { offset : landing_pad.offset + 1, .. landing_pad };
+                match tag & 3 {
+                    0 | 1 => self.object(depth + 1, tag_at, tag, target),
+                    _ => self.visitor.error(depth + 1, tag_at,
+                                            "Double-far tag is not a struct or list pointer."),
+                }
+            }
+            _ => {
+                if lower == 3 {
+                    self.visitor.pointer(depth, at, Pointer::Capability { index : upper });
+                } else {
+                    self.visitor.error(depth, at, &format!("Unknown pointer type {:#x}.", word));
+                }
+            }
+        }
+    }
+
+    /// Visits the struct or list pointer `word`, found at `at`, whose target is `target`.
+    fn object(&mut self, depth : u32, at : Location, word : u64, target : Location) {
+        let upper = (word >> 32) as u32;
+        if word & 3 == 0 {
+            let data_words = upper as u16;
+            let pointer_count = (upper >> 16) as u16;
+            let size = data_words as u64 + pointer_count as u64;
+            if !self.in_bounds(target, size) {
+                self.visitor.error(depth, at, &format!(
+                    "Struct at {} with {} data words and {} pointers is out of bounds.",
+                    target, data_words, pointer_count));
+                return;
+            }
+            if !self.charge(depth, at, size) { return; }
+            self.visitor.pointer(depth, at, Pointer::Struct { target : target,
+                                                              data_words : data_words,
+                                                              pointer_count : pointer_count });
+            for i in 0..(pointer_count as u32) {
+                self.pointer(depth + 1, Location { offset : target.offset + data_words as u32 + i,
+                                                   .. target }, true);
+            }
+            return;
+        }
+
+        let element_size = element_size_from_bits((word >> 32) & 7);
+        let count = upper >> 3;
+        if element_size != ElementSize::InlineComposite {
+            let word_count = (count as u64 * element_bits(element_size) + 63) / 64;
+            if !self.in_bounds(target, word_count) {
+                self.visitor.error(depth, at, &format!(
+                    "List at {} with {} elements is out of bounds.", target, count));
+                return;
+            }
+            if !self.charge(depth, at, word_count) { return; }
+            self.visitor.pointer(depth, at, Pointer::List { target : target,
+                                                            element_size : element_size,
+                                                            element_count : count,
+                                                            word_count : word_count as u32 });
+            if element_size == ElementSize::Pointer {
+                for i in 0..count {
+                    self.pointer(depth + 1, Location { offset : target.offset + i, .. target }, true);
+                }
+            }
+            return;
+        }
+
+        if !self.in_bounds(target, count as u64 + 1) {
+            self.visitor.error(depth, at, &format!(
+                "Struct list at {} with {} words is out of bounds.", target, count));
+            return;
+        }
+        let tag = self.read(target).unwrap();
+        if tag & 3 != 0 {
+            self.visitor.error(depth, at, "Struct list tag is not a struct pointer.");
+            return;
+        }
+        let element_count = (tag as u32) >> 2;
+        let data_words = (tag >> 32) as u16;
+        let pointer_count = (tag >> 48) as u16;
+        let step = data_words as u64 + pointer_count as u64;
+        if element_count as u64 * step > count as u64 {
+            self.visitor.error(depth, at, &format!(
+                "Struct list at {} has {} elements of {} words, which do not fit in {} words.",
+                target, element_count, step, count));
+            return;
+        }
+        if !self.charge(depth, at, count as u64 + 1) { return; }
+        self.visitor.pointer(depth, at, Pointer::StructList { target : target,
+                                                              element_count : element_count,
+                                                              data_words : data_words,
+                                                              pointer_count : pointer_count,
+                                                              word_count : count });
+        for i in 0..element_count {
+            let element = target.offset + 1 + i * step as u32;
+            for j in 0..(pointer_count as u32) {
+                self.pointer(depth + 1, Location { offset : element + data_words as u32 + j,
+                                                   .. target }, true);
+            }
+        }
+    }
+}
+
+fn element_size_name(element_size : ElementSize) -> &'static str {
+    match element_size {
+        ElementSize::Void => "void",
+        ElementSize::Bit => "bit",
+        ElementSize::Byte => "byte",
+        ElementSize::TwoBytes => "two-byte",
+        ElementSize::FourBytes => "four-byte",
+        ElementSize::EightBytes => "eight-byte",
+        ElementSize::Pointer => "pointer",
+        ElementSize::InlineComposite => "inline composite",
+    }
+}
+
+struct Printer<'w, W : 'w> {
+    out : &'w mut W,
+    result : fmt::Result,
+}
+
+impl <'w, W : fmt::Write> Printer<'w, W> {
+    fn line(&mut self, depth : u32, at : Location, args : fmt::Arguments) {
+        if self.result.is_ok() {
+            self.result = self.write_line(depth, at, args);
+        }
+    }
+
+    fn write_line(&mut self, depth : u32, at : Location, args : fmt::Arguments) -> fmt::Result {
+        for _ in 0..depth {
+            try!(self.out.write_str("  "));
+        }
+        try!(write!(self.out, "{} ", at));
+        try!(self.out.write_fmt(args));
+        self.out.write_str("\n")
+    }
+}
+
+impl <'w, W : fmt::Write> Visitor for Printer<'w, W> {
+    fn pointer(&mut self, depth : u32, at : Location, pointer : Pointer) {
+        match pointer {
+            Pointer::Null => self.line(depth, at, format_args!("null")),
+            Pointer::Struct { target, data_words, pointer_count } =>
+                self.line(depth, at, format_args!("struct -> {}, data {}, pointers {}",
+                                                  target, data_words, pointer_count)),
+            Pointer::List { target, element_size, element_count, word_count } =>
+                self.line(depth, at, format_args!("list -> {}, {} x {}, {} words",
+                                                  target, element_count,
+                                                  element_size_name(element_size), word_count)),
+            Pointer::StructList { target, element_count, data_words, pointer_count, word_count } =>
+                self.line(depth, at, format_args!(
+                    "struct list -> {}, {} x (data {}, pointers {}), {} words",
+                    target, element_count, data_words, pointer_count, word_count)),
+            Pointer::Far { landing_pad, double_far } =>
+                self.line(depth, at, format_args!("{} -> {}",
+                                                  if double_far { "double far" } else { "far" },
+                                                  landing_pad)),
+            Pointer::Capability { index } =>
+                self.line(depth, at, format_args!("capability {}", index)),
+        }
+    }
+
+    fn error(&mut self, depth : u32, at : Location, message : &str) {
+        self.line(depth, at, format_args!("error: {}", message));
+    }
+}
+
+/// Prints the segment table of the message made up of `segments`, the size of its root as
+/// computed by `layout`, and then every pointer found by `walk()`, indented by depth.
+///
+/// Only the root gets a total size. Asking `layout` for the size of every pointer would traverse
+/// each subtree once per enclosing pointer, and would fail for any pointer above damage that the
+/// walk itself steps around.
+pub fn dump<W : fmt::Write>(segments : &[&[Word]], options : ReaderOptions, out : &mut W)
+                            -> fmt::Result
+{
+    let total = segments.iter().fold(0, |total, segment| total + segment.len());
+    try!(writeln!(out, "segments: {}, {} words", segments.len(), total));
+    for (id, segment) in segments.iter().enumerate() {
+        try!(writeln!(out, "  {}: {} words", id, segment.len()));
+    }
+    if segments.is_empty() || segments[0].is_empty() {
+        return writeln!(out, "error: Message has no root pointer.");
+    }
+
+    let message = SegmentArrayMessageReader::new(segments, options);
+    match message.get_root_internal().and_then(|root| root.target_size()) {
+        Ok(size) => try!(writeln!(out, "root: {} words, {} capabilities",
+                                  size.word_count, size.cap_count)),
+        Err(e) => try!(writeln!(out, "root: error: {}", e)),
+    }
+
+    let mut printer = Printer { out : out, result : Ok(()) };
+    walk(segments, options, &mut printer);
+    printer.result
+}
+
+/// How the words of one segment are spent. Each word is counted at most once, in the category
+/// of the first object found to cover it.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SegmentStats {
+    /// The size of the segment.
+    pub words : u64,
+
+    /// Struct data sections, including those of struct list elements.
+    pub data : u64,
+
+    /// Struct pointer sections, bodies of pointer lists, and the root pointer.
+    pub pointers : u64,
+
+    /// Bodies of non-pointer lists other than text, and struct list tags.
+    pub lists : u64,
+
+    /// Byte lists ending in a NUL byte. Without a schema, text can only be told apart from
+    /// `Data` and `List(UInt8)` by this guess.
+    pub text : u64,
+
+    /// Landing pads of far pointers.
+    pub landing_pads : u64,
+
+    /// Words that no pointer reaches: space left behind when an object was replaced or
+    /// orphaned, and unused space at the end of a list of structs.
+    pub unreachable : u64,
+
+    /// Unused bytes at the ends of list bodies. These are inside the words counted above.
+    pub padding_bytes : u64,
+}
+
+impl SegmentStats {
+    fn new(words : u64) -> SegmentStats {
+        SegmentStats { words : words, data : 0, pointers : 0, lists : 0, text : 0,
+                       landing_pads : 0, unreachable : 0, padding_bytes : 0 }
+    }
+
+    fn plus_eq(&mut self, other : &SegmentStats) {
+        self.words += other.words;
+        self.data += other.data;
+        self.pointers += other.pointers;
+        self.lists += other.lists;
+        self.text += other.text;
+        self.landing_pads += other.landing_pads;
+        self.unreachable += other.unreachable;
+        self.padding_bytes += other.padding_bytes;
+    }
+}
+
+/// Size statistics of a message, as computed by `analyze()`.
+pub struct MessageStats {
+    pub segments : Vec<SegmentStats>,
+
+    /// The size of the root as computed by `layout`, or `None` if the message is too damaged
+    /// for `layout` to traverse.
+    pub root_size : Option<MessageSize>,
+
+    /// The number of pointers that could not be followed. Words that only they point to are
+    /// counted as unreachable.
+    pub errors : u32,
+}
+
+impl MessageStats {
+    /// The sum of the statistics of all segments.
+    pub fn get_total(&self) -> SegmentStats {
+        let mut total = SegmentStats::new(0);
+        for segment in self.segments.iter() {
+            total.plus_eq(segment);
+        }
+        total
+    }
+}
+
+fn write_stats_row(fmt : &mut fmt::Formatter, label : &str, s : &SegmentStats) -> fmt::Result {
+    writeln!(fmt, "{:>9} {:>9} {:>9} {:>9} {:>9} {:>9} {:>9} {:>11} {:>8}B",
+             label, s.words, s.data, s.pointers, s.lists, s.text, s.landing_pads,
+             s.unreachable, s.padding_bytes)
+}
+
+impl fmt::Display for MessageStats {
+    fn fmt(&self, fmt : &mut fmt::Formatter) -> fmt::Result {
+        try!(writeln!(fmt, "{:>9} {:>9} {:>9} {:>9} {:>9} {:>9} {:>9} {:>11} {:>9}",
+                      "segment", "words", "data", "pointers", "lists", "text", "far pads",
+                      "unreachable", "padding"));
+        for (id, segment) in self.segments.iter().enumerate() {
+            try!(write_stats_row(fmt, &id.to_string(), segment));
+        }
+        try!(write_stats_row(fmt, "total", &self.get_total()));
+        match self.root_size {
+            Some(size) => try!(write!(fmt, "root: {} words, {} capabilities",
+                                      size.word_count, size.cap_count)),
+            None => try!(write!(fmt, "root: invalid")),
+        }
+        if self.errors > 0 {
+            try!(write!(fmt, ", {} invalid pointers", self.errors));
+        }
+        Ok(())
+    }
+}
+
+struct Analyzer<'s> {
+    segments : &'s [&'s [Word]],
+    stats : Vec<SegmentStats>,
+    covered : Vec<Vec<bool>>,
+    errors : u32,
+}
+
+#[derive(Clone, Copy)]
+enum Category { Data, Pointers, Lists, Text, LandingPads }
+
+impl <'s> Analyzer<'s> {
+    /// Counts the words of `start .. start + word_count` that were not already counted.
+    fn count(&mut self, category : Category, start : Location, word_count : u64) -> u64 {
+        let segment = start.segment as usize;
+        if segment >= self.covered.len() { return 0; }
+        let covered = &mut self.covered[segment];
+        let begin = ::std::cmp::min(start.offset as usize, covered.len());
+        let end = ::std::cmp::min(begin + word_count as usize, covered.len());
+        let mut added = 0;
+        for word in covered[begin .. end].iter_mut() {
+            if !*word {
+                *word = true;
+                added += 1;
+            }
+        }
+        let stats = &mut self.stats[segment];
+        match category {
+            Category::Data => stats.data += added,
+            Category::Pointers => stats.pointers += added,
+            Category::Lists => stats.lists += added,
+            Category::Text => stats.text += added,
+            Category::LandingPads => stats.landing_pads += added,
+        }
+        added
+    }
+
+    fn is_text(&self, target : Location, element_count : u32) -> bool {
+        if element_count == 0 { return false; }
+        let last = target.offset as usize + (element_count as usize - 1) / 8;
+        let bytes = Word::words_to_bytes(&self.segments[target.segment as usize][last .. last + 1]);
+        bytes[(element_count as usize - 1) % 8] == 0
+    }
+}
+
+impl <'s> Visitor for Analyzer<'s> {
+    fn pointer(&mut self, _depth : u32, _at : Location, pointer : Pointer) {
+        match pointer {
+            Pointer::Null | Pointer::Capability { .. } => {}
+            Pointer::Struct { target, data_words, pointer_count } => {
+                self.count(Category::Data, target, data_words as u64);
+                self.count(Category::Pointers, Location { offset : target.offset + data_words as u32,
+                                                          .. target },
+                           pointer_count as u64);
+            }
+            Pointer::List { target, element_size, element_count, word_count } => {
+                let category = if element_size == ElementSize::Pointer {
+                    Category::Pointers
+                } else if element_size == ElementSize::Byte && self.is_text(target, element_count) {
+                    Category::Text
+                } else {
+                    Category::Lists
+                };
+                if self.count(category, target, word_count as u64) > 0 {
+                    let used_bytes = (element_count as u64 * element_bits(element_size) + 7) / 8;
+                    self.stats[target.segment as usize].padding_bytes +=
+                        word_count as u64 * 8 - used_bytes;
+                }
+            }
+            Pointer::StructList { target, element_count, data_words, pointer_count, .. } => {
+                self.count(Category::Lists, target, 1);
+                let step = data_words as u32 + pointer_count as u32;
+                for i in 0..element_count {
+                    let element = Location { offset : target.offset + 1 + i * step, .. target };
+                    self.count(Category::Data, element, data_words as u64);
+                    self.count(Category::Pointers,
+                               Location { offset : element.offset + data_words as u32, .. element },
+                               pointer_count as u64);
+                }
+            }
+            Pointer::Far { landing_pad, double_far } => {
+                self.count(Category::LandingPads, landing_pad, if double_far { 2 } else { 1 });
+            }
+        }
+    }
+
+    fn error(&mut self, _depth : u32, _at : Location, _message : &str) {
+        self.errors += 1;
+    }
+}
+
+/// Walks the message made up of `segments` and tallies how the words of each segment are used.
+pub fn analyze(segments : &[&[Word]], options : ReaderOptions) -> MessageStats {
+    let mut analyzer = Analyzer {
+        segments : segments,
+        stats : segments.iter().map(|s| SegmentStats::new(s.len() as u64)).collect(),
+        covered : segments.iter().map(|s| vec![false; s.len()]).collect(),
+        errors : 0,
+    };
+    let root_size = if segments.is_empty() || segments[0].is_empty() {
+        analyzer.errors += 1;
+        None
+    } else {
+        analyzer.count(Category::Pointers, Location { segment : 0, offset : 0 }, 1);
+        walk(segments, options, &mut analyzer);
+        let message = SegmentArrayMessageReader::new(segments, options);
+        message.get_root_internal().and_then(|root| root.target_size()).ok()
+    };
+
+    for (stats, covered) in analyzer.stats.iter_mut().zip(analyzer.covered.iter()) {
+        stats.unreachable = covered.iter().filter(|&&c| !c).count() as u64;
+    }
+    MessageStats { segments : analyzer.stats, root_size : root_size, errors : analyzer.errors }
+}
+
+/// Like `analyze()`, for a message that has already been read.
+pub fn analyze_message<M : MessageReader>(message : &M) -> MessageStats {
+    let segments : Vec<&[Word]> =
+        (0..message.get_segment_count()).map(|id| message.get_segment(id)).collect();
+    analyze(&segments, *message.get_options())
+}
+
+#[cfg(test)]
+mod tests {
+    use message::ReaderOptions;
+    use Word;
+    use super::{analyze, dump, SegmentStats};
+
+    fn words(values : &[u64]) -> Vec<Word> {
+        values.iter().map(|&v| Word { _unused_member : v.to_le() }).collect()
+    }
+
+    fn struct_pointer(offset : i32, data_words : u16, pointer_count : u16) -> u64 {
+        ((offset << 2) as u32 as u64) | (data_words as u64) << 32 | (pointer_count as u64) << 48
+    }
+
+    fn list_pointer(offset : i32, element_size : u32, element_count : u32) -> u64 {
+        ((offset << 2 | 1) as u32 as u64) | ((element_count << 3 | element_size) as u64) << 32
+    }
+
+    fn far_pointer(segment : u32, landing_pad : u32, double_far : bool) -> u64 {
+        (landing_pad << 3 | (double_far as u32) << 2 | 2) as u64 | (segment as u64) << 32
+    }
+
+    fn dump_lines(segments : &[Vec<Word>], options : ReaderOptions) -> Vec<String> {
+        let segments : Vec<&[Word]> = segments.iter().map(|s| &s[..]).collect();
+        let mut out = String::new();
+        dump(&segments, options, &mut out).unwrap();
+        out.lines().map(|line| line.to_string()).collect()
+    }
+
+    /// The lines of `dump()` that come from the walk.
+    fn walk_lines(segments : &[Vec<Word>], options : ReaderOptions) -> Vec<String> {
+        let lines = dump_lines(segments, options);
+        let root = lines.iter().position(|line| line.starts_with("root: ")).unwrap();
+        lines[root + 1 ..].to_vec()
+    }
+
+    #[test]
+    fn dump_far_pointer() {
+        let segments = vec![words(&[far_pointer(1, 0, false)]),
+                            words(&[struct_pointer(0, 1, 1), 42, list_pointer(0, 2, 3), 0x6968])];
+        assert_eq!(dump_lines(&segments, ReaderOptions::new()), vec![
+            "segments: 2, 5 words",
+            "  0: 1 words",
+            "  1: 4 words",
+            "root: 3 words, 0 capabilities",
+            "0:0 far -> 1:0",
+            "  1:0 struct -> 1:1, data 1, pointers 1",
+            "    1:2 list -> 1:3, 3 x byte, 1 words"]);
+    }
+
+    #[test]
+    fn out_of_bounds_struct() {
+        // The walk goes on to the second pointer after failing on the first.
+        let segments = vec![words(&[struct_pointer(0, 0, 2), struct_pointer(1, 2, 1),
+                                    struct_pointer(-1, 0, 0)])];
+        assert_eq!(walk_lines(&segments, ReaderOptions::new()), vec![
+            "0:0 struct -> 0:1, data 0, pointers 2",
+            "  0:1 error: Struct at 0:3 with 2 data words and 1 pointers is out of bounds.",
+            "  0:2 struct -> 0:2, data 0, pointers 0"]);
+    }
+
+    #[test]
+    fn far_pointer_to_far_pointer() {
+        let segments = vec![words(&[far_pointer(1, 0, false)]),
+                            words(&[far_pointer(1, 1, false), struct_pointer(0, 0, 0)])];
+        assert_eq!(dump_lines(&segments, ReaderOptions::new())[3..].to_vec(), vec![
+            "root: error: Unexpected FAR pointer.",
+            "0:0 far -> 1:0",
+            "  1:0 error: Far pointer landing pad holds another far pointer."]);
+    }
+
+    #[test]
+    fn bad_double_far_landing_pad() {
+        let not_far = vec![words(&[far_pointer(1, 0, true)]),
+                           words(&[struct_pointer(0, 1, 0), struct_pointer(0, 1, 0), 0])];
+        assert_eq!(walk_lines(&not_far, ReaderOptions::new()), vec![
+            "0:0 double far -> 1:0",
+            "  1:0 error: Double-far landing pad is not a single far pointer."]);
+
+        let cut_short = vec![words(&[far_pointer(1, 0, true)]), words(&[far_pointer(1, 0, false)])];
+        assert_eq!(walk_lines(&cut_short, ReaderOptions::new()), vec![
+            "0:0 double far -> 1:0",
+            "  1:0 error: Double-far landing pad is out of bounds."]);
+
+        let bad_tag = vec![words(&[far_pointer(1, 0, true)]),
+                           words(&[far_pointer(1, 2, false), far_pointer(0, 0, false), 0])];
+        assert_eq!(walk_lines(&bad_tag, ReaderOptions::new()), vec![
+            "0:0 double far -> 1:0",
+            "  1:1 error: Double-far tag is not a struct or list pointer."]);
+    }
+
+    #[test]
+    fn struct_list_tag_overflow() {
+        // The tag claims two elements of two words each, in a list of three words.
+        let segments = vec![words(&[list_pointer(0, 7, 3), struct_pointer(2, 1, 1), 0, 0, 0])];
+        assert_eq!(walk_lines(&segments, ReaderOptions::new()), vec![
+            concat!("0:0 error: Struct list at 0:1 has 2 elements of 2 words, ",
+                    "which do not fit in 3 words.")]);
+    }
+
+    #[test]
+    fn traversal_limit() {
+        // A list of three pointers to the same one-word struct costs six words to walk.
+        let segments = vec![words(&[list_pointer(0, 6, 3), struct_pointer(2, 1, 0),
+                                    struct_pointer(1, 1, 0), struct_pointer(0, 1, 0), 7])];
+        let mut options = ReaderOptions::new();
+        options.traversal_limit_in_words(6);
+        assert_eq!(walk_lines(&segments, options).len(), 4);
+        options.traversal_limit_in_words(5);
+        assert_eq!(walk_lines(&segments, options), vec![
+            "0:0 list -> 0:1, 3 x pointer, 3 words",
+            "  0:1 struct -> 0:4, data 1, pointers 0",
+            "  0:2 struct -> 0:4, data 1, pointers 0",
+            "  0:3 error: Exceeded traversal limit; stopping."]);
+    }
+
+    #[test]
+    fn analyze_every_category() {
+        let segments = vec![
+            words(&[far_pointer(1, 0, false),
+                    0xdead]),                       // orphaned
+            words(&[struct_pointer(0, 1, 2),        // landing pad
+                    5,
+                    list_pointer(1, 2, 4),          // "hi!" with its NUL
+                    list_pointer(1, 2, 3),          // three bytes, so not text
+                    0x00216968,
+                    0x030201,
+                    0xbeef])];                      // orphaned
+        let slices : Vec<&[Word]> = segments.iter().map(|s| &s[..]).collect();
+        let stats = analyze(&slices, ReaderOptions::new());
+
+        assert_eq!(stats.segments, vec![
+            SegmentStats { words : 2, data : 0, pointers : 1, lists : 0, text : 0,
+                           landing_pads : 0, unreachable : 1, padding_bytes : 0 },
+            SegmentStats { words : 7, data : 1, pointers : 2, lists : 1, text : 1,
+                           landing_pads : 1, unreachable : 1, padding_bytes : 9 }]);
+        assert_eq!(stats.get_total(),
+                   SegmentStats { words : 9, data : 1, pointers : 3, lists : 1, text : 1,
+                                  landing_pads : 1, unreachable : 2, padding_bytes : 9 });
+        let root_size = stats.root_size.unwrap();
+        assert_eq!((root_size.word_count, root_size.cap_count), (5, 0));
+        assert_eq!(stats.errors, 0);
+    }
+
+    #[test]
+    fn analyze_counts_shared_words_once() {
+        // Two pointers to one struct, and a third that is out of bounds.
+        let segments = vec![words(&[list_pointer(0, 6, 3), struct_pointer(2, 1, 0),
+                                    struct_pointer(1, 1, 0), struct_pointer(9, 1, 0), 7])];
+        let slices : Vec<&[Word]> = segments.iter().map(|s| &s[..]).collect();
+        let stats = analyze(&slices, ReaderOptions::new());
+        assert_eq!(stats.segments, vec![
+            SegmentStats { words : 5, data : 1, pointers : 4, lists : 0, text : 0,
+                           landing_pads : 0, unreachable : 0, padding_bytes : 0 }]);
+        assert!(stats.root_size.is_none());
+        assert_eq!(stats.errors, 1);
+    }
+}
@@ -188,6 +188,11 @@ impl <'a> ArrayInputStream<'a> {
     pub fn new<'b>(array : &'b [u8]) -> ArrayInputStream<'b> {
         ArrayInputStream { array : array }
     }
+
+    /// Returns true if every byte of the array has been consumed.
+    pub fn is_empty(&self) -> bool {
+        self.array.is_empty()
+    }
 }
 
 impl <'a> InputStream for ArrayInputStream<'a> {
@@ -0,0 +1,685 @@
+// Copyright (c) 2013-2015 Sandstorm Development Group, Inc. and contributors
+// Licensed under the MIT License:
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Conversion between Cap'n Proto messages and JSON, following the conventions of the C++
+//! implementation's `capnp/compat/json.h`:
+//!
+//! * Structs are objects keyed by field name. Null pointer fields are omitted, and the active
+//!   member of a union appears as an ordinary field.
+//! * `Int64` and `UInt64` are strings, since many JSON parsers cannot represent them exactly.
+//!   Other numbers are JSON numbers, except that non-finite floats are the strings `"NaN"`,
+//!   `"Infinity"` and `"-Infinity"`.
+//! * Enums are enumerant names. Values that are not in the schema are numbers.
+//! * `Void` is `null`.
+//! * `Data` is an array of bytes, or a base64 string if `JsonOptions::base64_data` is set.
+//!
+//! The decoder accepts all of these forms regardless of options, along with numbers for
+//! 64-bit integers. Unknown fields are ignored so that old readers can accept new data.
+
+use dynamic_list;
+use dynamic_struct;
+use dynamic_value;
+use message::{MessageBuilder, MessageReader};
+use private::layout::{PointerBuilder, PointerReader};
+use schema::{StructSchema, Type};
+use {Error, Result};
+
+/// Options controlling how values are encoded and decoded.
+#[derive(Clone, Copy)]
+pub struct JsonOptions {
+    /// If true, output is spread across lines and indented by two spaces.
+    pub pretty_print : bool,
+
+    /// If true, `Data` is encoded as a base64 string instead of an array of numbers.
+    pub base64_data : bool,
+
+    /// Limits how deeply nested JSON input may be, to protect against stack overflow.
+    pub max_nesting_depth : u32,
+}
+
+pub const DEFAULT_JSON_OPTIONS : JsonOptions =
+    JsonOptions { pretty_print : false, base64_data : false, max_nesting_depth : 64 };
+
+impl JsonOptions {
+    pub fn new() -> JsonOptions { DEFAULT_JSON_OPTIONS }
+
+    pub fn pretty_print<'a>(&'a mut self, value : bool) -> &'a mut JsonOptions {
+        self.pretty_print = value;
+        return self;
+    }
+
+    pub fn base64_data<'a>(&'a mut self, value : bool) -> &'a mut JsonOptions {
+        self.base64_data = value;
+        return self;
+    }
+
+    pub fn max_nesting_depth<'a>(&'a mut self, value : u32) -> &'a mut JsonOptions {
+        self.max_nesting_depth = value;
+        return self;
+    }
+}
+
+/// Encodes `value` as JSON.
+pub fn encode(value : dynamic_value::Reader, options : &JsonOptions) -> Result<String> {
+    let mut encoder = Encoder { out : String::new(), options : options };
+    try!(encoder.value(value, 0));
+    Ok(encoder.out)
+}
+
+/// Encodes the root of `message`, which must be a struct of type `schema`.
+pub fn encode_message<M : MessageReader>(message : &M, schema : StructSchema,
+                                         options : &JsonOptions) -> Result<String> {
+    let root : PointerReader = try!(message.get_root());
+    let reader = dynamic_struct::Reader::new(try!(root.get_struct(::std::ptr::null())), schema);
+    encode(dynamic_value::Reader::Struct(reader), options)
+}
+
+/// Decodes a JSON object into the struct that `builder` points at.
+pub fn decode(json : &str, builder : &dynamic_struct::Builder, options : &JsonOptions) -> Result<()> {
+    let value = try!(Parser::new(json, options.max_nesting_depth).parse_document());
+    decode_struct(builder, &value)
+}
+
+/// Decodes a JSON object as a struct of type `schema` and makes it the root of `message`.
+pub fn decode_message<M : MessageBuilder>(json : &str, schema : StructSchema, message : &mut M,
+                                          options : &JsonOptions) -> Result<()> {
+    let value = try!(Parser::new(json, options.max_nesting_depth).parse_document());
+    let root : PointerBuilder = message.get_root_internal().init_as();
+    let builder = dynamic_struct::Builder::new(
+        root.init_struct(dynamic_value::struct_size(schema)), schema);
+    decode_struct(&builder, &value)
+}
+
+static BASE64_CHARS : &'static [u8] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64_encode(bytes : &[u8]) -> String {
+    let mut result = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = ((b[0] as u32) << 16) | ((b[1] as u32) << 8) | (b[2] as u32);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                result.push(BASE64_CHARS[((n >> (18 - 6 * i)) & 0x3f) as usize] as char);
+            } else {
+                result.push('=');
+            }
+        }
+    }
+    result
+}
+
+fn base64_decode(text : &str) -> Result<Vec<u8>> {
+    let mut result = Vec::with_capacity(text.len() / 4 * 3);
+    let mut n : u32 = 0;
+    let mut bits = 0;
+    for c in text.bytes() {
+        let digit = match c {
+            b'A' ... b'Z' => c - b'A',
+            b'a' ... b'z' => c - b'a' + 26,
+            b'0' ... b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            b'=' => break,
+            _ => return Err(Error::new_decode_error("Invalid base64 data.", None)),
+        };
+        n = (n << 6) | digit as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            result.push((n >> bits) as u8);
+        }
+    }
+    Ok(result)
+}
+
+struct Encoder<'o> {
+    out : String,
+    options : &'o JsonOptions,
+}
+
+impl <'o> Encoder<'o> {
+    fn newline(&mut self, depth : u32) {
+        if self.options.pretty_print {
+            self.out.push('\n');
+            for _ in 0..depth {
+                self.out.push_str("  ");
+            }
+        }
+    }
+
+    fn string(&mut self, s : &str) {
+        self.out.push('"');
+        for c in s.chars() {
+            match c {
+                '"' => self.out.push_str("\\\""),
+                '\\' => self.out.push_str("\\\\"),
+                '\n' => self.out.push_str("\\n"),
+                '\r' => self.out.push_str("\\r"),
+                '\t' => self.out.push_str("\\t"),
+                '\x08' => self.out.push_str("\\b"),
+                '\x0c' => self.out.push_str("\\f"),
+                c if (c as u32) < 0x20 => self.out.push_str(&format!("\\u{:04x}", c as u32)),
+                c => self.out.push(c),
+            }
+        }
+        self.out.push('"');
+    }
+
+    fn float(&mut self, v : f64) {
+        if v.is_nan() {
+            self.out.push_str("\"NaN\"");
+        } else if v.is_infinite() {
+            self.out.push_str(if v > 0.0 { "\"Infinity\"" } else { "\"-Infinity\"" });
+        } else {
+            self.out.push_str(&format!("{}", v));
+        }
+    }
+
+    fn value(&mut self, value : dynamic_value::Reader, depth : u32) -> Result<()> {
+        match value {
+            dynamic_value::Reader::Void => self.out.push_str("null"),
+            dynamic_value::Reader::Bool(b) => self.out.push_str(if b { "true" } else { "false" }),
+            dynamic_value::Reader::Int8(v) => self.out.push_str(&format!("{}", v)),
+            dynamic_value::Reader::Int16(v) => self.out.push_str(&format!("{}", v)),
+            dynamic_value::Reader::Int32(v) => self.out.push_str(&format!("{}", v)),
+            dynamic_value::Reader::Int64(v) => self.out.push_str(&format!("\"{}\"", v)),
+            dynamic_value::Reader::UInt8(v) => self.out.push_str(&format!("{}", v)),
+            dynamic_value::Reader::UInt16(v) => self.out.push_str(&format!("{}", v)),
+            dynamic_value::Reader::UInt32(v) => self.out.push_str(&format!("{}", v)),
+            dynamic_value::Reader::UInt64(v) => self.out.push_str(&format!("\"{}\"", v)),
+            dynamic_value::Reader::Float32(v) => self.float(v as f64),
+            dynamic_value::Reader::Float64(v) => self.float(v),
+            dynamic_value::Reader::Enum(e) => match e.get_enumerant() {
+                Some(enumerant) => self.string(enumerant.get_name()),
+                None => self.out.push_str(&format!("{}", e.get_value())),
+            },
+            dynamic_value::Reader::Text(t) => self.string(t),
+            dynamic_value::Reader::Data(d) => {
+                if self.options.base64_data {
+                    let encoded = base64_encode(d);
+                    self.string(&encoded);
+                } else {
+                    self.out.push('[');
+                    for (i, b) in d.iter().enumerate() {
+                        if i > 0 { self.out.push(','); }
+                        self.out.push_str(&format!("{}", b));
+                    }
+                    self.out.push(']');
+                }
+            }
+            dynamic_value::Reader::List(l) => try!(self.list(l, depth)),
+            dynamic_value::Reader::Struct(s) => try!(self.struct_(s, depth)),
+            dynamic_value::Reader::AnyPointer(_) => return Err(Error::new_decode_error(
+                "AnyPointer fields cannot be encoded as JSON.", None)),
+        }
+        Ok(())
+    }
+
+    fn list(&mut self, list : dynamic_list::Reader, depth : u32) -> Result<()> {
+        self.out.push('[');
+        for i in 0..list.len() {
+            if i > 0 { self.out.push(','); }
+            self.newline(depth + 1);
+            try!(self.value(try!(list.get(i)), depth + 1));
+        }
+        if list.len() > 0 {
+            self.newline(depth);
+        }
+        self.out.push(']');
+        Ok(())
+    }
+
+    fn struct_(&mut self, value : dynamic_struct::Reader, depth : u32) -> Result<()> {
+        let fields = try!(value.get_present_fields());
+        self.out.push('{');
+        for (i, field) in fields.iter().enumerate() {
+            if i > 0 { self.out.push(','); }
+            self.newline(depth + 1);
+            self.string(field.get_name());
+            self.out.push_str(if self.options.pretty_print { ": " } else { ":" });
+            try!(self.value(try!(value.get(*field)), depth + 1));
+        }
+        if !fields.is_empty() {
+            self.newline(depth);
+        }
+        self.out.push('}');
+        Ok(())
+    }
+}
+
+/// A parsed JSON value. Numbers are kept as text so that 64-bit integers survive intact.
+enum Json {
+    Null,
+    Bool(bool),
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+fn kind_name(value : &Json) -> &'static str {
+    match *value {
+        Json::Null => "null",
+        Json::Bool(_) => "boolean",
+        Json::Number(_) => "number",
+        Json::String(_) => "string",
+        Json::Array(_) => "array",
+        Json::Object(_) => "object",
+    }
+}
+
+struct Parser<'a> {
+    input : &'a [u8],
+    pos : usize,
+    max_depth : u32,
+}
+
+impl <'a> Parser<'a> {
+    fn new(input : &'a str, max_depth : u32) -> Parser<'a> {
+        Parser { input : input.as_bytes(), pos : 0, max_depth : max_depth }
+    }
+
+    fn error<T>(&self, description : &'static str) -> Result<T> {
+        Err(Error::new_decode_error(description, Some(format!("at byte offset {}", self.pos))))
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.input.len() {
+            match self.input[self.pos] {
+                b' ' | b'\t' | b'\n' | b'\r' => self.pos += 1,
+                _ => break,
+            }
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_whitespace();
+        self.input.get(self.pos).map(|b| *b)
+    }
+
+    fn expect(&mut self, c : u8, description : &'static str) -> Result<()> {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            self.error(description)
+        }
+    }
+
+    fn parse_document(&mut self) -> Result<Json> {
+        let value = try!(self.parse_value(0));
+        if self.peek().is_some() {
+            return self.error("Unexpected trailing characters in JSON input.");
+        }
+        Ok(value)
+    }
+
+    fn keyword(&mut self, word : &'static str, value : Json) -> Result<Json> {
+        if self.input[self.pos..].starts_with(word.as_bytes()) {
+            self.pos += word.len();
+            Ok(value)
+        } else {
+            self.error("Invalid JSON value.")
+        }
+    }
+
+    fn parse_value(&mut self, depth : u32) -> Result<Json> {
+        if depth > self.max_depth {
+            return self.error("JSON input is nested too deeply.");
+        }
+        match self.peek() {
+            None => self.error("Unexpected end of JSON input."),
+            Some(b'n') => self.keyword("null", Json::Null),
+            Some(b't') => self.keyword("true", Json::Bool(true)),
+            Some(b'f') => self.keyword("false", Json::Bool(false)),
+            Some(b'"') => Ok(Json::String(try!(self.parse_string()))),
+            Some(b'[') => {
+                self.pos += 1;
+                let mut elements = Vec::new();
+                if self.peek() == Some(b']') {
+                    self.pos += 1;
+                    return Ok(Json::Array(elements));
+                }
+                loop {
+                    elements.push(try!(self.parse_value(depth + 1)));
+                    match self.peek() {
+                        Some(b',') => self.pos += 1,
+                        Some(b']') => { self.pos += 1; return Ok(Json::Array(elements)); }
+                        _ => return self.error("Expected `,` or `]` in JSON array."),
+                    }
+                }
+            }
+            Some(b'{') => {
+                self.pos += 1;
+                let mut members = Vec::new();
+                if self.peek() == Some(b'}') {
+                    self.pos += 1;
+                    return Ok(Json::Object(members));
+                }
+                loop {
+                    if self.peek() != Some(b'"') {
+                        return self.error("Expected a string key in JSON object.");
+                    }
+                    let key = try!(self.parse_string());
+                    try!(self.expect(b':', "Expected `:` in JSON object."));
+                    members.push((key, try!(self.parse_value(depth + 1))));
+                    match self.peek() {
+                        Some(b',') => self.pos += 1,
+                        Some(b'}') => { self.pos += 1; return Ok(Json::Object(members)); }
+                        _ => return self.error("Expected `,` or `}` in JSON object."),
+                    }
+                }
+            }
+            Some(c) if c == b'-' || (c >= b'0' && c <= b'9') => {
+                let start = self.pos;
+                self.pos += 1;
+                while self.pos < self.input.len() {
+                    match self.input[self.pos] {
+                        b'0' ... b'9' | b'.' | b'e' | b'E' | b'+' | b'-' => self.pos += 1,
+                        _ => break,
+                    }
+                }
+                let text = ::std::str::from_utf8(&self.input[start..self.pos]).unwrap();
+                Ok(Json::Number(text.to_string()))
+            }
+            Some(_) => self.error("Invalid JSON value."),
+        }
+    }
+
+    fn hex4(&mut self) -> Result<u32> {
+        if self.pos + 4 > self.input.len() {
+            return self.error("Truncated `\\u` escape in JSON string.");
+        }
+        let digits = ::std::str::from_utf8(&self.input[self.pos..self.pos + 4]).unwrap_or("");
+        match u32::from_str_radix(digits, 16) {
+            Ok(v) => { self.pos += 4; Ok(v) }
+            Err(_) => self.error("Invalid `\\u` escape in JSON string."),
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String> {
+        self.pos += 1; // opening quote
+        let mut bytes = Vec::new();
+        loop {
+            let c = match self.input.get(self.pos) {
+                None => return self.error("Unterminated JSON string."),
+                Some(c) => *c,
+            };
+            self.pos += 1;
+            match c {
+                b'"' => break,
+                b'\\' => {
+                    let e = match self.input.get(self.pos) {
+                        None => return self.error("Unterminated JSON string."),
+                        Some(e) => *e,
+                    };
+                    self.pos += 1;
+                    match e {
+                        b'"' | b'\\' | b'/' => bytes.push(e),
+                        b'b' => bytes.push(0x08),
+                        b'f' => bytes.push(0x0c),
+                        b'n' => bytes.push(b'\n'),
+                        b'r' => bytes.push(b'\r'),
+                        b't' => bytes.push(b'\t'),
+                        b'u' => {
+                            let mut code = try!(self.hex4());
+                            if code >= 0xd800 && code < 0xdc00 &&
+                                self.input[self.pos..].starts_with(b"\\u") {
+                                self.pos += 2;
+                                let low = try!(self.hex4());
+                                if low < 0xdc00 || low >= 0xe000 {
+                                    return self.error(
+                                        "High surrogate in JSON string is not followed by a low one.");
+                                }
+                                code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+                            }
+                            match ::std::char::from_u32(code) {
+                                Some(ch) => bytes.extend(ch.to_string().bytes()),
+                                None => return self.error("Invalid `\\u` escape in JSON string."),
+                            }
+                        }
+                        _ => return self.error("Invalid escape in JSON string."),
+                    }
+                }
+                c => bytes.push(c),
+            }
+        }
+        match String::from_utf8(bytes) {
+            Ok(s) => Ok(s),
+            Err(_) => self.error("JSON string is not valid UTF-8."),
+        }
+    }
+}
+
+fn mismatch(type_ : &Type, value : &Json) -> Error {
+    Error::new_decode_error("JSON value does not match the expected type.",
+                            Some(format!("expected {:?}, found {}", type_, kind_name(value))))
+}
+
+/// Parses a JSON number, or a string holding one, as a dynamic value of the appropriate kind.
+fn number<'a>(type_ : &Type, text : &'a str, value : &Json) -> Result<dynamic_value::Reader<'a>> {
+    let is_float = match *type_ {
+        Type::Float32 | Type::Float64 => true,
+        _ => false,
+    };
+    if is_float {
+        let v = match text {
+            "NaN" => ::std::f64::NAN,
+            "Infinity" => ::std::f64::INFINITY,
+            "-Infinity" => -::std::f64::INFINITY,
+            _ => match text.parse::<f64>() {
+                Ok(v) => v,
+                Err(_) => return Err(mismatch(type_, value)),
+            },
+        };
+        return Ok(dynamic_value::Reader::Float64(v));
+    }
+    if let Ok(v) = text.parse::<u64>() {
+        Ok(dynamic_value::Reader::UInt64(v))
+    } else if let Ok(v) = text.parse::<i64>() {
+        Ok(dynamic_value::Reader::Int64(v))
+    } else {
+        Err(mismatch(type_, value))
+    }
+}
+
+fn data(value : &Json) -> Result<Vec<u8>> {
+    match *value {
+        Json::String(ref s) => base64_decode(s),
+        Json::Array(ref elements) => {
+            let mut result = Vec::with_capacity(elements.len());
+            for element in elements.iter() {
+                match *element {
+                    Json::Number(ref n) => match n.parse::<u8>() {
+                        Ok(b) => result.push(b),
+                        Err(_) => return Err(mismatch(&Type::Data, element)),
+                    },
+                    _ => return Err(mismatch(&Type::Data, element)),
+                }
+            }
+            Ok(result)
+        }
+        _ => Err(mismatch(&Type::Data, value)),
+    }
+}
+
+/// Sets a non-struct, non-list value with `set`, which is either a struct field or a list
+/// element setter.
+fn decode_scalar<F>(type_ : &Type, value : &Json, set : F) -> Result<()>
+    where F : FnOnce(dynamic_value::Reader) -> Result<()>
+{
+    match (type_, value) {
+        (&Type::Void, &Json::Null) => set(dynamic_value::Reader::Void),
+        (&Type::Bool, &Json::Bool(b)) => set(dynamic_value::Reader::Bool(b)),
+        (&Type::Data, _) => {
+            let bytes = try!(data(value));
+            set(dynamic_value::Reader::Data(&bytes))
+        }
+        (&Type::Text, &Json::String(ref s)) => set(dynamic_value::Reader::Text(s)),
+        (&Type::Enum(_), &Json::String(ref s)) => set(dynamic_value::Reader::Text(s)),
+        (&Type::Enum(_), &Json::Number(ref n)) => match n.parse::<u16>() {
+            Ok(v) => set(dynamic_value::Reader::UInt16(v)),
+            Err(_) => Err(mismatch(type_, value)),
+        },
+        (&Type::Int8, &Json::Number(ref n)) | (&Type::Int16, &Json::Number(ref n)) |
+        (&Type::Int32, &Json::Number(ref n)) | (&Type::Int64, &Json::Number(ref n)) |
+        (&Type::UInt8, &Json::Number(ref n)) | (&Type::UInt16, &Json::Number(ref n)) |
+        (&Type::UInt32, &Json::Number(ref n)) | (&Type::UInt64, &Json::Number(ref n)) |
+        (&Type::Float32, &Json::Number(ref n)) | (&Type::Float64, &Json::Number(ref n)) |
+        (&Type::Int8, &Json::String(ref n)) | (&Type::Int16, &Json::String(ref n)) |
+        (&Type::Int32, &Json::String(ref n)) | (&Type::Int64, &Json::String(ref n)) |
+        (&Type::UInt8, &Json::String(ref n)) | (&Type::UInt16, &Json::String(ref n)) |
+        (&Type::UInt32, &Json::String(ref n)) | (&Type::UInt64, &Json::String(ref n)) |
+        (&Type::Float32, &Json::String(ref n)) | (&Type::Float64, &Json::String(ref n)) =>
+            set(try!(number(type_, n, value))),
+        _ => Err(mismatch(type_, value)),
+    }
+}
+
+fn decode_struct(builder : &dynamic_struct::Builder, value : &Json) -> Result<()> {
+    let members = match *value {
+        Json::Object(ref members) => members,
+        _ => return Err(mismatch(&Type::Struct(builder.get_schema().get_proto().id), value)),
+    };
+    let schema = builder.get_schema();
+    let mut union_member : Option<&str> = None;
+    for &(ref name, ref value) in members.iter() {
+        let field = match schema.find_field_by_name(name) {
+            Some(field) => field,
+            None => continue,
+        };
+        if field.get_discriminant_value().is_some() {
+            if let Some(previous) = union_member {
+                return Err(Error::new_decode_error(
+                    "JSON object sets more than one member of a union.",
+                    Some(format!("{} and {}", previous, name))));
+            }
+            union_member = Some(name);
+        }
+        let type_ = field.get_type();
+        match (&type_, value) {
+            // A null pointer is the same as an absent one.
+            (_, &Json::Null) if type_.is_pointer() => (),
+            (&Type::Struct(_), _) => match try!(builder.init(field)) {
+                dynamic_value::Builder::Struct(s) => try!(decode_struct(&s, value)),
+                _ => unreachable!(),
+            },
+            (&Type::List(_), &Json::Array(ref elements)) =>
+                match try!(builder.init_sized(field, elements.len() as u32)) {
+                    dynamic_value::Builder::List(l) => try!(decode_list(&l, elements)),
+                    _ => unreachable!(),
+                },
+            _ => try!(decode_scalar(&type_, value, |v| builder.set(field, v))),
+        }
+    }
+    Ok(())
+}
+
+fn decode_list(builder : &dynamic_list::Builder, elements : &[Json]) -> Result<()> {
+    let element_type = builder.get_element_type();
+    for (i, value) in elements.iter().enumerate() {
+        let i = i as u32;
+        match (element_type, value) {
+            (&Type::Struct(_), _) => match try!(builder.get(i)) {
+                dynamic_value::Builder::Struct(s) => try!(decode_struct(&s, value)),
+                _ => unreachable!(),
+            },
+            (&Type::List(_), &Json::Array(ref elements)) =>
+                match try!(builder.init(i, elements.len() as u32)) {
+                    dynamic_value::Builder::List(l) => try!(decode_list(&l, elements)),
+                    _ => unreachable!(),
+                },
+            _ => try!(decode_scalar(element_type, value, |v| builder.set(i, v))),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use dynamic_value;
+    use message::{MallocMessageBuilder, MessageBuilder};
+    use private::layout::PointerBuilder;
+    use schema::{SchemaLoader, Type};
+    use super::{base64_decode, base64_encode, decode_list, Encoder, JsonOptions, Json, Parser,
+                DEFAULT_JSON_OPTIONS};
+
+    /// Decodes `json` as a list of `element_type` and encodes the result again.
+    fn roundtrip(element_type : Type, json : &str, options : &JsonOptions) -> ::Result<String> {
+        let loader = SchemaLoader::new();
+        let type_ = Type::List(Box::new(element_type));
+        let elements = match try!(Parser::new(json, options.max_nesting_depth).parse_document()) {
+            Json::Array(elements) => elements,
+            _ => panic!("expected an array"),
+        };
+        let mut message = MallocMessageBuilder::new_default();
+        let root : PointerBuilder = message.get_root_internal().init_as();
+        let list = match try!(dynamic_value::init_pointer(&loader, &type_, root,
+                                                          elements.len() as u32)) {
+            dynamic_value::Builder::List(l) => l,
+            _ => unreachable!(),
+        };
+        try!(decode_list(&list, &elements));
+        let mut encoder = Encoder { out : String::new(), options : options };
+        try!(encoder.list(list.as_reader(), 0));
+        Ok(encoder.out)
+    }
+
+    #[test]
+    fn json_h_conventions() {
+        let options = DEFAULT_JSON_OPTIONS;
+        assert_eq!(roundtrip(Type::Int64, r#"["-9223372036854775808",7,"12"]"#, &options).unwrap(),
+                   r#"["-9223372036854775808","7","12"]"#);
+        assert_eq!(roundtrip(Type::UInt64, r#"["18446744073709551615"]"#, &options).unwrap(),
+                   r#"["18446744073709551615"]"#);
+        assert_eq!(roundtrip(Type::Int32, r#"[-5,"6"]"#, &options).unwrap(), "[-5,6]");
+        assert_eq!(roundtrip(Type::Float64, r#"["NaN","Infinity","-Infinity",0.5]"#,
+                             &options).unwrap(),
+                   r#"["NaN","Infinity","-Infinity",0.5]"#);
+        assert_eq!(roundtrip(Type::Void, "[null]", &options).unwrap(), "[null]");
+        assert_eq!(roundtrip(Type::Text, r#"["a\"\n\u0001\ud83d\ude00"]"#, &options).unwrap(),
+                   "[\"a\\\"\\n\\u0001\u{1f600}\"]");
+
+        // Data decodes from either form and encodes according to the options.
+        assert_eq!(roundtrip(Type::Data, r#"["+/8A",[1,2]]"#, &options).unwrap(),
+                   "[[251,255,0],[1,2]]");
+        let mut base64 = JsonOptions::new();
+        base64.base64_data(true);
+        assert_eq!(roundtrip(Type::Data, r#"["+/8A",[1,2]]"#, &base64).unwrap(),
+                   r#"["+/8A","AQI="]"#);
+
+        assert!(roundtrip(Type::Int8, "[128]", &options).is_err());
+        assert!(roundtrip(Type::Text, r#"["\ud83d\u0041"]"#, &options).is_err());
+        assert!(roundtrip(Type::Text, r#"["\ude00"]"#, &options).is_err());
+    }
+
+    #[test]
+    fn base64_roundtrip() {
+        let cases : [(&[u8], &str); 5] = [(b"", ""), (b"f", "Zg=="), (b"fo", "Zm8="),
+                                          (b"foo", "Zm9v"), (b"\xfb\xff\x00", "+/8A")];
+        for &(bytes, text) in cases.iter() {
+            assert_eq!(base64_encode(bytes), text);
+            assert_eq!(base64_decode(text).unwrap(), bytes);
+        }
+        assert!(base64_decode("Zm9v!").is_err());
+    }
+}
@@ -39,6 +39,11 @@
 #![crate_name="capnp"]
 #![crate_type = "lib"]
 
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(all(test, feature = "serde"))]
+extern crate serde_json;
+
 // reexports
 pub use message::{MessageBuilder, BuilderOptions, MessageReader, ReaderOptions};
 pub use message::MallocMessageBuilder;
@@ -48,14 +53,25 @@ pub mod any_pointer;
 pub mod capability;
 pub mod data;
 pub mod data_list;
+pub mod diff;
+pub mod dynamic_list;
+pub mod dynamic_struct;
+pub mod dynamic_value;
 pub mod enum_list;
+pub mod inspect;
 pub mod io;
+pub mod json;
 pub mod list_list;
 pub mod message;
+pub mod parse_text;
 pub mod primitive_list;
 pub mod private;
+pub mod schema;
 pub mod serialize;
 pub mod serialize_packed;
+#[cfg(feature = "serde")]
+pub mod serde_support;
+pub mod stringify;
 pub mod struct_list;
 pub mod text;
 pub mod text_list;
@@ -81,12 +97,28 @@ impl Word {
         return result;
     }
 
+    /// Reinterprets a byte slice as words, without checking that the slice is aligned to a word
+    /// boundary. Trailing bytes that do not fill a whole word are ignored. Use
+    /// `try_bytes_to_words()` for bytes that come from an arbitrary source.
     pub fn bytes_to_words<'a>(bytes : &'a [u8]) -> &'a [Word] {
         unsafe {
             ::std::slice::from_raw_parts(::std::mem::transmute(bytes.as_ptr()), bytes.len() / 8)
         }
     }
 
+    /// Reinterprets a byte slice as words. Returns an error if the slice is not aligned to a word
+    /// boundary or if its length is not a multiple of eight bytes.
+    pub fn try_bytes_to_words<'a>(bytes : &'a [u8]) -> Result<&'a [Word]> {
+        if bytes.as_ptr() as usize % ::std::mem::align_of::<Word>() != 0 {
+            Err(Error::new_decode_error("Byte buffer is not aligned to a word boundary.", None))
+        } else if bytes.len() % ::std::mem::size_of::<Word>() != 0 {
+            Err(Error::new_decode_error("Byte buffer does not contain a whole number of words.",
+                                        Some(format!("{} bytes", bytes.len()))))
+        } else {
+            Ok(Word::bytes_to_words(bytes))
+        }
+    }
+
     pub fn words_to_bytes<'a>(words : &'a [Word]) -> &'a [u8] {
         unsafe {
             ::std::slice::from_raw_parts(::std::mem::transmute(words.as_ptr()), words.len() * 8)
@@ -21,10 +21,20 @@
 
 //! List of lists.
 
-use traits::{FromPointerReader, FromPointerBuilder};
+use traits::{FromPointerReader, FromPointerBuilder, IndexMove, ListAppender, ListIter, ResizableList,
+             SetPointerBuilder};
 use private::layout::{ListReader, ListBuilder, PointerReader, PointerBuilder, Pointer};
 use Result;
 
+pub struct Owned<T> {
+    marker : ::std::marker::PhantomData<T>,
+}
+
+impl <'a, T> ::traits::Owned<'a> for Owned<T> where T : ::traits::Owned<'a> {
+    type Reader = Reader<'a, <T as ::traits::Owned<'a>>::Reader>;
+    type Builder = Builder<'a, <T as ::traits::Owned<'a>>::Builder>;
+}
+
 #[derive(Clone, Copy)]
 pub struct Reader<'a, T> {
     marker : ::std::marker::PhantomData<T>,
@@ -46,11 +56,43 @@ impl <'a, T : FromPointerReader<'a>> FromPointerReader<'a> for Reader<'a, T> {
     }
 }
 
+impl <'a, T : FromPointerReader<'a>> Reader<'a, T> {
+    pub fn iter(self) -> ListIter<Reader<'a, T>, Result<T>> {
+        let l = self.len();
+        ListIter::new(self, l)
+    }
+}
+
+impl <'a, T : FromPointerReader<'a>>  IndexMove<u32, Result<T>> for Reader<'a, T> {
+    fn index_move(&self, index : u32) -> Result<T> {
+        assert!(index <  self.len());
+        FromPointerReader::get_from_pointer(&self.reader.get_pointer_element(index))
+    }
+}
+
 impl <'a, T : FromPointerReader<'a>> Reader<'a, T> {
     pub fn get(self, index : u32) -> Result<T> {
         assert!(index <  self.len());
         FromPointerReader::get_from_pointer(&self.reader.get_pointer_element(index))
     }
+
+    /// Like `get()`, but returns `None` instead of panicking if `index` is out of bounds.
+    pub fn try_get(self, index : u32) -> Option<Result<T>> {
+        if index < self.len() {
+            Some(self.get(index))
+        } else {
+            None
+        }
+    }
+}
+
+impl <'a, T : FromPointerReader<'a>> ::std::iter::IntoIterator for Reader<'a, T> {
+    type Item = Result<T>;
+    type IntoIter = ListIter<Reader<'a, T>, Result<T>>;
+
+    fn into_iter(self) -> ListIter<Reader<'a, T>, Result<T>> {
+        self.iter()
+    }
 }
 
 pub struct Builder<'a, T> {
@@ -70,6 +112,21 @@ impl <'a, T : FromPointerBuilder<'a>> Builder<'a, T> {
             FromPointerBuilder::init_pointer(self.builder.get_pointer_element(index), size);
         result
     }
+
+    pub fn iter(self) -> ListIter<Builder<'a, T>, ElementBuilder<'a, T>> {
+        let l = self.len();
+        ListIter::new(self, l)
+    }
+
+    /// Shrinks the list to `new_len` elements. See `ListBuilder::truncate()`.
+    pub fn truncate(&mut self, new_len : u32) -> Result<()> {
+        unsafe { self.builder.truncate(new_len) }
+    }
+
+    /// Extends the list to `new_len` elements. See `ListBuilder::grow()`.
+    pub fn grow(&mut self, new_len : u32) -> Result<()> {
+        unsafe { self.builder.grow(new_len) }
+    }
 }
 
 impl <'a, T> Builder<'a, T> {
@@ -98,6 +155,62 @@ impl <'a, T : FromPointerBuilder<'a>> Builder<'a, T> {
         assert!(index < self.len());
         FromPointerBuilder::get_from_pointer(self.builder.get_pointer_element(index))
     }
+
+    /// Like `get()`, but returns `None` instead of panicking if `index` is out of bounds.
+    pub fn try_get(self, index : u32) -> Option<Result<T>> {
+        if index < self.len() {
+            Some(self.get(index))
+        } else {
+            None
+        }
+    }
+}
+
+impl <'a, T : FromPointerBuilder<'a>>  IndexMove<u32, ElementBuilder<'a, T>> for Builder<'a, T> {
+    fn index_move(&self, index : u32) -> ElementBuilder<'a, T> {
+        assert!(index < self.len());
+        ::traits::ElementBuilder::new(Builder { builder : self.builder, marker : ::std::marker::PhantomData }, index)
+    }
+}
+
+impl <'a, T : FromPointerBuilder<'a>> ::std::iter::IntoIterator for Builder<'a, T> {
+    type Item = ElementBuilder<'a, T>;
+    type IntoIter = ListIter<Builder<'a, T>, ElementBuilder<'a, T>>;
+
+    fn into_iter(self) -> ListIter<Builder<'a, T>, ElementBuilder<'a, T>> {
+        self.iter()
+    }
+}
+
+impl <'a, T : FromPointerBuilder<'a>> ResizableList for Builder<'a, T> {
+    fn len(&self) -> u32 { self.builder.len() }
+    fn truncate(&mut self, new_len : u32) -> Result<()> {
+        unsafe { self.builder.truncate(new_len) }
+    }
+    fn grow(&mut self, new_len : u32) -> Result<()> {
+        unsafe { self.builder.grow(new_len) }
+    }
+}
+
+impl <'a, T : FromPointerBuilder<'a>> ListAppender<Builder<'a, T>> {
+    /// Appends a deep copy of the list `value`.
+    pub fn push<R : SetPointerBuilder<T>>(&mut self, value : R) -> Result<()> {
+        let index = try!(self.next_index());
+        let pointer = self.get_list().builder.get_pointer_element(index);
+        SetPointerBuilder::set_pointer_builder(pointer, value)
+    }
+}
+
+pub type ElementBuilder<'a, T> = ::traits::ElementBuilder<Builder<'a, T>>;
+
+impl <'a, T : FromPointerBuilder<'a>> ::traits::ElementBuilder<Builder<'a, T>> {
+    pub fn get(self) -> Result<T> {
+        FromPointerBuilder::get_from_pointer(self.get_list().builder.get_pointer_element(self.index()))
+    }
+
+    pub fn init(self, size : u32) -> T {
+        FromPointerBuilder::init_pointer(self.get_list().builder.get_pointer_element(self.index()), size)
+    }
 }
 
 impl <'a, T> ::traits::SetPointerBuilder<Builder<'a, T>> for Reader<'a, T> {
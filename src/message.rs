@@ -26,8 +26,9 @@ use private::capability::ClientHook;
 use private::units::*;
 use private::arena::{BuilderArena, ReaderArena, SegmentBuilder, SegmentReader, NumWords, ZeroedWords};
 use private::layout;
-use traits::{FromPointerReader, FromPointerBuilder, SetPointerBuilder};
-use {Result, Word};
+use serialize::OwnedSpaceMessageReader;
+use traits::{FromPointerReader, FromPointerBuilder, SetPointerBuilder, Owned, IntoInternalStructReader};
+use {Error, Result, Word};
 
 /// Options controlling how data is read.
 #[derive(Clone, Copy)]
@@ -59,10 +60,31 @@ pub struct ReaderOptions {
     /// being very large. The default limit of 64 is probably low enough to prevent any chance of
     /// stack overflow, yet high enough that it is never a problem in practice.
     pub nesting_limit : i32,
+
+    /// Limits how many segments a message read by `serialize` or `serialize_packed` may have.
+    /// Messages are usually built in a handful of segments, so a large segment table is a sign of
+    /// a corrupt or malicious message. The default of 511 keeps the limit that `serialize` has
+    /// always applied.
+    pub segment_count_limit : u32,
+
+    /// Limits how many elements a list may have. An error is reported when a list pointer with a
+    /// larger element count is read.
+    pub list_element_count_limit : u32,
+
+    /// Limits the size in bytes of text and data blobs. An error is reported when a text or data
+    /// pointer to a larger blob is read. For text, the size does not include the NUL terminator.
+    pub blob_size_limit_in_bytes : u32,
+
+    // Private, so that it can only be turned on through the unsafe setter.
+    skip_utf8_validation : bool,
 }
 
 pub const DEFAULT_READER_OPTIONS : ReaderOptions =
-    ReaderOptions { traversal_limit_in_words : 8 * 1024 * 1024, nesting_limit : 64 };
+    ReaderOptions { traversal_limit_in_words : 8 * 1024 * 1024, nesting_limit : 64,
+                    segment_count_limit : 511,
+                    list_element_count_limit : ::std::u32::MAX,
+                    blob_size_limit_in_bytes : ::std::u32::MAX,
+                    skip_utf8_validation : false };
 
 impl ReaderOptions {
     pub fn new() -> ReaderOptions { DEFAULT_READER_OPTIONS }
@@ -76,6 +98,31 @@ impl ReaderOptions {
         self.traversal_limit_in_words = value;
         return self;
     }
+
+    pub fn segment_count_limit<'a>(&'a mut self, value : u32) -> &'a mut ReaderOptions {
+        self.segment_count_limit = value;
+        return self;
+    }
+
+    pub fn list_element_count_limit<'a>(&'a mut self, value : u32) -> &'a mut ReaderOptions {
+        self.list_element_count_limit = value;
+        return self;
+    }
+
+    pub fn blob_size_limit_in_bytes<'a>(&'a mut self, value : u32) -> &'a mut ReaderOptions {
+        self.blob_size_limit_in_bytes = value;
+        return self;
+    }
+
+    /// If true, text is not checked to be valid UTF-8 when it is read. Only set this for messages
+    /// from trusted producers. Unsafe because text that is not valid UTF-8 would then be handed out
+    /// as `&str`, which is undefined behavior; `get_text_as_bytes()` is the safe alternative.
+    pub unsafe fn skip_utf8_validation<'a>(&'a mut self, value : bool) -> &'a mut ReaderOptions {
+        self.skip_utf8_validation = value;
+        return self;
+    }
+
+    pub fn get_skip_utf8_validation(&self) -> bool { self.skip_utf8_validation }
 }
 
 
@@ -88,6 +135,10 @@ pub trait MessageReader {
     fn arena_mut(&mut self) -> &mut ReaderArena;
     fn get_options(&self) -> &ReaderOptions;
 
+    fn get_segment_count(&self) -> usize {
+        self.arena().more_segments.len() + 1
+    }
+
     fn get_root_internal(&self) -> Result<any_pointer::Reader> {
         unsafe {
             let segment : *const SegmentReader = &self.arena().segment0;
@@ -141,6 +192,66 @@ impl <'a> SegmentArrayMessageReader<'a> {
     }
 }
 
+/// A reader for a message that is known to be valid, such as a constant that was embedded in the
+/// program by the code generator. Like `readMessageUnchecked()` in the C++ implementation, it does
+/// no bounds checking and no traversal limiting, so reads are as fast as possible. Anything that
+/// arrived from elsewhere should be read with a checked reader.
+#[derive(Clone, Copy)]
+pub struct UncheckedMessageReader {
+    words : &'static [Word],
+}
+
+impl UncheckedMessageReader {
+    /// # Safety
+    ///
+    /// `words` must be a single segment that starts with the root pointer, contains every object
+    /// reachable from it, and holds no far pointers or capabilities. Nothing is checked, so reading
+    /// a malformed message accesses memory outside of `words`. The `'static` bound only keeps the
+    /// data alive; it says nothing about whether the data can be trusted.
+    pub unsafe fn new(words : &'static [Word]) -> UncheckedMessageReader {
+        assert!(words.len() >= POINTER_SIZE_IN_WORDS, "Message is too short to contain a root pointer.");
+        UncheckedMessageReader { words : words }
+    }
+
+    pub fn get_root_internal(&self) -> any_pointer::Reader<'static> {
+        any_pointer::Reader::new(layout::PointerReader::get_root_unchecked(self.words.as_ptr()))
+    }
+
+    /// Gets the root of the message, interpreting it as the given type.
+    pub fn get_root<T : FromPointerReader<'static>>(&self) -> Result<T> {
+        self.get_root_internal().get_as()
+    }
+}
+
+/// A message reader whose root is known to be of type `T`.
+pub struct TypedReader<M, T> where M : MessageReader, T : for<'a> Owned<'a> {
+    marker : ::std::marker::PhantomData<T>,
+    message : M,
+}
+
+impl <M, T> TypedReader<M, T> where M : MessageReader, T : for<'a> Owned<'a> {
+    pub fn new(message : M) -> TypedReader<M, T> {
+        TypedReader { marker : ::std::marker::PhantomData, message : message }
+    }
+
+    /// Gets the root of the message.
+    pub fn get<'a>(&'a self) -> Result<<T as Owned<'a>>::Reader> {
+        self.message.get_root()
+    }
+
+    pub fn into_inner(self) -> M {
+        self.message
+    }
+}
+
+impl <M, T> ::std::convert::From<M> for TypedReader<M, T>
+    where M : MessageReader, T : for<'a> Owned<'a>
+{
+    fn from(message : M) -> TypedReader<M, T> {
+        TypedReader::new(message)
+    }
+}
+
 #[derive(Clone, Copy)]
 pub enum AllocationStrategy {
     FixedSize,
@@ -302,3 +413,188 @@ impl <'b> MessageBuilder for ScratchSpaceMallocMessageBuilder<'b> {
         & *self.arena
     }
 }
+
+/// A message builder whose root is known to be of type `T`.
+pub struct TypedBuilder<T> where T : for<'a> Owned<'a> {
+    marker : ::std::marker::PhantomData<T>,
+    message : MallocMessageBuilder,
+}
+
+impl <T> TypedBuilder<T> where T : for<'a> Owned<'a> {
+    pub fn new(options : BuilderOptions) -> TypedBuilder<T> {
+        TypedBuilder::from(MallocMessageBuilder::new(options))
+    }
+
+    pub fn new_default() -> TypedBuilder<T> {
+        TypedBuilder::new(BuilderOptions::new())
+    }
+
+    /// Initializes the root, discarding any previous contents.
+    pub fn init_root<'a>(&'a mut self) -> <T as Owned<'a>>::Builder {
+        self.message.init_root()
+    }
+
+    /// Gets the root, initializing it if it has not been set.
+    pub fn get_root<'a>(&'a mut self) -> Result<<T as Owned<'a>>::Builder> {
+        self.message.get_root()
+    }
+
+    /// Sets the root to a deep copy of the given value.
+    pub fn set_root<'a, From>(&'a mut self, value : From) -> Result<()>
+        where From : SetPointerBuilder<<T as Owned<'a>>::Builder>
+    {
+        self.message.set_root(value)
+    }
+
+    pub fn into_inner(self) -> MallocMessageBuilder {
+        self.message
+    }
+}
+
+impl <T> ::std::convert::From<MallocMessageBuilder> for TypedBuilder<T> where T : for<'a> Owned<'a> {
+    fn from(message : MallocMessageBuilder) -> TypedBuilder<T> {
+        TypedBuilder { marker : ::std::marker::PhantomData, message : message }
+    }
+}
+
+impl <T> MessageBuilder for TypedBuilder<T> where T : for<'a> Owned<'a> {
+    fn arena_mut(&mut self) -> &mut BuilderArena {
+        self.message.arena_mut()
+    }
+    fn arena(&self) -> &BuilderArena {
+        self.message.arena()
+    }
+}
+
+/// Builds a new message whose root is filled in by `fill` and returns a reader that owns it. The
+/// first segment is sized to `word_count` words plus the root pointer, so the result is a single
+/// segment as long as `fill` writes no more than that.
+pub fn new_owned_reader<F>(word_count : u64, fill : F) -> Result<OwnedSpaceMessageReader>
+    where F : FnOnce(any_pointer::Builder) -> Result<()>
+{
+    if word_count >= (1 << 29) {
+        return Err(Error::new_decode_error("Value is too large to copy into a single segment.",
+                                           Some(format!("{} words", word_count))));
+    }
+
+    let mut message = MallocMessageBuilder::new(
+        *BuilderOptions::new()
+            .first_segment_words(word_count as u32 + WORDS_PER_POINTER as u32)
+            .allocation_strategy(AllocationStrategy::FixedSize));
+    try!(fill(message.get_root_internal()));
+
+    // The copy is trusted, so it does not need to count against a traversal limit.
+    let options = *ReaderOptions::new().traversal_limit_in_words(::std::u64::MAX);
+    let mut reader = OwnedSpaceMessageReader::new(message.get_segments_for_output(), options);
+    reader.init_cap_table(
+        message.get_cap_table().iter().map(|cap| cap.as_ref().map(|hook| hook.copy())).collect());
+    Ok(reader)
+}
+
+/// Deep-copies a struct into a new single-segment message, so that it can outlive the message it
+/// was read from. The type of the copy is given by `T`, e.g. `copy_to_owned::<foo::Owned>(reader)`.
+pub fn copy_to_owned<'a, T>(value : <T as Owned<'a>>::Reader)
+                            -> Result<TypedReader<OwnedSpaceMessageReader, T>>
+    where T : for<'b> Owned<'b>, <T as Owned<'a>>::Reader : IntoInternalStructReader<'a>
+{
+    let struct_reader = value.into_internal_struct_reader();
+    let size = try!(struct_reader.total_size());
+    let message = try!(new_owned_reader(size.word_count, |root| {
+        root.set_struct(&struct_reader)
+    }));
+    Ok(TypedReader::new(message))
+}
+
+/// Returns the canonical form of `message`: a single segment holding the root pointer followed
+/// by every object reachable from it in preorder, with no far pointers, and with trailing zero
+/// words and null pointers truncated from each struct. Messages with equal contents have equal
+/// canonical forms, so the result is suitable for hashing or signing. Fails if the message
+/// contains a capability.
+pub fn canonicalize<M : MessageReader>(message : &M) -> Result<Vec<Word>> {
+    let root = try!(message.get_root_internal());
+    let size = try!(root.target_size());
+    if size.word_count >= (1 << 29) {
+        return Err(Error::new_decode_error("Message is too large to canonicalize.",
+                                           Some(format!("{} words", size.word_count))));
+    }
+
+    // Truncation only shrinks objects, so the canonical form fits in the original size.
+    let mut builder = MallocMessageBuilder::new(
+        *BuilderOptions::new()
+            .first_segment_words(size.word_count as u32 + WORDS_PER_POINTER as u32)
+            .allocation_strategy(AllocationStrategy::FixedSize));
+    let src : layout::PointerReader = try!(root.get_as());
+    try!(builder.get_root_internal().init_as::<layout::PointerBuilder>().copy_canonical_from(src));
+    let segments = builder.get_segments_for_output();
+    assert_eq!(segments.len(), 1);
+    Ok(segments[0].to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use any_pointer;
+    use private::AlignedData;
+    use private::layout::{ElementSize, PointerReader};
+    use {text, text_list, Word};
+    use super::{MessageReader, ReaderOptions, SegmentArrayMessageReader, UncheckedMessageReader};
+
+    // A constant `["hi", "there"] :List(Text)`, laid out the way the code generator embeds one.
+    static CONSTANT : AlignedData<[u8; 40]> = AlignedData {
+        _dummy : 0,
+        data : [0x01, 0x00, 0x00, 0x00, 0x16, 0x00, 0x00, 0x00,
+                0x05, 0x00, 0x00, 0x00, 0x1a, 0x00, 0x00, 0x00,
+                0x05, 0x00, 0x00, 0x00, 0x32, 0x00, 0x00, 0x00,
+                b'h', b'i', 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                b't', b'h', b'e', b'r', b'e', 0x00, 0x00, 0x00]
+    };
+
+    #[test]
+    fn read_constant_unchecked() {
+        let reader = unsafe { UncheckedMessageReader::new(Word::bytes_to_words(&CONSTANT.data)) };
+        let list : text_list::Reader = reader.get_root().unwrap();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list.get(0).unwrap(), "hi");
+        assert_eq!(list.get(1).unwrap(), "there");
+    }
+
+    fn words(values : &[u64]) -> Vec<Word> {
+        values.iter().map(|&v| Word { _unused_member : v.to_le() }).collect()
+    }
+
+    #[test]
+    fn copy_across_far_pointers() {
+        let segments = vec![
+            words(&[0x0000_0001_0000_0002]),      // far -> 1:0
+            words(&[0x0000_0016_0000_0001,        // landing pad: list of two pointers
+                    0x0000_0002_0000_0006,        // double far -> 2:0
+                    0x0000_001a_0000_0001,        // 3 bytes at 1:3
+                    0x6b6f]),                     // "ok"
+            words(&[0x0000_0003_0000_0002,        // landing pad: far -> 3:0
+                    0x0000_001a_0000_0001]),      // tag: 3 bytes
+            words(&[0x6968])];                    // "hi"
+        let slices : Vec<&[Word]> = segments.iter().map(|s| &s[..]).collect();
+        let message = SegmentArrayMessageReader::new(&slices, ReaderOptions::new());
+        let root : any_pointer::Reader = message.get_root_internal().unwrap();
+
+        // The list and the two texts take four words, with nothing left over for far pointers.
+        let copy = root.copy_to_owned::<text_list::Owned>().unwrap().into_inner();
+        assert!(copy.arena().more_segments.is_empty());
+        assert_eq!(copy.get_segment(0).len(), 1 + 4);
+        let list : text_list::Reader = copy.get_root().unwrap();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list.get(0).unwrap(), "hi");
+        assert_eq!(list.get(1).unwrap(), "ok");
+
+        // Copying an element takes only its text, here from behind the double-far pointer.
+        let segment0 = &message.arena().segment0;
+        let root = PointerReader::get_root(segment0, unsafe { segment0.get_start_ptr() },
+                                           ReaderOptions::new().nesting_limit).unwrap();
+        let list = root.get_list(ElementSize::Pointer, ::std::ptr::null()).unwrap();
+        let element = any_pointer::Reader::new(list.get_pointer_element(0));
+        let copy = element.copy_to_owned::<text::Owned>().unwrap().into_inner();
+        assert!(copy.arena().more_segments.is_empty());
+        assert_eq!(copy.get_segment(0).len(), 2);
+        let text : text::Reader = copy.get_root().unwrap();
+        assert_eq!(text, "hi");
+    }
+}
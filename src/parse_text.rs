@@ -0,0 +1,666 @@
+// Copyright (c) 2013-2015 Sandstorm Development Group, Inc. and contributors
+// Licensed under the MIT License:
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Parsing of the Cap'n Proto text format into a message builder.
+//!
+//! The input is the format produced by `stringify`, e.g. `(name = "x", items = [(id = 1)])`.
+//! The outer parentheses may be omitted, and `#` starts a comment that runs to the end of the
+//! line, so the format is also convenient for hand-written test fixtures and config files.
+
+use std::fmt;
+
+use dynamic_list;
+use dynamic_struct;
+use dynamic_value;
+use message::MessageBuilder;
+use private::layout::PointerBuilder;
+use schema::{StructSchema, Type};
+
+/// A syntax error, or a value that does not fit the schema, at a position in the input.
+/// Lines and columns start at one; columns count characters, not bytes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    pub line : u32,
+    pub column : u32,
+    pub message : String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, fmt : &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl ::std::error::Error for ParseError {
+    fn description(&self) -> &str {
+        &self.message
+    }
+}
+
+pub type Result<T> = ::std::result::Result<T, ParseError>;
+
+/// Parses `text` as the contents of the struct that `builder` points at.
+pub fn parse_into(text : &str, builder : &dynamic_struct::Builder) -> Result<()> {
+    let fields = try!(Parser::new(text).parse_top_level());
+    apply_struct(builder, &fields)
+}
+
+/// Parses `text` as a struct of type `schema` and makes it the root of `message`.
+pub fn parse_message<M : MessageBuilder>(text : &str, schema : StructSchema,
+                                         message : &mut M) -> Result<()> {
+    let fields = try!(Parser::new(text).parse_top_level());
+    let root : PointerBuilder = message.get_root_internal().init_as();
+    let builder = dynamic_struct::Builder::new(
+        root.init_struct(dynamic_value::struct_size(schema)), schema);
+    apply_struct(&builder, &fields)
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Pos {
+    line : u32,
+    column : u32,
+}
+
+fn error<T>(pos : Pos, message : String) -> Result<T> {
+    Err(ParseError { line : pos.line, column : pos.column, message : message })
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum Token {
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    Comma,
+    Equals,
+    Minus,
+    Ident(String),
+    Int(u64),
+    Float(f64),
+    Str(Vec<u8>),
+    Data(Vec<u8>),
+    Eof,
+}
+
+fn describe(token : &Token) -> String {
+    match *token {
+        Token::LParen => "`(`".to_string(),
+        Token::RParen => "`)`".to_string(),
+        Token::LBracket => "`[`".to_string(),
+        Token::RBracket => "`]`".to_string(),
+        Token::Comma => "`,`".to_string(),
+        Token::Equals => "`=`".to_string(),
+        Token::Minus => "`-`".to_string(),
+        Token::Ident(ref name) => format!("`{}`", name),
+        Token::Int(_) | Token::Float(_) => "number".to_string(),
+        Token::Str(_) => "string".to_string(),
+        Token::Data(_) => "data".to_string(),
+        Token::Eof => "end of input".to_string(),
+    }
+}
+
+enum Expr {
+    Int(bool, u64),
+    Float(f64),
+    Ident(String),
+    Str(Vec<u8>),
+    Data(Vec<u8>),
+    List(Vec<(Pos, Expr)>),
+    Struct(Vec<FieldAssignment>),
+}
+
+struct FieldAssignment {
+    name_pos : Pos,
+    name : String,
+    value_pos : Pos,
+    value : Expr,
+}
+
+struct Lexer<'a> {
+    chars : ::std::iter::Peekable<::std::str::Chars<'a>>,
+    pos : Pos,
+}
+
+impl <'a> Lexer<'a> {
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().map(|c| *c)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next();
+        match c {
+            Some('\n') => { self.pos.line += 1; self.pos.column = 1; }
+            Some(_) => self.pos.column += 1,
+            None => (),
+        }
+        c
+    }
+
+    fn skip_whitespace_and_comments(&mut self) {
+        loop {
+            match self.peek() {
+                Some(c) if c.is_whitespace() => { self.bump(); }
+                Some('#') => {
+                    while let Some(c) = self.bump() {
+                        if c == '\n' { break; }
+                    }
+                }
+                _ => return,
+            }
+        }
+    }
+
+    /// Returns the next token and the position at which it starts.
+    fn next(&mut self) -> Result<(Pos, Token)> {
+        self.skip_whitespace_and_comments();
+        let start = self.pos;
+        let c = match self.peek() {
+            None => return Ok((start, Token::Eof)),
+            Some(c) => c,
+        };
+        let token = match c {
+            '(' => { self.bump(); Token::LParen }
+            ')' => { self.bump(); Token::RParen }
+            '[' => { self.bump(); Token::LBracket }
+            ']' => { self.bump(); Token::RBracket }
+            ',' => { self.bump(); Token::Comma }
+            '=' => { self.bump(); Token::Equals }
+            '-' => { self.bump(); Token::Minus }
+            '"' | '\'' => { self.bump(); Token::Str(try!(self.string(c, start))) }
+            c if c.is_digit(10) => try!(self.number(start)),
+            c if c.is_alphabetic() || c == '_' => {
+                let mut name = String::new();
+                while let Some(c) = self.peek() {
+                    if !(c.is_alphanumeric() || c == '_') { break; }
+                    name.push(c);
+                    self.bump();
+                }
+                Token::Ident(name)
+            }
+            c => return error(start, format!("unexpected character `{}`", c)),
+        };
+        Ok((start, token))
+    }
+
+    fn hex_digit(&mut self, start : Pos) -> Result<u8> {
+        let pos = self.pos;
+        match self.bump() {
+            Some(c) => match c.to_digit(16) {
+                Some(d) => Ok(d as u8),
+                None => error(pos, format!("expected a hex digit, found `{}`", c)),
+            },
+            None => error(start, "unterminated literal".to_string()),
+        }
+    }
+
+    fn string(&mut self, quote : char, start : Pos) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        loop {
+            let pos = self.pos;
+            let c = match self.bump() {
+                None => return error(start, "unterminated string".to_string()),
+                Some(c) => c,
+            };
+            if c == quote {
+                return Ok(bytes);
+            }
+            if c != '\\' {
+                bytes.extend(c.to_string().bytes());
+                continue;
+            }
+            match self.bump() {
+                Some('n') => bytes.push(b'\n'),
+                Some('r') => bytes.push(b'\r'),
+                Some('t') => bytes.push(b'\t'),
+                Some('0') => bytes.push(0),
+                Some('\\') => bytes.push(b'\\'),
+                Some('"') => bytes.push(b'"'),
+                Some('\'') => bytes.push(b'\''),
+                Some('x') => {
+                    let high = try!(self.hex_digit(start));
+                    let low = try!(self.hex_digit(start));
+                    bytes.push(high * 16 + low);
+                }
+                Some(c) => return error(pos, format!("unknown escape sequence `\\{}`", c)),
+                None => return error(start, "unterminated string".to_string()),
+            }
+        }
+    }
+
+    fn number(&mut self, start : Pos) -> Result<Token> {
+        let mut literal = String::new();
+        if self.peek() == Some('0') {
+            self.bump();
+            match self.peek() {
+                Some('x') | Some('X') => {
+                    self.bump();
+                    if self.peek() == Some('"') {
+                        self.bump();
+                        return Ok(Token::Data(try!(self.hex_data(start))));
+                    }
+                    while let Some(c) = self.peek() {
+                        if !c.is_digit(16) { break; }
+                        literal.push(c);
+                        self.bump();
+                    }
+                    return match u64::from_str_radix(&literal, 16) {
+                        Ok(v) => Ok(Token::Int(v)),
+                        Err(_) => error(start, "invalid hexadecimal number".to_string()),
+                    };
+                }
+                _ => literal.push('0'),
+            }
+        }
+        let mut is_float = false;
+        while let Some(c) = self.peek() {
+            if c.is_digit(10) {
+                literal.push(c);
+            } else if c == '.' || c == 'e' || c == 'E' {
+                is_float = true;
+                literal.push(c);
+            } else if (c == '-' || c == '+') && (literal.ends_with('e') || literal.ends_with('E')) {
+                literal.push(c);
+            } else {
+                break;
+            }
+            self.bump();
+        }
+        if is_float {
+            match literal.parse::<f64>() {
+                Ok(v) => Ok(Token::Float(v)),
+                Err(_) => error(start, format!("invalid number `{}`", literal)),
+            }
+        } else {
+            match literal.parse::<u64>() {
+                Ok(v) => Ok(Token::Int(v)),
+                Err(_) => error(start, format!("number `{}` is out of range", literal)),
+            }
+        }
+    }
+
+    /// The contents of a `0x"..."` data literal: pairs of hex digits, possibly separated by
+    /// whitespace.
+    fn hex_data(&mut self, start : Pos) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        loop {
+            match self.peek() {
+                Some('"') => { self.bump(); return Ok(bytes); }
+                Some(c) if c.is_whitespace() => { self.bump(); }
+                _ => {
+                    let high = try!(self.hex_digit(start));
+                    let low = try!(self.hex_digit(start));
+                    bytes.push(high * 16 + low);
+                }
+            }
+        }
+    }
+}
+
+// How deeply structs and lists may be nested. Parsing recurses once per level.
+const MAX_NESTING_DEPTH : u32 = 64;
+
+struct Parser<'a> {
+    lexer : Lexer<'a>,
+    lookahead : Option<(Pos, Token)>,
+}
+
+impl <'a> Parser<'a> {
+    fn new(text : &'a str) -> Parser<'a> {
+        Parser {
+            lexer : Lexer { chars : text.chars().peekable(), pos : Pos { line : 1, column : 1 } },
+            lookahead : None,
+        }
+    }
+
+    fn peek(&mut self) -> Result<(Pos, Token)> {
+        if self.lookahead.is_none() {
+            self.lookahead = Some(try!(self.lexer.next()));
+        }
+        Ok(self.lookahead.clone().unwrap())
+    }
+
+    fn next(&mut self) -> Result<(Pos, Token)> {
+        let result = try!(self.peek());
+        self.lookahead = None;
+        Ok(result)
+    }
+
+    fn expect(&mut self, expected : Token) -> Result<Pos> {
+        let (pos, token) = try!(self.next());
+        if token == expected {
+            Ok(pos)
+        } else {
+            error(pos, format!("expected {}, found {}", describe(&expected), describe(&token)))
+        }
+    }
+
+    /// Either a parenthesized field list or a bare one, followed by the end of the input.
+    fn parse_top_level(&mut self) -> Result<Vec<FieldAssignment>> {
+        let fields = if try!(self.peek()).1 == Token::LParen {
+            try!(self.next());
+            try!(self.parse_fields(Token::RParen, 0))
+        } else {
+            try!(self.parse_fields(Token::Eof, 0))
+        };
+        try!(self.expect(Token::Eof));
+        Ok(fields)
+    }
+
+    /// `name = value` pairs separated by commas, up to and including `close`. A trailing
+    /// comma is allowed. `depth` is the number of enclosing structs and lists.
+    fn parse_fields(&mut self, close : Token, depth : u32) -> Result<Vec<FieldAssignment>> {
+        let mut fields = Vec::new();
+        loop {
+            let (name_pos, token) = try!(self.next());
+            let name = match token {
+                ref t if *t == close => return Ok(fields),
+                Token::Ident(name) => name,
+                t => return error(name_pos, format!("expected a field name, found {}",
+                                                    describe(&t))),
+            };
+            try!(self.expect(Token::Equals));
+            let (value_pos, value) = try!(self.parse_value(depth));
+            fields.push(FieldAssignment { name_pos : name_pos, name : name,
+                                          value_pos : value_pos, value : value });
+            let (pos, token) = try!(self.next());
+            if token == close {
+                return Ok(fields);
+            } else if token != Token::Comma {
+                return error(pos, format!("expected `,` or {}, found {}",
+                                          describe(&close), describe(&token)));
+            }
+        }
+    }
+
+    fn parse_value(&mut self, depth : u32) -> Result<(Pos, Expr)> {
+        let (pos, token) = try!(self.next());
+        let value = match token {
+            Token::LParen | Token::LBracket if depth >= MAX_NESTING_DEPTH =>
+                return error(pos, format!("values are nested more than {} deep",
+                                          MAX_NESTING_DEPTH)),
+            Token::Int(v) => Expr::Int(false, v),
+            Token::Float(v) => Expr::Float(v),
+            Token::Minus => match try!(self.next()) {
+                (_, Token::Int(v)) => Expr::Int(true, v),
+                (_, Token::Float(v)) => Expr::Float(-v),
+                (_, Token::Ident(ref name)) if name == "inf" => Expr::Float(-::std::f64::INFINITY),
+                (pos, t) => return error(pos, format!("expected a number, found {}", describe(&t))),
+            },
+            Token::Ident(name) => Expr::Ident(name),
+            Token::Str(bytes) => Expr::Str(bytes),
+            Token::Data(bytes) => Expr::Data(bytes),
+            Token::LParen => Expr::Struct(try!(self.parse_fields(Token::RParen, depth + 1))),
+            Token::LBracket => {
+                let mut elements = Vec::new();
+                loop {
+                    if try!(self.peek()).1 == Token::RBracket {
+                        try!(self.next());
+                        break;
+                    }
+                    elements.push(try!(self.parse_value(depth + 1)));
+                    let (pos, token) = try!(self.next());
+                    match token {
+                        Token::RBracket => break,
+                        Token::Comma => (),
+                        t => return error(pos, format!("expected `,` or `]`, found {}",
+                                                       describe(&t))),
+                    }
+                }
+                Expr::List(elements)
+            }
+            t => return error(pos, format!("expected a value, found {}", describe(&t))),
+        };
+        Ok((pos, value))
+    }
+}
+
+fn capnp_error<T>(pos : Pos, e : ::Error) -> Result<T> {
+    error(pos, format!("{}", e))
+}
+
+/// Converts a literal that is not a struct or list to a dynamic value of type `type_`.
+fn scalar<'e>(pos : Pos, type_ : &Type, expr : &'e Expr) -> Result<dynamic_value::Reader<'e>> {
+    Ok(match *expr {
+        Expr::Int(false, v) => dynamic_value::Reader::UInt64(v),
+        Expr::Int(true, v) => {
+            if v <= ::std::i64::MAX as u64 {
+                dynamic_value::Reader::Int64(-(v as i64))
+            } else if v == 1 << 63 {
+                dynamic_value::Reader::Int64(::std::i64::MIN)
+            } else {
+                return error(pos, "number is out of range".to_string());
+            }
+        }
+        Expr::Float(v) => dynamic_value::Reader::Float64(v),
+        Expr::Ident(ref name) => match &name[..] {
+            "void" => dynamic_value::Reader::Void,
+            "true" => dynamic_value::Reader::Bool(true),
+            "false" => dynamic_value::Reader::Bool(false),
+            "inf" => dynamic_value::Reader::Float64(::std::f64::INFINITY),
+            "nan" => dynamic_value::Reader::Float64(::std::f64::NAN),
+            _ => match *type_ {
+                // An enumerant, which the setter looks up by name.
+                Type::Enum(_) => dynamic_value::Reader::Text(name),
+                _ => return error(pos, format!("unexpected identifier `{}`", name)),
+            },
+        },
+        Expr::Str(ref bytes) => match *type_ {
+            Type::Data => dynamic_value::Reader::Data(bytes),
+            _ => match ::std::str::from_utf8(bytes) {
+                Ok(s) => dynamic_value::Reader::Text(s),
+                Err(_) => return error(pos, "string is not valid UTF-8".to_string()),
+            },
+        },
+        Expr::Data(ref bytes) => dynamic_value::Reader::Data(bytes),
+        Expr::List(_) | Expr::Struct(_) =>
+            return error(pos, format!("expected a value of type {:?}", type_)),
+    })
+}
+
+fn apply_struct(builder : &dynamic_struct::Builder, fields : &[FieldAssignment]) -> Result<()> {
+    let schema = builder.get_schema();
+    for assignment in fields.iter() {
+        let field = match schema.find_field_by_name(&assignment.name) {
+            Some(field) => field,
+            None => return error(assignment.name_pos,
+                                 format!("struct `{}` has no field named `{}`",
+                                         schema.get_schema().get_short_name(), assignment.name)),
+        };
+        let pos = assignment.value_pos;
+        // Groups have the type of their struct node.
+        let type_ = field.get_type();
+        match (&type_, &assignment.value) {
+            (&Type::Struct(_), &Expr::Struct(ref fields)) => {
+                match builder.init(field) {
+                    Ok(dynamic_value::Builder::Struct(s)) => try!(apply_struct(&s, fields)),
+                    Ok(_) => unreachable!(),
+                    Err(e) => return capnp_error(pos, e),
+                }
+            }
+            (&Type::List(_), &Expr::List(ref elements)) => {
+                match builder.init_sized(field, elements.len() as u32) {
+                    Ok(dynamic_value::Builder::List(l)) => try!(apply_list(&l, elements)),
+                    Ok(_) => unreachable!(),
+                    Err(e) => return capnp_error(pos, e),
+                }
+            }
+            (&Type::Struct(_), _) | (&Type::List(_), _) | (_, &Expr::Struct(_)) |
+            (_, &Expr::List(_)) =>
+                return error(pos, format!("wrong kind of value for field `{}`", field.get_name())),
+            (_, value) => {
+                let value = try!(scalar(pos, &type_, value));
+                if let Err(e) = builder.set(field, value) {
+                    return capnp_error(pos, e);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn apply_list(builder : &dynamic_list::Builder, elements : &[(Pos, Expr)]) -> Result<()> {
+    let element_type = builder.get_element_type();
+    for (i, &(pos, ref element)) in elements.iter().enumerate() {
+        let i = i as u32;
+        match (element_type, element) {
+            (&Type::Struct(_), &Expr::Struct(ref fields)) => {
+                match builder.get(i) {
+                    Ok(dynamic_value::Builder::Struct(s)) => try!(apply_struct(&s, fields)),
+                    Ok(_) => unreachable!(),
+                    Err(e) => return capnp_error(pos, e),
+                }
+            }
+            (&Type::List(_), &Expr::List(ref elements)) => {
+                match builder.init(i, elements.len() as u32) {
+                    Ok(dynamic_value::Builder::List(l)) => try!(apply_list(&l, elements)),
+                    Ok(_) => unreachable!(),
+                    Err(e) => return capnp_error(pos, e),
+                }
+            }
+            (&Type::Struct(_), _) | (&Type::List(_), _) | (_, &Expr::Struct(_)) |
+            (_, &Expr::List(_)) =>
+                return error(pos, format!("wrong kind of value for a list of {:?}", element_type)),
+            (_, value) => {
+                let value = try!(scalar(pos, element_type, value));
+                if let Err(e) = builder.set(i, value) {
+                    return capnp_error(pos, e);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use dynamic_struct;
+    use dynamic_value;
+    use message::{canonicalize, MallocMessageBuilder, MessageBuilder, MessageReader, ReaderOptions,
+                  SegmentArrayMessageReader};
+    use private::layout::PointerReader;
+    use private::test_schema::{test_schema, FOO};
+    use schema::SchemaLoader;
+    use stringify;
+    use super::{parse_message, ParseError, Parser, MAX_NESTING_DEPTH};
+
+    fn parse(loader : &SchemaLoader, text : &str) -> MallocMessageBuilder {
+        let mut message = MallocMessageBuilder::new_default();
+        parse_message(text, loader.get_struct_schema(FOO).unwrap(), &mut message).unwrap();
+        message
+    }
+
+    fn parse_error(text : &str) -> ParseError {
+        let loader = test_schema();
+        let mut message = MallocMessageBuilder::new_default();
+        match parse_message(text, loader.get_struct_schema(FOO).unwrap(), &mut message) {
+            Err(e) => e,
+            Ok(()) => panic!("expected an error"),
+        }
+    }
+
+    fn to_string(loader : &SchemaLoader, message : &mut MallocMessageBuilder) -> String {
+        let segments = message.get_segments_for_output();
+        let reader = SegmentArrayMessageReader::new(segments, ReaderOptions::new());
+        stringify::message_to_string(&reader, loader.get_struct_schema(FOO).unwrap(),
+                                     &stringify::PrintOptions::new()).unwrap()
+    }
+
+    #[test]
+    fn parse_into_builder() {
+        let loader = test_schema();
+        let mut message = parse(&loader, "# A comment.\n\
+            a = 9, name = \"x\\ty\", items = [(id = 0x10, label = \"l\"), ()],\n\
+            nums = [-1, 2], e = green, uText = \"\u{e9}\", grp = (x = -2, y = 1.5)");
+
+        let segments = message.get_segments_for_output();
+        let reader = SegmentArrayMessageReader::new(segments, ReaderOptions::new());
+        let root : PointerReader = reader.get_root().unwrap();
+        let foo = dynamic_struct::Reader::new(root.get_struct(::std::ptr::null()).unwrap(),
+                                              loader.get_struct_schema(FOO).unwrap());
+        let get = |name| format!("{}", foo.get_named(name).unwrap());
+        assert_eq!(get("a"), "9");
+        assert_eq!(get("name"), "\"x\\ty\"");
+        assert_eq!(get("items"), "[(id = 16, label = \"l\"), (id = 0)]");
+        assert_eq!(get("nums"), "[-1, 2]");
+        assert_eq!(get("e"), "green");
+        assert_eq!(get("grp"), "(x = -2, y = 1.5)");
+        match foo.get_named("uText").unwrap() {
+            dynamic_value::Reader::Text(text) => assert_eq!(text, "\u{e9}"),
+            _ => panic!("expected text"),
+        }
+        assert_eq!(foo.which().unwrap().unwrap().get_name(), "uText");
+    }
+
+    #[test]
+    fn stringify_roundtrip() {
+        let loader = test_schema();
+        let mut first = parse(&loader, "(a = 5, b = true, name = \"\\x01\\n\", \
+                                         items = [(id = 18446744073709551615)], uInt = -7, \
+                                         grp = (y = -0.25))");
+        let text = to_string(&loader, &mut first);
+        let mut second = parse(&loader, &text);
+        assert_eq!(to_string(&loader, &mut second), text);
+
+        let canonical = |message : &mut MallocMessageBuilder| {
+            let segments = message.get_segments_for_output();
+            canonicalize(&SegmentArrayMessageReader::new(segments, ReaderOptions::new())).unwrap()
+        };
+        assert_eq!(::Word::words_to_bytes(&canonical(&mut first)),
+                   ::Word::words_to_bytes(&canonical(&mut second)));
+    }
+
+    #[test]
+    fn error_positions() {
+        // Columns count characters, so the two-byte `\u{e9}` takes up one.
+        let e = parse_error("a = 1,\n  uText = \"\u{e9}\", nmae = 2");
+        assert_eq!((e.line, e.column), (2, 16));
+        assert_eq!(e.message, "struct `Foo` has no field named `nmae`");
+
+        let e = parse_error("a = 1,\nitems = 5");
+        assert_eq!((e.line, e.column), (2, 9));
+        assert_eq!(e.message, "wrong kind of value for field `items`");
+
+        let e = parse_error("items = [(id = 1), 2]");
+        assert_eq!((e.line, e.column), (1, 20));
+
+        let e = parse_error("name = \"a\\qb\"");
+        assert_eq!((e.line, e.column), (1, 10));
+        assert_eq!(format!("{}", e), "1:10: unknown escape sequence `\\q`");
+    }
+
+    fn repeat(s : &str, n : u32) -> String {
+        (0..n).map(|_| s).collect()
+    }
+
+    #[test]
+    fn nesting_limit() {
+        let ok = format!("x = {}{}", repeat("[", MAX_NESTING_DEPTH), repeat("]", MAX_NESTING_DEPTH));
+        assert!(Parser::new(&ok).parse_top_level().is_ok());
+
+        // Alternate lists and structs, six characters per level.
+        let too_deep = format!("x = {}{}", repeat("[(x = ", MAX_NESTING_DEPTH),
+                               repeat("1)]", MAX_NESTING_DEPTH));
+        match Parser::new(&too_deep).parse_top_level() {
+            Err(e) => assert_eq!((e.line, e.column), (1, 5 + 6 * MAX_NESTING_DEPTH / 2)),
+            Ok(_) => panic!("expected an error"),
+        }
+    }
+}
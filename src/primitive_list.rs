@@ -21,11 +21,20 @@
 
 //! List of primitives.
 
-use traits::{FromPointerReader, FromPointerBuilder};
+use traits::{FromPointerReader, FromPointerBuilder, IndexMove, ListIter, ListAppender, ResizableList};
 use private::layout::{ListReader, ListBuilder, PointerReader, PointerBuilder,
                       PrimitiveElement, element_size_for_type};
 use Result;
 
+pub struct Owned<T> {
+    marker : ::std::marker::PhantomData<T>,
+}
+
+impl <'a, T : PrimitiveElement> ::traits::Owned<'a> for Owned<T> {
+    type Reader = Reader<'a, T>;
+    type Builder = Builder<'a, T>;
+}
+
 #[derive(Clone, Copy)]
 pub struct Reader<'a, T> {
     marker : ::std::marker::PhantomData<T>,
@@ -38,20 +47,72 @@ impl <'a, T : PrimitiveElement> Reader<'a, T> {
     }
 
     pub fn len(&self) -> u32 { self.reader.len() }
+
+    pub fn iter(self) -> ListIter<Reader<'a, T>, T> {
+        let l = self.len();
+        ListIter::new(self, l)
+    }
+
+    /// Returns the elements as a native slice, or `None` if the encoding of the list does not
+    /// match the in-memory layout of `T` on this host. That is the case on big-endian hosts,
+    /// for lists of `bool`, and for lists that have been upgraded to struct lists.
+    pub fn as_slice(&self) -> Option<&'a [T]> {
+        if self.len() == 0 {
+            Some(&[])
+        } else if has_native_layout::<T>(self.reader.get_step_size_in_bits()) {
+            let bytes = self.reader.into_raw_bytes();
+            if bytes.as_ptr() as usize % ::std::mem::align_of::<T>() != 0 { return None; }
+            Some(unsafe {
+                ::std::slice::from_raw_parts(bytes.as_ptr() as *const T, self.len() as usize)
+            })
+        } else {
+            None
+        }
+    }
+}
+
+fn has_native_layout<T : PrimitiveElement>(step : u32) -> bool {
+    cfg!(target_endian = "little") &&
+        <T as PrimitiveElement>::element_size() == element_size_for_type::<T>() &&
+        step as usize == ::std::mem::size_of::<T>() * 8
 }
 
 impl <'a, T : PrimitiveElement> FromPointerReader<'a> for Reader<'a, T> {
     fn get_from_pointer(reader : &PointerReader<'a>) -> Result<Reader<'a, T>> {
-        Ok(Reader { reader : try!(reader.get_list(element_size_for_type::<T>(), ::std::ptr::null())),
+        Ok(Reader { reader : try!(reader.get_list(<T as PrimitiveElement>::element_size(), ::std::ptr::null())),
                     marker : ::std::marker::PhantomData })
     }
 }
 
+impl <'a, T : PrimitiveElement>  IndexMove<u32, T> for Reader<'a, T> {
+    fn index_move(&self, index : u32) -> T {
+        self.get(index)
+    }
+}
+
 impl <'a, T : PrimitiveElement> Reader<'a, T> {
     pub fn get(&self, index : u32) -> T {
         assert!(index < self.len());
         PrimitiveElement::get(&self.reader, index)
     }
+
+    /// Like `get()`, but returns `None` instead of panicking if `index` is out of bounds.
+    pub fn try_get(&self, index : u32) -> Option<T> {
+        if index < self.len() {
+            Some(self.get(index))
+        } else {
+            None
+        }
+    }
+}
+
+impl <'a, T : PrimitiveElement> ::std::iter::IntoIterator for Reader<'a, T> {
+    type Item = T;
+    type IntoIter = ListIter<Reader<'a, T>, T>;
+
+    fn into_iter(self) -> ListIter<Reader<'a, T>, T> {
+        self.iter()
+    }
 }
 
 pub struct Builder<'a, T> {
@@ -69,15 +130,60 @@ impl <'a, T : PrimitiveElement> Builder<'a, T> {
     pub fn set(&mut self, index : u32, value : T) {
         PrimitiveElement::set(&self.builder, index, value);
     }
+
+    /// Shrinks the list to `new_len` elements. See `ListBuilder::truncate()`.
+    pub fn truncate(&mut self, new_len : u32) -> Result<()> {
+        unsafe { self.builder.truncate(new_len) }
+    }
+
+    /// Extends the list to `new_len` elements. See `ListBuilder::grow()`.
+    pub fn grow(&mut self, new_len : u32) -> Result<()> {
+        unsafe { self.builder.grow(new_len) }
+    }
+
+    pub fn iter(self) -> ListIter<Builder<'a, T>, ElementBuilder<'a, T>> {
+        let l = self.len();
+        ListIter::new(self, l)
+    }
+
+    /// Mutable counterpart of `Reader::as_slice()`.
+    pub fn as_mut_slice<'b>(&'b mut self) -> Option<&'b mut [T]> {
+        if self.len() == 0 {
+            Some(&mut [])
+        } else if has_native_layout::<T>(self.builder.get_step_size_in_bits()) {
+            let bytes = self.builder.into_raw_bytes();
+            if bytes.as_ptr() as usize % ::std::mem::align_of::<T>() != 0 { return None; }
+            Some(unsafe {
+                ::std::slice::from_raw_parts_mut(bytes.as_mut_ptr() as *mut T, self.len() as usize)
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Sets every element of the list. Panics if `values` and the list differ in length.
+    pub fn copy_from_slice(&mut self, values : &[T]) {
+        assert!(values.len() == self.len() as usize);
+        match self.as_mut_slice() {
+            Some(slice) => {
+                slice.copy_from_slice(values);
+                return;
+            }
+            None => {}
+        }
+        for (index, value) in values.iter().enumerate() {
+            self.set(index as u32, *value);
+        }
+    }
 }
 
 impl <'a, T : PrimitiveElement> FromPointerBuilder<'a> for Builder<'a, T> {
     fn init_pointer(builder : PointerBuilder<'a>, size : u32) -> Builder<'a, T> {
-        Builder { builder : builder.init_list(element_size_for_type::<T>(), size),
+        Builder { builder : builder.init_list(<T as PrimitiveElement>::element_size(), size),
                   marker : ::std::marker::PhantomData }
     }
     fn get_from_pointer(builder : PointerBuilder<'a>) -> Result<Builder<'a, T>> {
-        Ok(Builder { builder : try!(builder.get_list(element_size_for_type::<T>(), ::std::ptr::null())),
+        Ok(Builder { builder : try!(builder.get_list(<T as PrimitiveElement>::element_size(), ::std::ptr::null())),
                      marker : ::std::marker::PhantomData })
     }
 }
@@ -87,6 +193,61 @@ impl <'a, T : PrimitiveElement> Builder<'a, T> {
         assert!(index < self.len());
         PrimitiveElement::get_from_builder(&self.builder, index)
     }
+
+    /// Like `get()`, but returns `None` instead of panicking if `index` is out of bounds.
+    pub fn try_get(&self, index : u32) -> Option<T> {
+        if index < self.len() {
+            Some(self.get(index))
+        } else {
+            None
+        }
+    }
+}
+
+impl <'a, T : PrimitiveElement>  IndexMove<u32, ElementBuilder<'a, T>> for Builder<'a, T> {
+    fn index_move(&self, index : u32) -> ElementBuilder<'a, T> {
+        assert!(index < self.len());
+        ::traits::ElementBuilder::new(Builder { builder : self.builder, marker : ::std::marker::PhantomData }, index)
+    }
+}
+
+impl <'a, T : PrimitiveElement> ::std::iter::IntoIterator for Builder<'a, T> {
+    type Item = ElementBuilder<'a, T>;
+    type IntoIter = ListIter<Builder<'a, T>, ElementBuilder<'a, T>>;
+
+    fn into_iter(self) -> ListIter<Builder<'a, T>, ElementBuilder<'a, T>> {
+        self.iter()
+    }
+}
+
+impl <'a, T : PrimitiveElement> ResizableList for Builder<'a, T> {
+    fn len(&self) -> u32 { self.builder.len() }
+    fn truncate(&mut self, new_len : u32) -> Result<()> {
+        unsafe { self.builder.truncate(new_len) }
+    }
+    fn grow(&mut self, new_len : u32) -> Result<()> {
+        unsafe { self.builder.grow(new_len) }
+    }
+}
+
+impl <'a, T : PrimitiveElement> ListAppender<Builder<'a, T>> {
+    pub fn push(&mut self, value : T) -> Result<()> {
+        let index = try!(self.next_index());
+        self.get_list().set(index, value);
+        Ok(())
+    }
+}
+
+pub type ElementBuilder<'a, T> = ::traits::ElementBuilder<Builder<'a, T>>;
+
+impl <'a, T : PrimitiveElement> ::traits::ElementBuilder<Builder<'a, T>> {
+    pub fn get(&self) -> T {
+        PrimitiveElement::get_from_builder(&self.get_list().builder, self.index())
+    }
+
+    pub fn set(&mut self, value : T) {
+        PrimitiveElement::set(&self.get_list().builder, self.index(), value);
+    }
 }
 
 impl <'a, T> ::traits::SetPointerBuilder<Builder<'a, T>> for Reader<'a, T> {
@@ -101,3 +262,154 @@ impl <'a, 'b : 'a, T> ::traits::CastableTo<Builder<'a, T> > for Builder<'b, T> {
         Builder { builder : self.builder, marker : ::std::marker::PhantomData }
     }
 }
+
+impl <'a> Reader<'a, bool> {
+    /// Returns a view of the list's packed bits, or `None` if the list is not encoded as a bit
+    /// list (e.g. if it was written as a list of bytes by an older version of this library).
+    pub fn as_bits(&self) -> Option<Bits<'a>> {
+        if self.reader.get_step_size_in_bits() == 1 || self.len() == 0 {
+            Some(Bits { bytes : self.reader.into_raw_bytes(), len : self.len() })
+        } else {
+            None
+        }
+    }
+}
+
+impl <'a> Builder<'a, bool> {
+    /// Mutable counterpart of `Reader::as_bits()`.
+    pub fn as_bits_mut<'b>(&'b mut self) -> Option<BitsMut<'b>> {
+        if self.builder.get_step_size_in_bits() == 1 || self.len() == 0 {
+            Some(BitsMut { bytes : self.builder.into_raw_bytes(), len : self.len() })
+        } else {
+            None
+        }
+    }
+}
+
+#[inline]
+fn get_bit(bytes : &[u8], index : u32) -> bool {
+    bytes[(index / 8) as usize] & (1 << (index % 8)) != 0
+}
+
+fn count_ones(bytes : &[u8], len : u32) -> u32 {
+    let full_bytes = (len / 8) as usize;
+    let mut result = 0;
+    for b in &bytes[..full_bytes] {
+        result += b.count_ones();
+    }
+    if len % 8 != 0 {
+        result += (bytes[full_bytes] & ((1u8 << (len % 8)) - 1)).count_ones();
+    }
+    result
+}
+
+/// A read-only view of a list of bools, with element `i` stored in bit `i % 8` of byte `i / 8`.
+#[derive(Clone, Copy)]
+pub struct Bits<'a> {
+    bytes : &'a [u8],
+    len : u32,
+}
+
+impl <'a> Bits<'a> {
+    pub fn len(&self) -> u32 { self.len }
+
+    pub fn get(&self, index : u32) -> bool {
+        assert!(index < self.len);
+        get_bit(self.bytes, index)
+    }
+
+    /// The number of elements that are `true`.
+    pub fn count_ones(&self) -> u32 { count_ones(self.bytes, self.len) }
+
+    /// Iterates over the indices of the elements that are `true`, in increasing order.
+    pub fn iter_ones(&self) -> SetBits<'a> {
+        SetBits { bytes : self.bytes, len : self.len, byte_index : 0, current : 0 }
+    }
+}
+
+/// A mutable view of a list of bools. See `Bits`.
+pub struct BitsMut<'a> {
+    bytes : &'a mut [u8],
+    len : u32,
+}
+
+impl <'a> BitsMut<'a> {
+    pub fn len(&self) -> u32 { self.len }
+
+    pub fn as_bits<'b>(&'b self) -> Bits<'b> {
+        Bits { bytes : self.bytes, len : self.len }
+    }
+
+    pub fn get(&self, index : u32) -> bool {
+        assert!(index < self.len);
+        get_bit(self.bytes, index)
+    }
+
+    pub fn set(&mut self, index : u32, value : bool) {
+        assert!(index < self.len);
+        let b = &mut self.bytes[(index / 8) as usize];
+        let bitnum = index % 8;
+        *b = (*b & !(1 << bitnum)) | ((value as u8) << bitnum);
+    }
+
+    pub fn count_ones(&self) -> u32 { count_ones(self.bytes, self.len) }
+
+    pub fn iter_ones<'b>(&'b self) -> SetBits<'b> {
+        self.as_bits().iter_ones()
+    }
+
+    /// Sets every element. Panics if `values` and the list differ in length.
+    pub fn set_from_bools(&mut self, values : &[bool]) {
+        assert!(values.len() == self.len as usize);
+        for (chunk, b) in values.chunks(8).zip(self.bytes.iter_mut()) {
+            let mut byte = 0u8;
+            for (bitnum, value) in chunk.iter().enumerate() {
+                byte |= (*value as u8) << bitnum;
+            }
+            *b = byte;
+        }
+    }
+
+    /// Sets every element from a little-endian bitmask, in which element `i` is bit `i % 8` of
+    /// `mask[i / 8]`. Panics if `mask` does not have exactly `(len + 7) / 8` bytes. Bits of
+    /// the last byte beyond the end of the list are ignored.
+    pub fn set_from_bitmask(&mut self, mask : &[u8]) {
+        let num_bytes = ((self.len + 7) / 8) as usize;
+        assert!(mask.len() == num_bytes);
+        self.bytes[..num_bytes].copy_from_slice(mask);
+        if self.len % 8 != 0 {
+            self.bytes[num_bytes - 1] &= (1u8 << (self.len % 8)) - 1;
+        }
+    }
+}
+
+/// Iterator over the indices of the set bits of a `Bits`.
+pub struct SetBits<'a> {
+    bytes : &'a [u8],
+    len : u32,
+    byte_index : u32,
+    current : u8,
+}
+
+impl <'a> ::std::iter::Iterator for SetBits<'a> {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<u32> {
+        loop {
+            if self.current != 0 {
+                let index = (self.byte_index - 1) * 8 + self.current.trailing_zeros();
+                self.current &= self.current - 1;
+                if index < self.len {
+                    return Some(index);
+                } else {
+                    return None;
+                }
+            }
+            if self.byte_index * 8 >= self.len {
+                return None;
+            }
+            self.current = self.bytes[self.byte_index as usize];
+            self.byte_index += 1;
+        }
+    }
+}
@@ -127,6 +127,24 @@ impl SegmentBuilder {
         }
     }
 
+    /// Extends the most recent allocation, which must end at `from`, so that it ends at `to`.
+    /// Returns false if `from` is not the end of the allocated space or the segment is too small.
+    pub fn try_extend(&mut self, from : *mut Word, to : *mut Word) -> bool {
+        if self.pos != from { return false; }
+        let end = self.get_ptr_unchecked(self.reader.size);
+        if to as usize > end as usize { return false; }
+        self.pos = to;
+        true
+    }
+
+    /// Gives back the space between `to` and `from`, if `from` is the end of the allocated space.
+    /// The space must already be zeroed.
+    pub fn try_truncate(&mut self, from : *mut Word, to : *mut Word) -> bool {
+        if self.pos != from { return false; }
+        self.pos = to;
+        true
+    }
+
     pub fn currently_allocated<'a>(&'a self) -> &'a [Word] {
         unsafe { ::std::slice::from_raw_parts(self.get_ptr_unchecked(0), self.current_size() as usize) }
     }
@@ -164,6 +182,8 @@ pub struct ReaderArena {
     pub cap_table : Vec<Option<Box<ClientHook+Send>>>,
 
     pub read_limiter : ::std::rc::Rc<ReadLimiter>,
+
+    pub options : message::ReaderOptions,
 }
 
 unsafe impl Send for ReaderArena {}
@@ -182,6 +202,7 @@ impl ReaderArena {
             more_segments : Vec::new(),
             cap_table : Vec::new(),
             read_limiter : limiter.clone(),
+            options : options,
         });
 
 
@@ -399,6 +420,15 @@ impl ArenaPtr {
         }
     }
 
+    /// Gets the options that the message is being read with. Messages that are being built are
+    /// trusted, so they have no options.
+    pub fn get_reader_options(&self) -> Option<message::ReaderOptions> {
+        match self {
+            &ArenaPtr::Reader(reader) => unsafe { Some((*reader).options) },
+            _ => None,
+        }
+    }
+
     pub fn extract_cap(&self, index : usize) -> Option<Box<ClientHook+Send>> {
         unsafe {
             match self {
@@ -388,6 +388,38 @@ mod wire_helpers {
         }
     }
 
+    #[inline]
+    pub unsafe fn check_list_element_count(segment : *const SegmentReader,
+                                           element_count : ElementCount32) -> Result<()> {
+        if !segment.is_null() {
+            match (*segment).arena.get_reader_options() {
+                Some(ref options) if element_count > options.list_element_count_limit => {
+                    return Err(Error::new_decode_error(
+                        "Message contains list with too many elements. See ReaderOptions.",
+                        Some(format!("{} elements", element_count))));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub unsafe fn check_blob_size(segment : *const SegmentReader,
+                                  size : ByteCount32) -> Result<()> {
+        if !segment.is_null() {
+            match (*segment).arena.get_reader_options() {
+                Some(ref options) if size > options.blob_size_limit_in_bytes => {
+                    return Err(Error::new_decode_error(
+                        "Message contains text or data that is too large. See ReaderOptions.",
+                        Some(format!("{} bytes", size))));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
     #[inline]
     pub unsafe fn allocate(reff : &mut *mut WirePointer,
                            segment : &mut *mut SegmentBuilder,
@@ -711,7 +743,8 @@ mod wire_helpers {
                 }
             }
             WirePointerKind::Far => {
-                panic!("Unexpected FAR pointer.");
+                // A landing pad that holds another far pointer.
+                return Err(Error::new_decode_error("Unexpected FAR pointer.", None));
             }
             WirePointerKind::Other => {
                 if (*reff).is_capability() {
@@ -768,7 +801,18 @@ mod wire_helpers {
             match (*src_segment).allocate(1) {
                 None => {
                     //# Darn, need a double-far.
-                    panic!("unimplemented");
+                    let (far_segment, landing_pad_word) = (*(*src_segment).get_arena()).allocate(2);
+                    let landing_pad : *mut WirePointer = ::std::mem::transmute(landing_pad_word);
+
+                    (*landing_pad).set_far(false, (*src_segment).get_word_offset_to(src_ptr));
+                    (*landing_pad).mut_far_ref().set((*src_segment).get_segment_id());
+
+                    (*landing_pad.offset(1)).set_kind_with_zero_offset((*src_tag).kind());
+                    ::std::ptr::copy_nonoverlapping(&(*src_tag).upper32bits,
+                                                    &mut (*landing_pad.offset(1)).upper32bits, 1);
+
+                    (*dst).set_far(true, (*far_segment).get_word_offset_to(landing_pad_word));
+                    (*dst).mut_far_ref().set((*far_segment).get_segment_id());
                 }
                 Some(landing_pad_word) => {
                     //# Simple landing pad is just a pointer.
@@ -888,6 +932,9 @@ mod wire_helpers {
         assert!(element_size != InlineComposite,
                 "Should have called initStructListPointer() instead");
 
+        let origin = reff;
+        let origin_segment = segment_builder;
+
         let data_size = data_bits_per_element(element_size);
         let pointer_count = pointers_per_element(element_size);
         let step = data_size + pointer_count * BITS_PER_POINTER as u32;
@@ -903,7 +950,10 @@ mod wire_helpers {
             step : step,
             element_count : element_count,
             struct_data_size : data_size,
-            struct_pointer_count : pointer_count as u16
+            struct_pointer_count : pointer_count as u16,
+            element_size : element_size,
+            origin : origin,
+            origin_segment : origin_segment,
         }
     }
 
@@ -912,6 +962,8 @@ mod wire_helpers {
                                                mut segment_builder : *mut SegmentBuilder,
                                                element_count : ElementCount32,
                                                element_size : StructSize) -> ListBuilder<'a> {
+        let origin = reff;
+        let origin_segment = segment_builder;
         let words_per_element = element_size.total();
 
         //# Allocate the list, prefixed by a single WirePointer.
@@ -934,7 +986,10 @@ mod wire_helpers {
             step : words_per_element * BITS_PER_WORD as u32,
             element_count : element_count,
             struct_data_size : element_size.data as u32 * (BITS_PER_WORD as u32),
-            struct_pointer_count : element_size.pointers
+            struct_pointer_count : element_size.pointers,
+            element_size : InlineComposite,
+            origin : origin,
+            origin_segment : origin_segment,
         }
     }
 
@@ -951,7 +1006,18 @@ mod wire_helpers {
         if (*orig_ref).is_null() {
             if default_value.is_null() ||
                 (*::std::mem::transmute::<*const Word,*const WirePointer>(default_value)).is_null() {
-                    return Ok(ListBuilder::new_default());
+                    // Remember where the list would go, so that it can be grown later.
+                    let data_size = data_bits_per_element(element_size);
+                    let pointer_count = pointers_per_element(element_size);
+                    return Ok(ListBuilder {
+                        step : data_size + pointer_count * BITS_PER_POINTER as u32,
+                        struct_data_size : data_size,
+                        struct_pointer_count : pointer_count as u16,
+                        element_size : element_size,
+                        origin : orig_ref,
+                        origin_segment : orig_segment,
+                        .. ListBuilder::new_default()
+                    });
                 }
             unimplemented!()
         }
@@ -1025,7 +1091,10 @@ mod wire_helpers {
                 element_count : (*tag).inline_composite_list_element_count(),
                 step : (*tag).struct_ref().word_size() * BITS_PER_WORD as u32,
                 struct_data_size : data_size as u32 * BITS_PER_WORD as u32,
-                struct_pointer_count : pointer_count
+                struct_pointer_count : pointer_count,
+                element_size : InlineComposite,
+                origin : orig_ref,
+                origin_segment : orig_segment,
             });
         } else {
             let data_size = data_bits_per_element(old_size);
@@ -1046,7 +1115,10 @@ mod wire_helpers {
                 step : step,
                 element_count : (*reff).list_ref().element_count(),
                 struct_data_size : data_size,
-                struct_pointer_count : pointer_count as u16
+                struct_pointer_count : pointer_count as u16,
+                element_size : old_size,
+                origin : orig_ref,
+                origin_segment : orig_segment,
             });
         }
     }
@@ -1061,7 +1133,16 @@ mod wire_helpers {
         if (*orig_ref).is_null() {
             if default_value.is_null() ||
                 (*::std::mem::transmute::<*const Word,*const WirePointer>(default_value)).is_null() {
-                    return Ok(ListBuilder::new_default());
+                    // Remember where the list would go, so that it can be grown later.
+                    return Ok(ListBuilder {
+                        step : element_size.total() * BITS_PER_WORD as u32,
+                        struct_data_size : element_size.data as u32 * BITS_PER_WORD as u32,
+                        struct_pointer_count : element_size.pointers,
+                        element_size : InlineComposite,
+                        origin : orig_ref,
+                        origin_segment : orig_segment,
+                        .. ListBuilder::new_default()
+                    });
                 }
             unimplemented!()
         }
@@ -1104,7 +1185,10 @@ mod wire_helpers {
                     element_count : element_count,
                     step : old_step * BITS_PER_WORD as u32,
                     struct_data_size : old_data_size as u32 * BITS_PER_WORD as u32,
-                    struct_pointer_count : old_pointer_count
+                    struct_pointer_count : old_pointer_count,
+                    element_size : InlineComposite,
+                    origin : orig_ref,
+                    origin_segment : orig_segment,
                 });
             }
 
@@ -1121,6 +1205,9 @@ mod wire_helpers {
     pub unsafe fn init_text_pointer<'a>(mut reff : *mut WirePointer,
                                         mut segment : *mut SegmentBuilder,
                                         size : ByteCount32) -> SegmentAnd<text::Builder<'a>> {
+        let origin = reff;
+        let origin_segment = segment;
+
         //# The byte list must include a NUL terminator.
         let byte_size = size + 1;
 
@@ -1131,11 +1218,20 @@ mod wire_helpers {
         //# Initialize the pointer.
         (*reff).mut_list_ref().set(Byte, byte_size);
 
+        let list = ListBuilder {
+            marker : ::std::marker::PhantomData::<&'a ()>,
+            segment : segment,
+            ptr : ::std::mem::transmute(ptr),
+            element_count : byte_size,
+            step : BITS_PER_BYTE as u32,
+            struct_data_size : BITS_PER_BYTE as u32,
+            struct_pointer_count : 0,
+            element_size : Byte,
+            origin : origin,
+            origin_segment : origin_segment,
+        };
         return SegmentAnd {segment : segment,
-                                  value : text::Builder::new(
-                                      ::std::slice::from_raw_parts_mut(::std::mem::transmute(ptr),
-                                                                       size as usize),
-                                      0).unwrap() }
+                           value : text::Builder::new_growable(list, 0).unwrap() }
     }
 
     #[inline]
@@ -1154,9 +1250,12 @@ mod wire_helpers {
                                                 mut segment : *mut SegmentBuilder,
                                                 _default_value : *const Word,
                                                 default_size : ByteCount32) -> Result<text::Builder<'a>> {
+        let origin = reff;
+        let origin_segment = segment;
         if (*reff).is_null() {
             if default_size == 0 {
-                return text::Builder::new(::std::slice::from_raw_parts_mut(::std::ptr::null_mut(), 0), 0);
+                return text::Builder::new_growable(
+                    try!(get_writable_list_pointer(origin, origin_segment, Byte, ::std::ptr::null())), 0);
             } else {
                 let _builder = init_text_pointer(reff, segment, default_size).value;
                 unimplemented!()
@@ -1182,7 +1281,8 @@ mod wire_helpers {
         }
 
         // Subtract 1 from the size for the NUL terminator.
-        return text::Builder::new(::std::slice::from_raw_parts_mut(cptr, (count - 1) as usize), count - 1);
+        return text::Builder::new_growable(
+            try!(get_writable_list_pointer(origin, origin_segment, Byte, ::std::ptr::null())), count - 1);
     }
 
     #[inline]
@@ -1241,26 +1341,56 @@ mod wire_helpers {
         return Ok(data::new_builder(::std::mem::transmute(ptr), (*reff).list_ref().element_count()));
     }
 
+    /// The data and pointer section sizes of a struct with its trailing zero words and null
+    /// pointers removed, as required by the canonical encoding.
+    unsafe fn canonical_struct_size(data : *const Word, data_size : BitCount32,
+                                    pointers : *const WirePointer,
+                                    pointer_count : WirePointerCount16) -> (WordCount32, WirePointerCount16) {
+        let mut data_words = if data_size == 1 {
+            (*::std::mem::transmute::<*const Word, *const u8>(data) & 1) as u32
+        } else {
+            data_size / BITS_PER_WORD as u32
+        };
+        while data_words > 0 &&
+            *::std::mem::transmute::<*const Word, *const u64>(data.offset(data_words as isize - 1)) == 0 {
+            data_words -= 1;
+        }
+        let mut pointer_count = pointer_count;
+        while pointer_count > 0 && (*pointers.offset(pointer_count as isize - 1)).is_null() {
+            pointer_count -= 1;
+        }
+        (data_words, pointer_count)
+    }
+
     pub unsafe fn set_struct_pointer<'a>(mut segment : *mut SegmentBuilder,
                                          mut reff : *mut WirePointer,
-                                         value : StructReader) -> Result<SegmentAnd<*mut Word>> {
-        let data_size : WordCount32 = round_bits_up_to_words(value.data_size as u64);
-        let total_size : WordCount32 = data_size + value.pointer_count as u32 * WORDS_PER_POINTER as u32;
+                                         value : StructReader,
+                                         canonical : bool) -> Result<SegmentAnd<*mut Word>> {
+        let (data_size, pointer_count) = if canonical {
+            canonical_struct_size(::std::mem::transmute(value.data), value.data_size,
+                                  value.pointers, value.pointer_count)
+        } else {
+            (round_bits_up_to_words(value.data_size as u64), value.pointer_count)
+        };
+        let total_size : WordCount32 = data_size + pointer_count as u32 * WORDS_PER_POINTER as u32;
 
         let ptr = allocate(&mut reff, &mut segment, total_size, WirePointerKind::Struct);
-        (*reff).mut_struct_ref().set(data_size as u16, value.pointer_count);
+        (*reff).mut_struct_ref().set(data_size as u16, pointer_count);
 
         if value.data_size == 1 {
-            *::std::mem::transmute::<*mut Word, *mut u8>(ptr) = value.get_bool_field(0) as u8
+            if data_size > 0 {
+                *::std::mem::transmute::<*mut Word, *mut u8>(ptr) = value.get_bool_field(0) as u8
+            }
         } else {
-            ::std::ptr::copy_nonoverlapping::<Word>(::std::mem::transmute(value.data), ptr,
-                                                    value.data_size as usize / BITS_PER_WORD);
+            ::std::ptr::copy_nonoverlapping::<Word>(
+                ::std::mem::transmute(value.data), ptr,
+                ::std::cmp::min(data_size as usize, value.data_size as usize / BITS_PER_WORD));
         }
 
         let pointer_section : *mut WirePointer = ::std::mem::transmute(ptr.offset(data_size as isize));
-        for i in 0..value.pointer_count as isize {
+        for i in 0..pointer_count as isize {
             try!(copy_pointer(segment, pointer_section.offset(i), value.segment, value.pointers.offset(i),
-                              value.nesting_limit));
+                              value.nesting_limit, canonical));
         }
 
         Ok(SegmentAnd { segment : segment, value : ptr })
@@ -1274,10 +1404,14 @@ mod wire_helpers {
 
     pub unsafe fn set_list_pointer<'a>(mut segment : *mut SegmentBuilder,
                                        mut reff : *mut WirePointer,
-                                       value : ListReader) -> Result<SegmentAnd<*mut Word>> {
+                                       value : ListReader,
+                                       canonical : bool) -> Result<SegmentAnd<*mut Word>> {
         let total_size = round_bits_up_to_words((value.element_count * value.step) as u64);
 
-        if value.step <= BITS_PER_WORD as u32 {
+        // Decide by the source's encoding rather than its step, so that a struct list keeps its
+        // InlineComposite form even when its elements are no larger than a word. The canonical
+        // encoding requires that.
+        if value.element_size != InlineComposite {
             //# List of non-structs.
             let ptr = allocate(&mut reff, &mut segment, total_size, WirePointerKind::List);
 
@@ -1288,21 +1422,11 @@ mod wire_helpers {
                     try!(copy_pointer(segment, ::std::mem::transmute::<*mut Word,*mut WirePointer>(ptr).offset(i),
                                       value.segment,
                                       ::std::mem::transmute::<*const u8,*const WirePointer>(value.ptr).offset(i),
-                                      value.nesting_limit));
+                                      value.nesting_limit, canonical));
                 }
             } else {
                 //# List of data.
-                let element_size = match value.step {
-                    0 => Void,
-                    1 => Bit,
-                    8 => Byte,
-                    16 => TwoBytes,
-                    32 => FourBytes,
-                    64 => EightBytes,
-                    _ => { panic!("invalid list step size: {}", value.step) }
-                };
-
-                (*reff).mut_list_ref().set(element_size, value.element_count);
+                (*reff).mut_list_ref().set(value.element_size, value.element_count);
                 ::std::ptr::copy_nonoverlapping(::std::mem::transmute::<*const u8,*const Word>(value.ptr),
                                                 ptr,
                                                 total_size as usize);
@@ -1311,12 +1435,32 @@ mod wire_helpers {
             Ok(SegmentAnd { segment : segment, value : ptr })
         } else {
             //# List of structs.
+            let src_data_size = round_bits_up_to_words(value.struct_data_size as u64);
+            let src_step = value.step / BITS_PER_WORD as u32;
+
+            let (data_size, pointer_count) = if canonical {
+                // Every element gets the size of the largest element after truncation.
+                let mut data_size = 0;
+                let mut pointer_count = 0;
+                let mut src : *const Word = ::std::mem::transmute(value.ptr);
+                for _ in 0..value.element_count {
+                    let (d, p) = canonical_struct_size(
+                        src, value.struct_data_size,
+                        ::std::mem::transmute(src.offset(src_data_size as isize)),
+                        value.struct_pointer_count);
+                    data_size = ::std::cmp::max(data_size, d);
+                    pointer_count = ::std::cmp::max(pointer_count, p);
+                    src = src.offset(src_step as isize);
+                }
+                (data_size, pointer_count)
+            } else {
+                (src_data_size, value.struct_pointer_count)
+            };
+            let total_size = value.element_count * (data_size + pointer_count as u32 * WORDS_PER_POINTER as u32);
+
             let ptr = allocate(&mut reff, &mut segment, total_size + POINTER_SIZE_IN_WORDS as u32, WirePointerKind::List);
             (*reff).mut_list_ref().set_inline_composite(total_size);
 
-            let data_size = round_bits_up_to_words(value.struct_data_size as u64);
-            let pointer_count = value.struct_pointer_count;
-
             let tag : *mut WirePointer = ::std::mem::transmute(ptr);
             (*tag).set_kind_and_inline_composite_list_element_count(WirePointerKind::Struct, value.element_count);
             (*tag).mut_struct_ref().set(data_size as u16, pointer_count);
@@ -1324,25 +1468,27 @@ mod wire_helpers {
 
             let mut src : *const Word = ::std::mem::transmute(value.ptr);
             for _ in 0.. value.element_count {
-                ::std::ptr::copy_nonoverlapping(src, dst,
-                                                value.struct_data_size as usize / BITS_PER_WORD);
+                ::std::ptr::copy_nonoverlapping(src, dst, data_size as usize);
                 dst = dst.offset(data_size as isize);
-                src = src.offset(data_size as isize);
 
-                for _ in 0..pointer_count {
+                let src_pointers = src.offset(src_data_size as isize);
+                for i in 0..pointer_count as isize {
                     try!(copy_pointer(segment, ::std::mem::transmute(dst),
-                                      value.segment, ::std::mem::transmute(src), value.nesting_limit));
+                                      value.segment, ::std::mem::transmute(src_pointers.offset(i)),
+                                      value.nesting_limit, canonical));
                     dst = dst.offset(POINTER_SIZE_IN_WORDS as isize);
-                    src = src.offset(POINTER_SIZE_IN_WORDS as isize);
                 }
+                src = src.offset(src_step as isize);
             }
             Ok(SegmentAnd { segment : segment, value : ptr })
         }
     }
 
+    /// Deep-copies the object `src` points to. With `canonical` set, the copy is made in the
+    /// canonical encoding: structs are truncated and capabilities are rejected.
     pub unsafe fn copy_pointer(dst_segment : *mut SegmentBuilder, dst : *mut WirePointer,
                                mut src_segment : *const SegmentReader, mut src : *const WirePointer,
-                               nesting_limit : i32) -> Result<SegmentAnd<*mut Word>> {
+                               nesting_limit : i32, canonical : bool) -> Result<SegmentAnd<*mut Word>> {
         let src_target = (*src).target();
 
         if (*src).is_null() {
@@ -1371,7 +1517,8 @@ mod wire_helpers {
                         pointers : ::std::mem::transmute(ptr.offset((*src).struct_ref().data_size.get() as isize)),
                         data_size : (*src).struct_ref().data_size.get() as u32 * BITS_PER_WORD as u32,
                         pointer_count : (*src).struct_ref().ptr_count.get(),
-                        nesting_limit : nesting_limit - 1 });
+                        nesting_limit : nesting_limit - 1 },
+                    canonical);
 
             }
             WirePointerKind::List => {
@@ -1418,8 +1565,10 @@ mod wire_helpers {
                             step : words_per_element * BITS_PER_WORD as u32,
                             struct_data_size : (*tag).struct_ref().data_size.get() as u32 * BITS_PER_WORD as u32,
                             struct_pointer_count : (*tag).struct_ref().ptr_count.get(),
+                            element_size : InlineComposite,
                             nesting_limit : nesting_limit - 1
-                        })
+                        },
+                        canonical)
                 } else {
                     let data_size = data_bits_per_element(element_size);
                     let pointer_count = pointers_per_element(element_size);
@@ -1445,8 +1594,10 @@ mod wire_helpers {
                             step : step,
                             struct_data_size : data_size,
                             struct_pointer_count : pointer_count as u16,
+                            element_size : element_size,
                             nesting_limit : nesting_limit - 1
-                        })
+                        },
+                        canonical)
                 }
             }
             WirePointerKind::Far => {
@@ -1456,6 +1607,10 @@ mod wire_helpers {
                 if !(*src).is_capability() {
                     return Err(Error::new_decode_error("Unknown pointer type.", None));
                 }
+                if canonical {
+                    return Err(Error::new_decode_error(
+                        "Cannot create a canonical message with a capability.", None));
+                }
                 match (*src_segment).arena.extract_cap((*src).cap_ref().index.get() as usize) {
                     Some(cap) => {
                         set_capability_pointer(dst_segment, dst, cap);
@@ -1524,6 +1679,10 @@ mod wire_helpers {
         } else if !(*reff).is_capability() {
             return Err(Error::new_decode_error(
                 "Message contains non-capability pointer where capability pointer was expected.", None));
+        } else if segment.is_null() {
+            //# Unchecked messages have no capability table.
+            return Err(Error::new_decode_error(
+                "Unchecked message contains a capability pointer.", None));
         } else {
             let n = (*reff).cap_ref().index.get() as usize;
             match (*segment).arena.extract_cap(n) {
@@ -1598,6 +1757,8 @@ mod wire_helpers {
                     try!(amplified_read(segment, size as u64));
                 }
 
+                try!(check_list_element_count(segment, size));
+
                 // If a struct list was not expected, then presumably a non-struct list was upgraded
                 // to a struct list. We need to manipulate the pointer to point at the first field
                 // of the struct. Together with the "stepBits", this will allow the struct list to
@@ -1637,6 +1798,7 @@ mod wire_helpers {
                     step : words_per_element * BITS_PER_WORD as u32,
                     struct_data_size : struct_ref.data_size.get() as u32 * (BITS_PER_WORD as u32),
                     struct_pointer_count : struct_ref.ptr_count.get(),
+                    element_size : InlineComposite,
                     nesting_limit : nesting_limit - 1
                 });
             }
@@ -1658,6 +1820,8 @@ mod wire_helpers {
                     try!(amplified_read(segment, element_count as u64));
                 }
 
+                try!(check_list_element_count(segment, element_count));
+
                 // Verify that the elements are at least as large as the expected type. Note that if
                 // we expected InlineComposite, the expected sizes here will be zero, because bounds
                 // checking will be performed at field access time. So this check here is for the
@@ -1680,6 +1844,7 @@ mod wire_helpers {
                     step : step,
                     struct_data_size : data_size,
                     struct_pointer_count : pointer_count as u16,
+                    element_size : element_size,
                     nesting_limit : nesting_limit - 1
                 });
             }
@@ -1687,14 +1852,32 @@ mod wire_helpers {
     }
 
     #[inline]
-    pub unsafe fn read_text_pointer<'a>(mut segment : *const SegmentReader,
-                                        mut reff : *const WirePointer,
+    pub unsafe fn read_text_pointer<'a>(segment : *const SegmentReader,
+                                        reff : *const WirePointer,
                                         default_value : *const Word,
                                         default_size : ByteCount32) -> Result<text::Reader<'a>> {
+        let bytes = try!(read_text_pointer_as_bytes(segment, reff, default_value, default_size));
+        if !segment.is_null() {
+            match (*segment).arena.get_reader_options() {
+                Some(ref options) if options.get_skip_utf8_validation() => {
+                    return Ok(::std::str::from_utf8_unchecked(bytes));
+                }
+                _ => {}
+            }
+        }
+        text::new_reader(bytes)
+    }
+
+    /// Like `read_text_pointer()`, but does not check that the text is UTF-8.
+    pub unsafe fn read_text_pointer_as_bytes<'a>(mut segment : *const SegmentReader,
+                                                 mut reff : *const WirePointer,
+                                                 default_value : *const Word,
+                                                 default_size : ByteCount32) -> Result<&'a [u8]> {
         if (*reff).is_null() {
             //   TODO?       if default_value.is_null() { default_value = &"" }
-            return text::new_reader(
-                ::std::slice::from_raw_parts(::std::mem::transmute(default_value), default_size as usize));
+            if default_size == 0 { return Ok(&[]); }
+            return Ok(::std::slice::from_raw_parts(::std::mem::transmute(default_value),
+                                                   default_size as usize));
         }
 
         let ref_target = (*reff).target();
@@ -1720,6 +1903,8 @@ mod wire_helpers {
             return Err(Error::new_decode_error("Message contains text that is not NUL-terminated.", None));
         }
 
+        try!(check_blob_size(segment, size - 1));
+
         let str_ptr = ::std::mem::transmute::<*const Word,*const u8>(ptr);
 
         if (*str_ptr.offset((size - 1) as isize)) != 0u8 {
@@ -1727,7 +1912,7 @@ mod wire_helpers {
                 "Message contains text that is not NUL-terminated", None));
         }
 
-        Ok(try!(text::new_reader(::std::slice::from_raw_parts(str_ptr, size as usize -1))))
+        Ok(::std::slice::from_raw_parts(str_ptr, size as usize -1))
     }
 
     #[inline]
@@ -1761,6 +1946,8 @@ mod wire_helpers {
                           ptr.offset(round_bytes_up_to_words(size) as isize),
                           WirePointerKind::List));
 
+        try!(check_blob_size(segment, size));
+
         Ok(data::new_reader(::std::mem::transmute(ptr), size))
     }
 }
@@ -1837,6 +2024,20 @@ impl <'a> PointerReader<'a> {
         }
     }
 
+    /// Reads text without checking that it is UTF-8. The NUL terminator is not included.
+    pub fn get_text_as_bytes(&self, default_value : *const Word,
+                             default_size : ByteCount32) -> Result<&'a [u8]> {
+        unsafe {
+            wire_helpers::read_text_pointer_as_bytes(self.segment, self.pointer, default_value, default_size)
+        }
+    }
+
+    /// Reads text, replacing any invalid UTF-8 sequences with U+FFFD.
+    pub fn get_text_lossy(&self, default_value : *const Word,
+                          default_size : ByteCount32) -> Result<::std::borrow::Cow<'a, str>> {
+        Ok(String::from_utf8_lossy(try!(self.get_text_as_bytes(default_value, default_size))))
+    }
+
     pub fn get_data(&self, default_value : *const Word, default_size : ByteCount32) -> Result<data::Reader<'a>> {
         unsafe {
             wire_helpers::read_data_pointer(self.segment, self.pointer, default_value, default_size)
@@ -1957,14 +2158,14 @@ impl <'a> PointerBuilder<'a> {
 
     pub fn set_struct(&self, value : &StructReader) -> Result<()> {
         unsafe {
-            try!(wire_helpers::set_struct_pointer(self.segment, self.pointer, *value));
+            try!(wire_helpers::set_struct_pointer(self.segment, self.pointer, *value, false));
             Ok(())
         }
     }
 
     pub fn set_list(&self, value : &ListReader) -> Result<()> {
         unsafe {
-            try!(wire_helpers::set_list_pointer(self.segment, self.pointer, *value));
+            try!(wire_helpers::set_list_pointer(self.segment, self.pointer, *value, false));
             Ok(())
         }
     }
@@ -1981,6 +2182,32 @@ impl <'a> PointerBuilder<'a> {
         }
     }
 
+    pub fn copy_from(&self, other : PointerReader) -> Result<()> {
+        self.copy_from_internal(other, false)
+    }
+
+    /// Like `copy_from()`, but writes the copy in the canonical encoding. Into an empty,
+    /// sufficiently large segment, that produces the canonical form of `other`.
+    pub fn copy_canonical_from(&self, other : PointerReader) -> Result<()> {
+        self.copy_from_internal(other, true)
+    }
+
+    fn copy_from_internal(&self, other : PointerReader, canonical : bool) -> Result<()> {
+        unsafe {
+            if !(*self.pointer).is_null() {
+                wire_helpers::zero_object(self.segment, self.pointer);
+                ::std::ptr::write_bytes(self.pointer, 0, 1);
+            }
+            if other.is_null() {
+                return Ok(());
+            }
+            try!(wire_helpers::copy_pointer(self.segment, self.pointer,
+                                            other.segment, other.pointer, other.nesting_limit,
+                                            canonical));
+            Ok(())
+        }
+    }
+
     pub fn set_capability(&self, cap : Box<ClientHook+Send>) {
         unsafe {
             wire_helpers::set_capability_pointer(self.segment, self.pointer, cap);
@@ -2209,6 +2436,62 @@ impl <'a> StructBuilder<'a> {
         }
     }
 
+    /// Overwrites this struct with a deep copy of `other`. Fields that don't fit in this struct's
+    /// data and pointer sections are dropped, and fields that `other` lacks are zeroed.
+    pub fn copy_content_from(&self, other : &StructReader) -> Result<()> {
+        use std::cmp::min;
+        // Determine the amount of data the builders have in common.
+        let shared_data_size = min(self.data_size, other.data_size);
+        let shared_pointer_count = min(self.pointer_count, other.pointer_count);
+
+        if (shared_data_size > 0 && other.data == self.data as *const u8) ||
+            (shared_pointer_count > 0 && other.pointers == self.pointers as *const WirePointer) {
+            // At least one of the section pointers is pointing to ourself. Verify that the other is too
+            // (but ignore empty sections).
+            if (shared_data_size == 0 || other.data == self.data as *const u8) &&
+                (shared_pointer_count == 0 || other.pointers == self.pointers as *const WirePointer) {
+                return Ok(());
+            } else {
+                return Err(::Error::new_decode_error("Tried to copy a struct into itself?", None));
+            }
+        }
+
+        unsafe {
+            if self.data_size > shared_data_size {
+                // Since the target is larger than the source, make sure to zero out the extra bits that the
+                // source doesn't have.
+                if self.data_size == 1 {
+                    self.set_bool_field(0, false);
+                } else {
+                    let unshared = self.data.offset((shared_data_size as usize / BITS_PER_BYTE) as isize);
+                    ::std::ptr::write_bytes(
+                        unshared, 0, ((self.data_size - shared_data_size) as usize) / BITS_PER_BYTE);
+                }
+            }
+
+            // Copy over the shared part.
+            if shared_data_size == 1 {
+                self.set_bool_field(0, other.get_bool_field(0));
+            } else {
+                ::std::ptr::copy_nonoverlapping(other.data, self.data,
+                                                shared_data_size as usize / BITS_PER_BYTE);
+            }
+
+            // Zero out all pointers in the target.
+            for i in 0..self.pointer_count as isize {
+                wire_helpers::zero_object(self.segment, self.pointers.offset(i));
+            }
+            ::std::ptr::write_bytes(self.pointers, 0, self.pointer_count as usize);
+
+            // Copy the pointers.
+            for i in 0..shared_pointer_count as isize {
+                try!(wire_helpers::copy_pointer(self.segment, self.pointers.offset(i),
+                                                other.segment, other.pointers.offset(i),
+                                                other.nesting_limit, false));
+            }
+        }
+        Ok(())
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -2220,6 +2503,7 @@ pub struct ListReader<'a> {
     step : BitCount32,
     struct_data_size : BitCount32,
     struct_pointer_count : WirePointerCount16,
+    element_size : ElementSize,
     nesting_limit : i32
 }
 
@@ -2230,12 +2514,30 @@ impl <'a> ListReader<'a> {
             marker : ::std::marker::PhantomData::<&'b ()>,
             segment : ::std::ptr::null(),
             ptr : ::std::ptr::null(), element_count : 0, step: 0, struct_data_size : 0,
-            struct_pointer_count : 0, nesting_limit : 0x7fffffff}
+            struct_pointer_count : 0, element_size : Void, nesting_limit : 0x7fffffff}
     }
 
     #[inline]
     pub fn len(&self) -> ElementCount32 { self.element_count }
 
+    #[inline]
+    pub fn get_element_size(&self) -> ElementSize { self.element_size }
+
+    /// Distance between the starts of consecutive elements.
+    #[inline]
+    pub fn get_step_size_in_bits(&self) -> BitCount32 { self.step }
+
+    /// The bytes spanned by the list's elements, not including any tag word.
+    pub fn into_raw_bytes(self) -> &'a [u8] {
+        if self.element_count == 0 {
+            &[]
+        } else {
+            let num_bytes = (self.element_count as usize * self.step as usize + BITS_PER_BYTE - 1)
+                / BITS_PER_BYTE;
+            unsafe { ::std::slice::from_raw_parts(self.ptr, num_bytes) }
+        }
+    }
+
     pub fn get_struct_element(&self, index : ElementCount32) -> StructReader<'a> {
         let index_bit : BitCount64 = index as ElementCount64 * (self.step as BitCount64);
 
@@ -2279,7 +2581,12 @@ pub struct ListBuilder<'a> {
     element_count : ElementCount32,
     step : BitCount32,
     struct_data_size : BitCount32,
-    struct_pointer_count : WirePointerCount16
+    struct_pointer_count : WirePointerCount16,
+    element_size : ElementSize,
+
+    // The pointer that owns this list, and its segment. Needed to resize the list. Null if unknown.
+    origin : *mut WirePointer,
+    origin_segment : *mut SegmentBuilder,
 }
 
 impl <'a> ListBuilder<'a> {
@@ -2289,13 +2596,214 @@ impl <'a> ListBuilder<'a> {
         ListBuilder {
             marker : ::std::marker::PhantomData::<&'b ()>,
             segment : ::std::ptr::null_mut(), ptr : ::std::ptr::null_mut(), element_count : 0,
-            step : 0, struct_data_size : 0, struct_pointer_count : 0
+            step : 0, struct_data_size : 0, struct_pointer_count : 0, element_size : Void,
+            origin : ::std::ptr::null_mut(), origin_segment : ::std::ptr::null_mut()
+        }
+    }
+
+    #[inline]
+    pub fn get_element_size(&self) -> ElementSize { self.element_size }
+
+    pub fn as_reader(&self) -> ListReader<'a> {
+        let segment : *const SegmentReader = if self.segment.is_null() {
+            ::std::ptr::null()
+        } else {
+            unsafe { &(*self.segment).reader }
+        };
+        ListReader {
+            marker : ::std::marker::PhantomData::<&'a ()>,
+            segment : segment,
+            ptr : self.ptr as *const u8,
+            element_count : self.element_count,
+            step : self.step,
+            struct_data_size : self.struct_data_size,
+            struct_pointer_count : self.struct_pointer_count,
+            element_size : self.element_size,
+            nesting_limit : 0x7fffffff
+        }
+    }
+
+    // Number of words taken up by `element_count` elements, not counting any tag.
+    fn words_for(&self, element_count : ElementCount32) -> WordCount32 {
+        wire_helpers::round_bits_up_to_words(element_count as u64 * self.step as u64)
+    }
+
+    // Follows `origin` to the list's WirePointer and returns it along with the first element.
+    unsafe fn find_list(&self) -> Result<(*mut WirePointer, *mut Word)> {
+        let mut reff = self.origin;
+        let mut segment = self.origin_segment;
+        let target = try!(wire_helpers::follow_builder_fars(&mut reff, (*self.origin).mut_target(),
+                                                            &mut segment));
+        if self.element_size == InlineComposite {
+            Ok((reff, target.offset(POINTER_SIZE_IN_WORDS as isize)))
+        } else {
+            Ok((reff, target))
+        }
+    }
+
+    unsafe fn set_list_len(&mut self, reff : *mut WirePointer, start : *mut Word,
+                           element_count : ElementCount32) {
+        if self.element_size == InlineComposite {
+            let tag : *mut WirePointer =
+                ::std::mem::transmute(start.offset(-(POINTER_SIZE_IN_WORDS as isize)));
+            (*tag).set_kind_and_inline_composite_list_element_count(WirePointerKind::Struct, element_count);
+            (*reff).mut_list_ref().set_inline_composite(self.words_for(element_count));
+        } else {
+            (*reff).mut_list_ref().set(self.element_size, element_count);
+        }
+        self.element_count = element_count;
+    }
+
+    /// Shrinks the list to `new_len` elements. The dropped elements are zeroed, and their space is
+    /// given back to the segment if the list was the last thing allocated there.
+    ///
+    /// Unsafe because other builders for this list or its elements, which would be left pointing
+    /// at words that may since have been handed out again, must not be used afterwards.
+    pub unsafe fn truncate(&mut self, new_len : ElementCount32) -> Result<()> {
+        assert!(new_len <= self.element_count, "Cannot truncate a list to a larger size.");
+        if new_len == self.element_count { return Ok(()); }
+        assert!(!self.origin.is_null(), "List builder does not know where its list is stored.");
+        let (reff, start) = try!(self.find_list());
+        let elements : *mut u8 = ::std::mem::transmute(start);
+        let step = self.step as u64;
+
+        if self.struct_pointer_count > 0 {
+            for index in new_len..self.element_count {
+                let pointers : *mut WirePointer = ::std::mem::transmute(
+                    elements.offset(((index as u64 * step + self.struct_data_size as u64)
+                                     / BITS_PER_BYTE as u64) as isize));
+                for i in 0..self.struct_pointer_count as isize {
+                    wire_helpers::zero_object(self.segment, pointers.offset(i));
+                }
+            }
+        }
+
+        let new_bits = new_len as u64 * step;
+        let old_bytes = (self.element_count as u64 * step + 7) / BITS_PER_BYTE as u64;
+        let new_bytes = (new_bits + 7) / BITS_PER_BYTE as u64;
+        if new_bits % BITS_PER_BYTE as u64 != 0 {
+            // Part of the last byte survives (only possible for bit lists).
+            let b = elements.offset((new_bits / BITS_PER_BYTE as u64) as isize);
+            *b &= (1u8 << (new_bits % BITS_PER_BYTE as u64)) - 1;
+        }
+        ::std::ptr::write_bytes(elements.offset(new_bytes as isize), 0,
+                                (old_bytes - new_bytes) as usize);
+
+        (*self.segment).try_truncate(start.offset(self.words_for(self.element_count) as isize),
+                                     start.offset(self.words_for(new_len) as isize));
+        self.set_list_len(reff, start, new_len);
+        Ok(())
+    }
+
+    /// Extends the list to `new_len` elements, the new ones zeroed. The list is extended in place
+    /// if it was the last thing allocated in its segment; otherwise it is moved to new space and
+    /// the old copy is zeroed.
+    ///
+    /// Unsafe because other builders for this list or its elements, which would be left pointing
+    /// at the zeroed copy, must not be used afterwards.
+    pub unsafe fn grow(&mut self, new_len : ElementCount32) -> Result<()> {
+        assert!(new_len >= self.element_count, "Cannot grow a list to a smaller size.");
+        if new_len == self.element_count { return Ok(()); }
+        assert!(!self.origin.is_null(), "List builder does not know where its list is stored.");
+        if (*self.origin).is_null() {
+            // The list has not been allocated yet.
+            let origin = self.origin;
+            let origin_segment = self.origin_segment;
+            *self = self.init_like(origin, origin_segment, new_len);
+            return Ok(());
+        }
+
+        let (reff, start) = try!(self.find_list());
+        if (*self.segment).try_extend(start.offset(self.words_for(self.element_count) as isize),
+                                      start.offset(self.words_for(new_len) as isize)) {
+            self.set_list_len(reff, start, new_len);
+            return Ok(());
+        }
+
+        // Remember where the old copy lives, including any landing pad, so that it can be
+        // zeroed once everything has been moved out.
+        let mut pad : *mut Word = ::std::ptr::null_mut();
+        let mut pad_words : isize = 0;
+        if (*self.origin).kind() == WirePointerKind::Far {
+            let pad_segment = try!((*(*self.origin_segment).get_arena()).get_segment(
+                (*self.origin).far_ref().segment_id.get()));
+            pad = (*pad_segment).get_ptr_unchecked((*self.origin).far_position_in_segment());
+            pad_words = if (*self.origin).is_double_far() { 2 } else { 1 };
+        }
+        let tag_words = if self.element_size == InlineComposite { POINTER_SIZE_IN_WORDS } else { 0 };
+        let old_start = start.offset(-(tag_words as isize));
+        let old_words = self.words_for(self.element_count) as usize + tag_words;
+
+        let old = *self;
+        let old_elements : *mut u8 = ::std::mem::transmute(start);
+        ::std::ptr::write_bytes(self.origin, 0, 1);
+        let mut new = self.init_like(self.origin, self.origin_segment, new_len);
+        let new_elements = new.ptr;
+
+        if old.struct_pointer_count == 0 {
+            ::std::ptr::copy_nonoverlapping(
+                old_elements, new_elements,
+                ((old.element_count as u64 * old.step as u64 + 7) / BITS_PER_BYTE as u64) as usize);
+        } else {
+            let data_bytes = (old.struct_data_size / BITS_PER_BYTE as u32) as usize;
+            for index in 0..old.element_count {
+                let offset = (index as u64 * old.step as u64 / BITS_PER_BYTE as u64) as isize;
+                let src = old_elements.offset(offset);
+                let dst = new_elements.offset(offset);
+                ::std::ptr::copy_nonoverlapping(src, dst, data_bytes);
+                let src_pointers : *mut WirePointer =
+                    ::std::mem::transmute(src.offset(data_bytes as isize));
+                let dst_pointers : *mut WirePointer =
+                    ::std::mem::transmute(dst.offset(data_bytes as isize));
+                for i in 0..old.struct_pointer_count as isize {
+                    wire_helpers::transfer_pointer(new.segment, dst_pointers.offset(i),
+                                                   old.segment, src_pointers.offset(i));
+                }
+            }
+        }
+        ::std::ptr::write_bytes(old_start, 0, old_words);
+        if !pad.is_null() {
+            ::std::ptr::write_bytes(pad, 0, pad_words as usize);
+        }
+
+        // Keep pointing at the same part of each element as before. (See
+        // get_writable_list_pointer(), which may point into the middle of a struct.)
+        new.ptr = new.ptr.offset(old.ptr as isize - old_elements as isize);
+        *self = new;
+        Ok(())
+    }
+
+    // Allocates a new list with the same layout as this one.
+    unsafe fn init_like(&self, origin : *mut WirePointer, origin_segment : *mut SegmentBuilder,
+                        element_count : ElementCount32) -> ListBuilder<'a> {
+        if self.element_size == InlineComposite {
+            wire_helpers::init_struct_list_pointer(
+                origin, origin_segment, element_count,
+                StructSize { data : (self.step / BITS_PER_WORD as u32) as u16 - self.struct_pointer_count,
+                             pointers : self.struct_pointer_count })
+        } else {
+            wire_helpers::init_list_pointer(origin, origin_segment, element_count, self.element_size)
         }
     }
 
     #[inline]
     pub fn len(&self) -> ElementCount32 { self.element_count }
 
+    /// Distance between the starts of consecutive elements.
+    #[inline]
+    pub fn get_step_size_in_bits(&self) -> BitCount32 { self.step }
+
+    /// The bytes spanned by the list's elements, not including any tag word.
+    pub fn into_raw_bytes(self) -> &'a mut [u8] {
+        if self.element_count == 0 {
+            &mut []
+        } else {
+            let num_bytes = (self.element_count as usize * self.step as usize + BITS_PER_BYTE - 1)
+                / BITS_PER_BYTE;
+            unsafe { ::std::slice::from_raw_parts_mut(self.ptr, num_bytes) }
+        }
+    }
+
     pub fn get_struct_element(&self, index : ElementCount32) -> StructBuilder<'a> {
         let index_bit = index * self.step;
         let struct_data = unsafe{ self.ptr.offset((index_bit / BITS_PER_BYTE as u32) as isize)};
@@ -2326,7 +2834,13 @@ impl <'a> ListBuilder<'a> {
 }
 
 
-pub trait PrimitiveElement : Endian {
+pub trait PrimitiveElement : Endian + Copy {
+    /// The size of a list element when encoding a list of this type.
+    #[inline]
+    fn element_size() -> ElementSize {
+        element_size_for_type::<Self>()
+    }
+
     #[inline]
     fn get(list_reader : &ListReader, index : ElementCount32) -> Self {
         unsafe {
@@ -2372,6 +2886,12 @@ impl PrimitiveElement for f32 { }
 impl PrimitiveElement for f64 { }
 
 impl PrimitiveElement for bool {
+    // Lists of bools are bit-packed on the wire. `size_of::<bool>()` is one byte, so without
+    // this override they would be encoded as byte lists, which other implementations reject.
+    // Byte-encoded bool lists are still readable, because `get()` honors the list's step.
+    #[inline]
+    fn element_size() -> ElementSize { Bit }
+
     #[inline]
     fn get(list : &ListReader, index : ElementCount32) -> bool {
         let bindex : BitCount0 = index as ElementCount * list.step as usize;
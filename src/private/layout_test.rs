@@ -61,3 +61,234 @@ fn simple_raw_data_struct() {
     assert_eq!(reader.get_bool_field(63), true);
     assert_eq!(reader.get_bool_field(64), false);
 }
+
+#[test]
+fn bool_list_encoding() {
+    use message::{MallocMessageBuilder, MessageBuilder};
+    use traits::FromPointerReader;
+    use {primitive_list, Word};
+
+    let mut message = MallocMessageBuilder::new_default();
+    {
+        let mut list : primitive_list::Builder<bool> =
+            message.get_root_internal().init_as_sized(10);
+        list.set(0, true);
+        list.set(9, true);
+    }
+    // A bit list of ten elements, packed into the word after the root pointer.
+    assert_eq!(Word::words_to_bytes(message.get_segments_for_output()[0]),
+               &[0x01, 0x00, 0x00, 0x00, 0x51, 0x00, 0x00, 0x00,
+                 0x01, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00][..]);
+
+    // Older versions of this library wrote bool lists with one byte per element.
+    let data : ::private::AlignedData<[u8; 16]> = ::private::AlignedData {
+        _dummy: 0,
+        data : [0x01, 0x00, 0x00, 0x00, 0x1a, 0x00, 0x00, 0x00,
+                0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00]
+    };
+    let pointer = unsafe { ::private::layout::PointerReader::get_root_unchecked(
+        ::std::mem::transmute(data.data.as_ptr())) };
+    let list : primitive_list::Reader<bool> = FromPointerReader::get_from_pointer(&pointer).unwrap();
+    assert_eq!(list.len(), 3);
+    assert_eq!((list.get(0), list.get(1), list.get(2)), (true, false, true));
+}
+
+#[test]
+fn primitive_list_slices() {
+    use message::{MallocMessageBuilder, MessageBuilder};
+    use primitive_list;
+
+    let mut message = MallocMessageBuilder::new_default();
+    {
+        let mut list : primitive_list::Builder<u32> =
+            message.get_root_internal().init_as_sized(3);
+        list.copy_from_slice(&[1, 2, 3]);
+        if cfg!(target_endian = "little") {
+            list.as_mut_slice().unwrap()[1] = 20;
+        } else {
+            assert!(list.as_mut_slice().is_none());
+            list.set(1, 20);
+        }
+    }
+    {
+        let list : primitive_list::Reader<u32> =
+            message.get_root_internal().as_reader().get_as().unwrap();
+        assert_eq!((list.get(0), list.get(1), list.get(2)), (1, 20, 3));
+        if cfg!(target_endian = "little") {
+            assert_eq!(list.as_slice(), Some(&[1, 20, 3][..]));
+        }
+    }
+    {
+        let mut list : primitive_list::Builder<u64> = message.get_root_internal().init_as_sized(0);
+        assert_eq!(list.as_mut_slice(), Some(&mut [][..]));
+    }
+
+    // Bool lists are bit-packed, so they have no native slice and copy_from_slice() sets each bit.
+    let mut list : primitive_list::Builder<bool> = message.get_root_internal().init_as_sized(3);
+    assert!(list.as_mut_slice().is_none());
+    list.copy_from_slice(&[true, false, true]);
+    assert_eq!((list.get(0), list.get(1), list.get(2)), (true, false, true));
+}
+
+#[test]
+fn grow_list_with_double_far_landing_pad() {
+    use message::{AllocationStrategy, BuilderOptions, MallocMessageBuilder, MessageBuilder,
+                  MessageReader, ReaderOptions, SegmentArrayMessageReader};
+    use text_list;
+
+    // The first segment has room for the root pointer, a one-element list and its text, so
+    // that moving the list leaves no room for a simple landing pad next to the text.
+    let mut message = MallocMessageBuilder::new(
+        *BuilderOptions::new().first_segment_words(3)
+            .allocation_strategy(AllocationStrategy::FixedSize));
+    {
+        let mut list : text_list::Builder = message.get_root_internal().init_as_sized(1);
+        list.set(0, "hi");
+        list.grow(2).unwrap();
+        list.set(1, "there");
+    }
+    let segments = message.get_segments_for_output();
+    assert!(segments.len() > 1);
+
+    let reader = SegmentArrayMessageReader::new(segments, ReaderOptions::new());
+    let list : text_list::Reader = reader.get_root().unwrap();
+    assert_eq!(list.len(), 2);
+    assert_eq!(list.get(0).unwrap(), "hi");
+    assert_eq!(list.get(1).unwrap(), "there");
+}
+
+#[test]
+fn list_appender_stops_at_max_len() {
+    use message::{MallocMessageBuilder, MessageBuilder};
+    use traits::ListAppender;
+    use primitive_list;
+
+    // A list of voids takes up no space, so it can be made as long as a list can be.
+    let mut message = MallocMessageBuilder::new_default();
+    let list : primitive_list::Builder<()> =
+        message.get_root_internal().init_as_sized((1 << 29) - 2);
+    let mut appender = ListAppender::new(list);
+    appender.push(()).unwrap();
+    assert_eq!(appender.len(), (1 << 29) - 1);
+    assert!(appender.push(()).is_err());
+    assert_eq!(appender.finish().unwrap().len(), (1 << 29) - 1);
+}
+
+#[test]
+fn canonical_struct_list_stays_inline_composite() {
+    use message::{canonicalize, ReaderOptions, SegmentArrayMessageReader};
+    use Word;
+
+    // The same two structs, with one data word each and with an extra zero word each.
+    let small : ::private::AlignedData<[u8; 32]> = ::private::AlignedData {
+        _dummy: 0,
+        data : [0x01, 0x00, 0x00, 0x00, 0x17, 0x00, 0x00, 0x00,
+                0x08, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
+                0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]
+    };
+    let padded : ::private::AlignedData<[u8; 48]> = ::private::AlignedData {
+        _dummy: 0,
+        data : [0x01, 0x00, 0x00, 0x00, 0x27, 0x00, 0x00, 0x00,
+                0x08, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00,
+                0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]
+    };
+
+    let small_segments = [Word::bytes_to_words(&small.data)];
+    let small_canonical = canonicalize(
+        &SegmentArrayMessageReader::new(&small_segments, ReaderOptions::new())).unwrap();
+    let padded_segments = [Word::bytes_to_words(&padded.data)];
+    let padded_canonical = canonicalize(
+        &SegmentArrayMessageReader::new(&padded_segments, ReaderOptions::new())).unwrap();
+
+    assert_eq!(Word::words_to_bytes(&small_canonical), &small.data[..]);
+    assert_eq!(Word::words_to_bytes(&padded_canonical), &small.data[..]);
+}
+
+#[test]
+fn list_and_blob_limits() {
+    use message::{MallocMessageBuilder, MessageBuilder, MessageReader, ReaderOptions,
+                  SegmentArrayMessageReader};
+    use private::layout::{ElementSize, PointerBuilder, PointerReader, StructBuilder, StructSize};
+    use traits::FromPointerBuilder;
+    use Result;
+
+    struct Root<'a>(StructBuilder<'a>);
+
+    impl <'a> FromPointerBuilder<'a> for Root<'a> {
+        fn init_pointer(builder : PointerBuilder<'a>, _size : u32) -> Root<'a> {
+            Root(builder.init_struct(StructSize { data : 0, pointers : 4 }))
+        }
+        fn get_from_pointer(_builder : PointerBuilder<'a>) -> Result<Root<'a>> {
+            unimplemented!()
+        }
+    }
+
+    let mut message = MallocMessageBuilder::new_default();
+    {
+        let root : Root = message.get_root_internal().init_as();
+        let root = root.0;
+        root.get_pointer_field(0).init_list(ElementSize::TwoBytes, 5);
+        root.get_pointer_field(1).init_struct_list(5, StructSize { data : 1, pointers : 0 });
+        root.get_pointer_field(2).set_text("hello");
+        root.get_pointer_field(3).set_data(b"bytes");
+    }
+    let segments = message.get_segments_for_output();
+
+    // Reads each of the four pointers with `options`, and returns which of them succeeded.
+    let read = |options : ReaderOptions| {
+        let reader = SegmentArrayMessageReader::new(segments, options);
+        let segment0 = &reader.arena().segment0;
+        let root = PointerReader::get_root(segment0, unsafe { segment0.get_start_ptr() },
+                                           options.nesting_limit).unwrap();
+        let root = root.get_struct(::std::ptr::null()).unwrap();
+        (root.get_pointer_field(0).get_list(ElementSize::TwoBytes, ::std::ptr::null()).is_ok(),
+         root.get_pointer_field(1).get_list(ElementSize::InlineComposite,
+                                            ::std::ptr::null()).is_ok(),
+         root.get_pointer_field(2).get_text(::std::ptr::null(), 0).is_ok(),
+         root.get_pointer_field(3).get_data(::std::ptr::null(), 0).is_ok())
+    };
+
+    assert_eq!(read(ReaderOptions::new()), (true, true, true, true));
+    assert_eq!(read(*ReaderOptions::new().list_element_count_limit(5)), (true, true, true, true));
+    assert_eq!(read(*ReaderOptions::new().list_element_count_limit(4)), (false, false, true, true));
+
+    // The limit on text does not count its NUL terminator.
+    assert_eq!(read(*ReaderOptions::new().blob_size_limit_in_bytes(5)), (true, true, true, true));
+    assert_eq!(read(*ReaderOptions::new().blob_size_limit_in_bytes(4)), (true, true, false, false));
+}
+
+#[test]
+fn invalid_utf8_text() {
+    use message::{MessageReader, ReaderOptions, SegmentArrayMessageReader};
+    use text_list;
+    use Word;
+
+    let words : Vec<Word> = [0x0000_0016_0000_0001,     // list of two pointers
+                             0x0000_0022_0000_0005,     // 4 bytes at 3
+                             0x0000_001a_0000_0005,     // 3 bytes at 4
+                             0x00c3_61ff,               // "\xffa\xc3", which is not UTF-8
+                             0x6b6f]                    // "ok"
+        .iter().map(|&v : &u64| Word { _unused_member : v.to_le() }).collect();
+    let segments = [&words[..]];
+
+    let reader = SegmentArrayMessageReader::new(&segments, ReaderOptions::new());
+    let list : text_list::Reader = reader.get_root().unwrap();
+    assert!(list.get(0).is_err());
+    assert_eq!(list.get_as_bytes(0).unwrap(), b"\xffa\xc3");
+    assert_eq!(list.get_lossy(0).unwrap(), "\u{fffd}a\u{fffd}");
+    assert_eq!(list.get(1).unwrap(), "ok");
+    assert_eq!(list.get_as_bytes(1).unwrap(), b"ok");
+    assert_eq!(list.get_lossy(1).unwrap(), "ok");
+
+    let mut options = ReaderOptions::new();
+    unsafe { options.skip_utf8_validation(true); }
+    let reader = SegmentArrayMessageReader::new(&segments, options);
+    let list : text_list::Reader = reader.get_root().unwrap();
+    // Only look at the bytes of the unchecked text, since it is not a valid `str`.
+    assert_eq!(list.get(0).unwrap().as_bytes(), b"\xffa\xc3");
+    assert_eq!(list.get(1).unwrap(), "ok");
+}
@@ -35,6 +35,8 @@ mod zero;
 
 #[cfg(test)]
 mod layout_test;
+#[cfg(test)]
+pub mod test_schema;
 
 /// Some data that's guaranteed to be aligned on a word boundary.
 ///
@@ -57,7 +59,7 @@ impl <'a> RawSchema<'a> {
     pub fn get_encoded_node(&self) -> &'a [Word] {
         unsafe {
             ::std::slice::from_raw_parts(
-                ::std::mem::transmute(&self.blob.as_ptr()),
+                self.blob.as_ptr() as *const Word,
                 self.blob.len() / 8)
         }
     }
@@ -0,0 +1,218 @@
+// Copyright (c) 2013-2015 Sandstorm Development Group, Inc. and contributors
+// Licensed under the MIT License:
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Schema nodes built by hand, for testing the schema-driven modules without generated code.
+//!
+//! `test_schema()` loads the equivalent of:
+//!
+//! ```capnp
+//! struct Foo @0x1 {               # 4 data words, 4 pointers
+//!   a @0 :UInt32 = 5;
+//!   b @1 :Bool;                   # bit 32
+//!   e @2 :Color;                  # u16 offset 3
+//!   name @3 :Text = "anon";       # pointer 0
+//!   items @4 :List(Item);         # pointer 1
+//!   nums @5 :List(Int16);         # pointer 2
+//!   union {                       # discriminant at u16 offset 4
+//!     uInt @6 :Int64;             # u64 offset 2
+//!     uText @7 :Text;             # pointer 3
+//!   }
+//!   grp :group {                  # @0x4
+//!     x @8 :Int8;                 # byte 24
+//!     y @9 :Float32;              # u32 offset 7
+//!   }
+//! }
+//! enum Color @0x2 { red @0; green @1; blue @2; }
+//! struct Item @0x3 { id @0 :UInt64; label @1 :Text; }
+//! ```
+
+use message::{MallocMessageBuilder, MessageBuilder};
+use private::layout::{PointerBuilder, StructBuilder, StructSize};
+use schema::{SchemaLoader, Type};
+use traits::FromPointerBuilder;
+use Result;
+
+pub const FOO : u64 = 1;
+pub const COLOR : u64 = 2;
+pub const ITEM : u64 = 3;
+pub const FOO_GRP : u64 = 4;
+
+/// The default value of a field, where it has one.
+pub enum Default { None, Int(u64), Text(&'static str) }
+
+pub enum FieldKind { Slot(u32, Type, Default), Group(u64) }
+
+pub struct FieldDef {
+    pub name : &'static str,
+    pub discriminant : Option<u16>,
+    pub kind : FieldKind,
+}
+
+pub fn slot(name : &'static str, offset : u32, type_ : Type) -> FieldDef {
+    slot_with_default(name, offset, type_, Default::None)
+}
+
+pub fn slot_with_default(name : &'static str, offset : u32, type_ : Type,
+                         default : Default) -> FieldDef {
+    FieldDef { name : name, discriminant : None, kind : FieldKind::Slot(offset, type_, default) }
+}
+
+pub fn group(name : &'static str, type_id : u64) -> FieldDef {
+    FieldDef { name : name, discriminant : None, kind : FieldKind::Group(type_id) }
+}
+
+pub fn in_union(field : FieldDef, discriminant : u16) -> FieldDef {
+    FieldDef { discriminant : Some(discriminant), .. field }
+}
+
+fn type_which(type_ : &Type) -> u16 {
+    match *type_ {
+        Type::Void => 0, Type::Bool => 1, Type::Int8 => 2, Type::Int16 => 3, Type::Int32 => 4,
+        Type::Int64 => 5, Type::UInt8 => 6, Type::UInt16 => 7, Type::UInt32 => 8,
+        Type::UInt64 => 9, Type::Float32 => 10, Type::Float64 => 11, Type::Text => 12,
+        Type::Data => 13, Type::List(_) => 14, Type::Enum(_) => 15, Type::Struct(_) => 16,
+        Type::Interface(_) => 17, Type::AnyPointer => 18,
+    }
+}
+
+fn write_type(pointer : PointerBuilder, type_ : &Type) {
+    let builder = pointer.init_struct(StructSize { data : 3, pointers : 1 });
+    builder.set_data_field::<u16>(0, type_which(type_));
+    match *type_ {
+        Type::List(ref element_type) => write_type(builder.get_pointer_field(0), element_type),
+        Type::Enum(id) | Type::Struct(id) | Type::Interface(id) =>
+            builder.set_data_field::<u64>(1, id),
+        _ => (),
+    }
+}
+
+fn write_value(pointer : PointerBuilder, type_ : &Type, default : &Default) {
+    let builder = pointer.init_struct(StructSize { data : 2, pointers : 1 });
+    builder.set_data_field::<u16>(0, type_which(type_));
+    match (type_, default) {
+        (&Type::Int8, &Default::Int(v)) | (&Type::UInt8, &Default::Int(v)) =>
+            builder.set_data_field::<u8>(2, v as u8),
+        (&Type::Int16, &Default::Int(v)) | (&Type::UInt16, &Default::Int(v)) |
+        (&Type::Enum(_), &Default::Int(v)) => builder.set_data_field::<u16>(1, v as u16),
+        (&Type::Int32, &Default::Int(v)) | (&Type::UInt32, &Default::Int(v)) =>
+            builder.set_data_field::<u32>(1, v as u32),
+        (&Type::Int64, &Default::Int(v)) | (&Type::UInt64, &Default::Int(v)) =>
+            builder.set_data_field::<u64>(1, v),
+        (&Type::Text, &Default::Text(text)) => builder.get_pointer_field(0).set_text(text),
+        _ => (),
+    }
+}
+
+struct NodeBuilder<'a>(StructBuilder<'a>);
+
+impl <'a> FromPointerBuilder<'a> for NodeBuilder<'a> {
+    fn init_pointer(builder : PointerBuilder<'a>, _size : u32) -> NodeBuilder<'a> {
+        NodeBuilder(builder.init_struct(StructSize { data : 5, pointers : 6 }))
+    }
+    fn get_from_pointer(builder : PointerBuilder<'a>) -> Result<NodeBuilder<'a>> {
+        Ok(NodeBuilder(try!(builder.get_struct(StructSize { data : 5, pointers : 6 },
+                                               ::std::ptr::null()))))
+    }
+}
+
+fn load_node<F>(loader : &mut SchemaLoader, id : u64, name : &str, which : u16, fill : F)
+    where F : FnOnce(&StructBuilder)
+{
+    let mut message = MallocMessageBuilder::new_default();
+    {
+        let node : NodeBuilder = message.get_root_internal().init_as();
+        let node = node.0;
+        node.set_data_field::<u64>(0, id);
+        node.get_pointer_field(0).set_text(&format!("test.capnp:{}", name));
+        node.set_data_field::<u32>(2, 11);
+        node.set_data_field::<u16>(6, which);
+        fill(&node);
+    }
+    loader.load_encoded_node(message.get_segments_for_output()[0]).unwrap();
+}
+
+pub fn load_struct(loader : &mut SchemaLoader, id : u64, name : &str, size : StructSize,
+                   discriminant_count : u16, discriminant_offset : u32, is_group : bool,
+                   fields : Vec<FieldDef>) {
+    load_node(loader, id, name, 1, |node| {
+        node.set_data_field::<u16>(7, size.data);
+        node.set_data_field::<u16>(12, size.pointers);
+        node.set_bool_field(224, is_group);
+        node.set_data_field::<u16>(15, discriminant_count);
+        node.set_data_field::<u32>(8, discriminant_offset);
+        let list = node.get_pointer_field(3).init_struct_list(
+            fields.len() as u32, StructSize { data : 3, pointers : 4 });
+        for (i, field) in fields.iter().enumerate() {
+            let builder = list.get_struct_element(i as u32);
+            builder.get_pointer_field(0).set_text(field.name);
+            builder.set_data_field::<u16>(0, i as u16);
+            builder.set_data_field_mask::<u16>(1, field.discriminant.unwrap_or(0xffff), 0xffff);
+            match field.kind {
+                FieldKind::Slot(offset, ref type_, ref default) => {
+                    builder.set_data_field::<u32>(1, offset);
+                    write_type(builder.get_pointer_field(2), type_);
+                    write_value(builder.get_pointer_field(3), type_, default);
+                    builder.set_data_field::<u16>(5, 1);
+                    builder.set_data_field::<u16>(6, i as u16);
+                }
+                FieldKind::Group(type_id) => {
+                    builder.set_data_field::<u16>(4, 1);
+                    builder.set_data_field::<u64>(2, type_id);
+                }
+            }
+        }
+    });
+}
+
+pub fn load_enum(loader : &mut SchemaLoader, id : u64, name : &str, enumerants : &[&str]) {
+    load_node(loader, id, name, 2, |node| {
+        let list = node.get_pointer_field(3).init_struct_list(
+            enumerants.len() as u32, StructSize { data : 1, pointers : 2 });
+        for (i, enumerant) in enumerants.iter().enumerate() {
+            let builder = list.get_struct_element(i as u32);
+            builder.get_pointer_field(0).set_text(enumerant);
+            builder.set_data_field::<u16>(0, i as u16);
+        }
+    });
+}
+
+pub fn test_schema() -> SchemaLoader {
+    let mut loader = SchemaLoader::new();
+    load_struct(&mut loader, FOO, "Foo", StructSize { data : 4, pointers : 4 }, 2, 4, false, vec![
+        slot_with_default("a", 0, Type::UInt32, Default::Int(5)),
+        slot("b", 32, Type::Bool),
+        slot("e", 3, Type::Enum(COLOR)),
+        slot_with_default("name", 0, Type::Text, Default::Text("anon")),
+        slot("items", 1, Type::List(Box::new(Type::Struct(ITEM)))),
+        slot("nums", 2, Type::List(Box::new(Type::Int16))),
+        in_union(slot("uInt", 2, Type::Int64), 0),
+        in_union(slot("uText", 3, Type::Text), 1),
+        group("grp", FOO_GRP),
+    ]);
+    load_enum(&mut loader, COLOR, "Color", &["red", "green", "blue"]);
+    load_struct(&mut loader, ITEM, "Item", StructSize { data : 1, pointers : 1 }, 0, 0, false, vec![
+        slot("id", 0, Type::UInt64),
+        slot("label", 0, Type::Text),
+    ]);
+    load_struct(&mut loader, FOO_GRP, "Foo.grp", StructSize { data : 4, pointers : 4 }, 0, 0, true,
+                vec![slot("x", 24, Type::Int8), slot("y", 7, Type::Float32)]);
+    loader
+}
@@ -0,0 +1,994 @@
+// Copyright (c) 2013-2015 Sandstorm Development Group, Inc. and contributors
+// Licensed under the MIT License:
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Runtime representation of schemas.
+//!
+//! A `SchemaLoader` decodes `schema.capnp` nodes, such as the ones embedded in generated code
+//! as `RawSchema`s or the ones passed to a compiler plugin in a `CodeGeneratorRequest`, and
+//! keeps them indexed by id. The `Schema` family of types are cheap, copyable views into the
+//! loader that resolve dependencies by id.
+
+use std::collections::HashMap;
+
+use any_pointer;
+use message::{MessageReader, ReaderOptions, TypedReader};
+use private::RawSchema;
+use private::layout::{PointerReader, StructReader};
+use serialize::OwnedSpaceMessageReader;
+use traits::{FromPointerReader, IntoInternalStructReader};
+use {Error, Result, Word};
+
+/// The type of a field, constant, list element, or annotation.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Type {
+    Void,
+    Bool,
+    Int8,
+    Int16,
+    Int32,
+    Int64,
+    UInt8,
+    UInt16,
+    UInt32,
+    UInt64,
+    Float32,
+    Float64,
+    Text,
+    Data,
+    List(Box<Type>),
+    Enum(u64),
+    Struct(u64),
+    Interface(u64),
+    AnyPointer,
+}
+
+impl Type {
+    /// Returns true if values of this type are stored in the pointer section.
+    pub fn is_pointer(&self) -> bool {
+        match *self {
+            Type::Text | Type::Data | Type::List(_) | Type::Struct(_) |
+            Type::Interface(_) | Type::AnyPointer => true,
+            _ => false,
+        }
+    }
+}
+
+/// A default value of pointer type, copied out of the schema node that contained it.
+pub struct PointerValue {
+    message : TypedReader<OwnedSpaceMessageReader, any_pointer::Owned>,
+}
+
+impl PointerValue {
+    pub fn get<'a>(&'a self) -> Result<any_pointer::Reader<'a>> {
+        self.message.get()
+    }
+}
+
+/// A constant or default value.
+pub enum Value {
+    Void,
+    Bool(bool),
+    Int8(i8),
+    Int16(i16),
+    Int32(i32),
+    Int64(i64),
+    UInt8(u8),
+    UInt16(u16),
+    UInt32(u32),
+    UInt64(u64),
+    Float32(f32),
+    Float64(f64),
+    Text(String),
+    Data(Vec<u8>),
+    List(PointerValue),
+    Enum(u16),
+    Struct(PointerValue),
+    Interface,
+    AnyPointer(PointerValue),
+}
+
+/// Decoded `schema.capnp` nodes.
+///
+/// These mirror the structures of `schema.capnp` closely. Most code will want to use the
+/// `Schema` views instead, which follow ids to the nodes they refer to.
+pub mod proto {
+    use super::{Type, Value};
+
+    /// Sentinel `discriminant_value` of a field that is not a member of a union.
+    pub const NO_DISCRIMINANT : u16 = 0xffff;
+
+    pub struct Annotation {
+        pub id : u64,
+        pub value : Value,
+    }
+
+    pub struct NestedNode {
+        pub name : String,
+        pub id : u64,
+    }
+
+    pub enum FieldKind {
+        Slot {
+            offset : u32,
+            type_ : Type,
+            default_value : Value,
+            had_explicit_default : bool,
+        },
+        Group {
+            type_id : u64,
+        },
+    }
+
+    pub struct Field {
+        pub name : String,
+        pub code_order : u16,
+        pub annotations : Vec<Annotation>,
+        pub discriminant_value : u16,
+        pub kind : FieldKind,
+
+        /// The explicit `@N` ordinal, if any. Group fields have none.
+        pub ordinal : Option<u16>,
+    }
+
+    pub struct Enumerant {
+        pub name : String,
+        pub code_order : u16,
+        pub annotations : Vec<Annotation>,
+    }
+
+    pub struct Method {
+        pub name : String,
+        pub code_order : u16,
+        pub param_struct_type : u64,
+        pub result_struct_type : u64,
+        pub annotations : Vec<Annotation>,
+    }
+
+    #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+    pub enum AnnotationTarget {
+        File,
+        Const,
+        Enum,
+        Enumerant,
+        Struct,
+        Field,
+        Union,
+        Group,
+        Interface,
+        Method,
+        Param,
+        Annotation,
+    }
+
+    pub enum NodeKind {
+        File,
+        Struct {
+            data_word_count : u16,
+            pointer_count : u16,
+            preferred_list_encoding : u16,
+            is_group : bool,
+            discriminant_count : u16,
+            discriminant_offset : u32,
+            fields : Vec<Field>,
+        },
+        Enum {
+            enumerants : Vec<Enumerant>,
+        },
+        Interface {
+            methods : Vec<Method>,
+            superclasses : Vec<u64>,
+        },
+        Const {
+            type_ : Type,
+            value : Value,
+        },
+        Annotation {
+            type_ : Type,
+            targets : Vec<AnnotationTarget>,
+        },
+    }
+
+    pub struct Node {
+        pub id : u64,
+        pub display_name : String,
+        pub display_name_prefix_length : u32,
+        pub scope_id : u64,
+        pub nested_nodes : Vec<NestedNode>,
+        pub annotations : Vec<Annotation>,
+        pub kind : NodeKind,
+    }
+
+    impl Node {
+        /// The part of the display name after the scope prefix, e.g. `Bar` for `foo.capnp:Bar`.
+        pub fn get_short_name<'a>(&'a self) -> &'a str {
+            let prefix = ::std::cmp::min(self.display_name_prefix_length as usize,
+                                         self.display_name.len());
+            &self.display_name[prefix..]
+        }
+    }
+}
+
+/// Owns a set of decoded schema nodes, indexed by id.
+pub struct SchemaLoader {
+    nodes : HashMap<u64, proto::Node>,
+}
+
+impl SchemaLoader {
+    pub fn new() -> SchemaLoader {
+        SchemaLoader { nodes : HashMap::new() }
+    }
+
+    /// Decodes `node` and adds it to the loader, replacing any node with the same id.
+    /// Returns the id of the node.
+    ///
+    /// Dependencies are not checked here; they are resolved lazily when looked up.
+    pub fn load<'a, R : IntoInternalStructReader<'a>>(&mut self, node : R) -> Result<u64> {
+        let node = try!(decode::node(node.into_internal_struct_reader()));
+        let id = node.id;
+        self.nodes.insert(id, node);
+        Ok(id)
+    }
+
+    /// Loads a node encoded as a single-segment message whose root is a `schema.capnp` `Node`.
+    pub fn load_encoded_node(&mut self, words : &[Word]) -> Result<u64> {
+        let message = OwnedSpaceMessageReader::new(&[words], ReaderOptions::new());
+        let root : NodeReader = try!(message.get_root());
+        self.load(root.0)
+    }
+
+    /// Loads the node embedded in generated code for some type.
+    pub fn load_raw(&mut self, raw : &RawSchema) -> Result<u64> {
+        let id = try!(self.load_encoded_node(raw.get_encoded_node()));
+        if id != raw.id {
+            return Err(Error::new_decode_error(
+                "Encoded schema node does not match the id of its RawSchema.",
+                Some(format!("expected 0x{:x}, got 0x{:x}", raw.id, id))));
+        }
+        Ok(id)
+    }
+
+    /// Loads every node of a `schema.capnp` `CodeGeneratorRequest`.
+    pub fn load_code_generator_request<M : MessageReader>(&mut self, message : &M) -> Result<()> {
+        let root : NodeReader = try!(message.get_root());
+        let nodes = try!(decode::struct_list(root.0.get_pointer_field(0)));
+        for i in 0..nodes.len() {
+            try!(self.load(nodes.get_struct_element(i)));
+        }
+        Ok(())
+    }
+
+    pub fn get<'a>(&'a self, id : u64) -> Option<Schema<'a>> {
+        self.nodes.get(&id).map(|node| Schema { loader : self, node : node })
+    }
+
+    /// Like `get()`, but returns an error if the node has not been loaded.
+    pub fn get_dependency<'a>(&'a self, id : u64) -> Result<Schema<'a>> {
+        match self.get(id) {
+            Some(schema) => Ok(schema),
+            None => Err(Error::new_decode_error("Schema node has not been loaded.",
+                                                Some(format!("id = 0x{:x}", id)))),
+        }
+    }
+
+    /// Like `get_dependency()`, but also checks that the node is a struct.
+    pub fn get_struct_schema<'a>(&'a self, id : u64) -> Result<StructSchema<'a>> {
+        match try!(self.get_dependency(id)).as_struct() {
+            Some(s) => Ok(s),
+            None => Err(Error::new_decode_error("Schema node is not a struct.",
+                                                Some(format!("id = 0x{:x}", id)))),
+        }
+    }
+
+    /// Like `get_dependency()`, but also checks that the node is an enum.
+    pub fn get_enum_schema<'a>(&'a self, id : u64) -> Result<EnumSchema<'a>> {
+        match try!(self.get_dependency(id)).as_enum() {
+            Some(e) => Ok(e),
+            None => Err(Error::new_decode_error("Schema node is not an enum.",
+                                                Some(format!("id = 0x{:x}", id)))),
+        }
+    }
+
+    /// Like `get_dependency()`, but also checks that the node is an interface.
+    pub fn get_interface_schema<'a>(&'a self, id : u64) -> Result<InterfaceSchema<'a>> {
+        match try!(self.get_dependency(id)).as_interface() {
+            Some(i) => Ok(i),
+            None => Err(Error::new_decode_error("Schema node is not an interface.",
+                                                Some(format!("id = 0x{:x}", id)))),
+        }
+    }
+
+    /// All of the loaded nodes, in no particular order.
+    pub fn get_all_loaded<'a>(&'a self) -> Vec<Schema<'a>> {
+        self.nodes.values().map(|node| Schema { loader : self, node : node }).collect()
+    }
+}
+
+/// A loaded schema node.
+#[derive(Clone, Copy)]
+pub struct Schema<'a> {
+    loader : &'a SchemaLoader,
+    node : &'a proto::Node,
+}
+
+impl <'a> Schema<'a> {
+    pub fn get_id(&self) -> u64 { self.node.id }
+
+    pub fn get_proto(&self) -> &'a proto::Node { self.node }
+
+    pub fn get_loader(&self) -> &'a SchemaLoader { self.loader }
+
+    pub fn get_short_name(&self) -> &'a str { self.node.get_short_name() }
+
+    pub fn get_dependency(&self, id : u64) -> Result<Schema<'a>> {
+        self.loader.get_dependency(id)
+    }
+
+    /// Looks up a nested declaration by its short name.
+    pub fn find_nested(&self, name : &str) -> Result<Option<Schema<'a>>> {
+        for nested in self.node.nested_nodes.iter() {
+            if nested.name == name {
+                return Ok(Some(try!(self.get_dependency(nested.id))));
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn get_annotation(&self, id : u64) -> Option<&'a proto::Annotation> {
+        self.node.annotations.iter().find(|a| a.id == id)
+    }
+
+    pub fn as_struct(&self) -> Option<StructSchema<'a>> {
+        match self.node.kind {
+            proto::NodeKind::Struct { .. } => Some(StructSchema { schema : *self }),
+            _ => None,
+        }
+    }
+
+    pub fn as_enum(&self) -> Option<EnumSchema<'a>> {
+        match self.node.kind {
+            proto::NodeKind::Enum { .. } => Some(EnumSchema { schema : *self }),
+            _ => None,
+        }
+    }
+
+    pub fn as_interface(&self) -> Option<InterfaceSchema<'a>> {
+        match self.node.kind {
+            proto::NodeKind::Interface { .. } => Some(InterfaceSchema { schema : *self }),
+            _ => None,
+        }
+    }
+
+    pub fn as_const(&self) -> Option<ConstSchema<'a>> {
+        match self.node.kind {
+            proto::NodeKind::Const { .. } => Some(ConstSchema { schema : *self }),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct StructSchema<'a> {
+    schema : Schema<'a>,
+}
+
+impl <'a> StructSchema<'a> {
+    pub fn get_schema(&self) -> Schema<'a> { self.schema }
+
+    pub fn get_proto(&self) -> &'a proto::Node { self.schema.node }
+
+    fn get_fields_proto(&self) -> &'a [proto::Field] {
+        match self.schema.node.kind {
+            proto::NodeKind::Struct { ref fields, .. } => fields,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Returns (data word count, pointer count).
+    pub fn get_struct_size(&self) -> (u16, u16) {
+        match self.schema.node.kind {
+            proto::NodeKind::Struct { data_word_count, pointer_count, .. } =>
+                (data_word_count, pointer_count),
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn is_group(&self) -> bool {
+        match self.schema.node.kind {
+            proto::NodeKind::Struct { is_group, .. } => is_group,
+            _ => unreachable!(),
+        }
+    }
+
+    /// The number of fields in the unnamed union, or zero if there is none.
+    pub fn get_discriminant_count(&self) -> u16 {
+        match self.schema.node.kind {
+            proto::NodeKind::Struct { discriminant_count, .. } => discriminant_count,
+            _ => unreachable!(),
+        }
+    }
+
+    /// The offset of the union discriminant, in multiples of 16 bits.
+    pub fn get_discriminant_offset(&self) -> u32 {
+        match self.schema.node.kind {
+            proto::NodeKind::Struct { discriminant_offset, .. } => discriminant_offset,
+            _ => unreachable!(),
+        }
+    }
+
+    /// All fields, in the order in which they appear in the node (ordinal order).
+    pub fn get_fields(&self) -> Vec<Field<'a>> {
+        let len = self.get_fields_proto().len();
+        (0..len).map(|i| Field { parent : *self, index : i as u32 }).collect()
+    }
+
+    /// Like `get_fields()`, but in the order in which the fields were declared.
+    pub fn get_fields_in_code_order(&self) -> Vec<Field<'a>> {
+        let mut fields = self.get_fields();
+        fields.sort_by(|a, b| a.get_proto().code_order.cmp(&b.get_proto().code_order));
+        fields
+    }
+
+    /// The fields that are members of the unnamed union.
+    pub fn get_union_fields(&self) -> Vec<Field<'a>> {
+        self.get_fields().into_iter().filter(|f| f.get_discriminant_value().is_some()).collect()
+    }
+
+    /// The fields that are not members of the unnamed union.
+    pub fn get_non_union_fields(&self) -> Vec<Field<'a>> {
+        self.get_fields().into_iter().filter(|f| f.get_discriminant_value().is_none()).collect()
+    }
+
+    pub fn find_field_by_name(&self, name : &str) -> Option<Field<'a>> {
+        self.get_fields().into_iter().find(|f| f.get_name() == name)
+    }
+
+    /// The union member whose discriminant value is `discriminant`.
+    pub fn get_field_by_discriminant(&self, discriminant : u16) -> Option<Field<'a>> {
+        self.get_fields().into_iter().find(|f| f.get_discriminant_value() == Some(discriminant))
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Field<'a> {
+    parent : StructSchema<'a>,
+    index : u32,
+}
+
+impl <'a> Field<'a> {
+    pub fn get_proto(&self) -> &'a proto::Field {
+        &self.parent.get_fields_proto()[self.index as usize]
+    }
+
+    pub fn get_containing_struct(&self) -> StructSchema<'a> { self.parent }
+
+    /// The index of this field in its containing struct's field list.
+    pub fn get_index(&self) -> u32 { self.index }
+
+    pub fn get_name(&self) -> &'a str { &self.get_proto().name }
+
+    /// The type of the field. For groups this is `Type::Struct` of the group's node.
+    pub fn get_type(&self) -> Type {
+        match self.get_proto().kind {
+            proto::FieldKind::Slot { ref type_, .. } => type_.clone(),
+            proto::FieldKind::Group { type_id } => Type::Struct(type_id),
+        }
+    }
+
+    pub fn is_group(&self) -> bool {
+        match self.get_proto().kind {
+            proto::FieldKind::Group { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Returns the group's struct schema, or `None` if this is not a group field.
+    pub fn get_group(&self) -> Result<Option<StructSchema<'a>>> {
+        match self.get_proto().kind {
+            proto::FieldKind::Group { type_id } =>
+                Ok(Some(try!(self.parent.schema.loader.get_struct_schema(type_id)))),
+            _ => Ok(None),
+        }
+    }
+
+    /// The offset of a slot, in multiples of the field's size. `None` for groups.
+    pub fn get_offset(&self) -> Option<u32> {
+        match self.get_proto().kind {
+            proto::FieldKind::Slot { offset, .. } => Some(offset),
+            _ => None,
+        }
+    }
+
+    pub fn get_default_value(&self) -> Option<&'a Value> {
+        match self.get_proto().kind {
+            proto::FieldKind::Slot { ref default_value, .. } => Some(default_value),
+            _ => None,
+        }
+    }
+
+    /// The discriminant value if this field is a member of the unnamed union.
+    pub fn get_discriminant_value(&self) -> Option<u16> {
+        match self.get_proto().discriminant_value {
+            proto::NO_DISCRIMINANT => None,
+            d => Some(d),
+        }
+    }
+
+    pub fn get_annotation(&self, id : u64) -> Option<&'a proto::Annotation> {
+        self.get_proto().annotations.iter().find(|a| a.id == id)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct EnumSchema<'a> {
+    schema : Schema<'a>,
+}
+
+impl <'a> EnumSchema<'a> {
+    pub fn get_schema(&self) -> Schema<'a> { self.schema }
+
+    pub fn get_proto(&self) -> &'a proto::Node { self.schema.node }
+
+    fn get_enumerants_proto(&self) -> &'a [proto::Enumerant] {
+        match self.schema.node.kind {
+            proto::NodeKind::Enum { ref enumerants } => enumerants,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn get_enumerants(&self) -> Vec<Enumerant<'a>> {
+        let len = self.get_enumerants_proto().len();
+        (0..len).map(|i| Enumerant { parent : *self, ordinal : i as u16 }).collect()
+    }
+
+    pub fn get_enumerant(&self, ordinal : u16) -> Option<Enumerant<'a>> {
+        if (ordinal as usize) < self.get_enumerants_proto().len() {
+            Some(Enumerant { parent : *self, ordinal : ordinal })
+        } else {
+            None
+        }
+    }
+
+    pub fn find_enumerant_by_name(&self, name : &str) -> Option<Enumerant<'a>> {
+        self.get_enumerants().into_iter().find(|e| e.get_name() == name)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Enumerant<'a> {
+    parent : EnumSchema<'a>,
+    ordinal : u16,
+}
+
+impl <'a> Enumerant<'a> {
+    pub fn get_proto(&self) -> &'a proto::Enumerant {
+        &self.parent.get_enumerants_proto()[self.ordinal as usize]
+    }
+
+    pub fn get_containing_enum(&self) -> EnumSchema<'a> { self.parent }
+
+    /// The numeric value of this enumerant.
+    pub fn get_ordinal(&self) -> u16 { self.ordinal }
+
+    pub fn get_name(&self) -> &'a str { &self.get_proto().name }
+}
+
+#[derive(Clone, Copy)]
+pub struct InterfaceSchema<'a> {
+    schema : Schema<'a>,
+}
+
+impl <'a> InterfaceSchema<'a> {
+    pub fn get_schema(&self) -> Schema<'a> { self.schema }
+
+    pub fn get_proto(&self) -> &'a proto::Node { self.schema.node }
+
+    fn get_methods_proto(&self) -> &'a [proto::Method] {
+        match self.schema.node.kind {
+            proto::NodeKind::Interface { ref methods, .. } => methods,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn get_methods(&self) -> Vec<Method<'a>> {
+        let len = self.get_methods_proto().len();
+        (0..len).map(|i| Method { parent : *self, ordinal : i as u16 }).collect()
+    }
+
+    pub fn find_method_by_name(&self, name : &str) -> Option<Method<'a>> {
+        self.get_methods().into_iter().find(|m| m.get_name() == name)
+    }
+
+    pub fn get_superclasses(&self) -> Result<Vec<InterfaceSchema<'a>>> {
+        let ids = match self.schema.node.kind {
+            proto::NodeKind::Interface { ref superclasses, .. } => superclasses,
+            _ => unreachable!(),
+        };
+        let mut result = Vec::with_capacity(ids.len());
+        for &id in ids.iter() {
+            result.push(try!(self.schema.loader.get_interface_schema(id)));
+        }
+        Ok(result)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Method<'a> {
+    parent : InterfaceSchema<'a>,
+    ordinal : u16,
+}
+
+impl <'a> Method<'a> {
+    pub fn get_proto(&self) -> &'a proto::Method {
+        &self.parent.get_methods_proto()[self.ordinal as usize]
+    }
+
+    pub fn get_containing_interface(&self) -> InterfaceSchema<'a> { self.parent }
+
+    pub fn get_ordinal(&self) -> u16 { self.ordinal }
+
+    pub fn get_name(&self) -> &'a str { &self.get_proto().name }
+
+    pub fn get_param_type(&self) -> Result<StructSchema<'a>> {
+        self.parent.schema.loader.get_struct_schema(self.get_proto().param_struct_type)
+    }
+
+    pub fn get_result_type(&self) -> Result<StructSchema<'a>> {
+        self.parent.schema.loader.get_struct_schema(self.get_proto().result_struct_type)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct ConstSchema<'a> {
+    schema : Schema<'a>,
+}
+
+impl <'a> ConstSchema<'a> {
+    pub fn get_schema(&self) -> Schema<'a> { self.schema }
+
+    pub fn get_proto(&self) -> &'a proto::Node { self.schema.node }
+
+    pub fn get_type(&self) -> &'a Type {
+        match self.schema.node.kind {
+            proto::NodeKind::Const { ref type_, .. } => type_,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn get_value(&self) -> &'a Value {
+        match self.schema.node.kind {
+            proto::NodeKind::Const { ref value, .. } => value,
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// Any struct pointer, read without knowing its type.
+struct NodeReader<'a>(StructReader<'a>);
+
+impl <'a> FromPointerReader<'a> for NodeReader<'a> {
+    fn get_from_pointer(reader : &PointerReader<'a>) -> Result<NodeReader<'a>> {
+        Ok(NodeReader(try!(reader.get_struct(::std::ptr::null()))))
+    }
+}
+
+/// Decoding of `schema.capnp` structs. Offsets are those of the generated code for
+/// `schema.capnp`: data offsets are in multiples of the field's size, and bool offsets are in bits.
+mod decode {
+    use any_pointer;
+    use private::layout::{InlineComposite, ListReader, PointerReader, StructReader};
+    use Result;
+    use super::{PointerValue, Type, Value};
+    use super::proto;
+
+    fn text(reader : PointerReader) -> Result<String> {
+        Ok(try!(reader.get_text(::std::ptr::null(), 0)).to_string())
+    }
+
+    pub fn struct_list<'a>(reader : PointerReader<'a>) -> Result<ListReader<'a>> {
+        reader.get_list(InlineComposite, ::std::ptr::null())
+    }
+
+    fn struct_<'a>(reader : PointerReader<'a>) -> Result<StructReader<'a>> {
+        reader.get_struct(::std::ptr::null())
+    }
+
+    fn list_of<'a, T, F>(reader : PointerReader<'a>, mut f : F) -> Result<Vec<T>>
+        where F : FnMut(StructReader<'a>) -> Result<T>
+    {
+        let list = try!(struct_list(reader));
+        let mut result = Vec::with_capacity(list.len() as usize);
+        for i in 0..list.len() {
+            result.push(try!(f(list.get_struct_element(i))));
+        }
+        Ok(result)
+    }
+
+    fn pointer_value(reader : PointerReader) -> Result<PointerValue> {
+        Ok(PointerValue { message : try!(any_pointer::Reader::new(reader).copy_to_owned()) })
+    }
+
+    fn unknown(what : &'static str, which : u16) -> ::Error {
+        ::Error::new_decode_error(what, Some(format!("discriminant {}", which)))
+    }
+
+    pub fn type_(reader : StructReader) -> Result<Type> {
+        let which = reader.get_data_field::<u16>(0);
+        Ok(match which {
+            0 => Type::Void,
+            1 => Type::Bool,
+            2 => Type::Int8,
+            3 => Type::Int16,
+            4 => Type::Int32,
+            5 => Type::Int64,
+            6 => Type::UInt8,
+            7 => Type::UInt16,
+            8 => Type::UInt32,
+            9 => Type::UInt64,
+            10 => Type::Float32,
+            11 => Type::Float64,
+            12 => Type::Text,
+            13 => Type::Data,
+            14 => Type::List(Box::new(try!(type_(try!(struct_(reader.get_pointer_field(0))))))),
+            15 => Type::Enum(reader.get_data_field::<u64>(1)),
+            16 => Type::Struct(reader.get_data_field::<u64>(1)),
+            17 => Type::Interface(reader.get_data_field::<u64>(1)),
+            18 => Type::AnyPointer,
+            n => return Err(unknown("Unknown schema type.", n)),
+        })
+    }
+
+    pub fn value(reader : StructReader) -> Result<Value> {
+        let which = reader.get_data_field::<u16>(0);
+        Ok(match which {
+            0 => Value::Void,
+            1 => Value::Bool(reader.get_bool_field(16)),
+            2 => Value::Int8(reader.get_data_field::<i8>(2)),
+            3 => Value::Int16(reader.get_data_field::<i16>(1)),
+            4 => Value::Int32(reader.get_data_field::<i32>(1)),
+            5 => Value::Int64(reader.get_data_field::<i64>(1)),
+            6 => Value::UInt8(reader.get_data_field::<u8>(2)),
+            7 => Value::UInt16(reader.get_data_field::<u16>(1)),
+            8 => Value::UInt32(reader.get_data_field::<u32>(1)),
+            9 => Value::UInt64(reader.get_data_field::<u64>(1)),
+            10 => Value::Float32(reader.get_data_field::<f32>(1)),
+            11 => Value::Float64(reader.get_data_field::<f64>(1)),
+            12 => Value::Text(try!(text(reader.get_pointer_field(0)))),
+            13 => Value::Data(try!(reader.get_pointer_field(0).get_data(::std::ptr::null(), 0)).to_vec()),
+            14 => Value::List(try!(pointer_value(reader.get_pointer_field(0)))),
+            15 => Value::Enum(reader.get_data_field::<u16>(1)),
+            16 => Value::Struct(try!(pointer_value(reader.get_pointer_field(0)))),
+            17 => Value::Interface,
+            18 => Value::AnyPointer(try!(pointer_value(reader.get_pointer_field(0)))),
+            n => return Err(unknown("Unknown schema value.", n)),
+        })
+    }
+
+    fn annotations(reader : PointerReader) -> Result<Vec<proto::Annotation>> {
+        list_of(reader, |a| {
+            Ok(proto::Annotation {
+                id : a.get_data_field::<u64>(0),
+                value : try!(value(try!(struct_(a.get_pointer_field(0))))),
+            })
+        })
+    }
+
+    fn field(reader : StructReader) -> Result<proto::Field> {
+        let kind = match reader.get_data_field::<u16>(4) {
+            0 => proto::FieldKind::Slot {
+                offset : reader.get_data_field::<u32>(1),
+                type_ : try!(type_(try!(struct_(reader.get_pointer_field(2))))),
+                default_value : try!(value(try!(struct_(reader.get_pointer_field(3))))),
+                had_explicit_default : reader.get_bool_field(128),
+            },
+            1 => proto::FieldKind::Group { type_id : reader.get_data_field::<u64>(2) },
+            n => return Err(unknown("Unknown field kind.", n)),
+        };
+        let ordinal = match reader.get_data_field::<u16>(5) {
+            0 => None,
+            1 => Some(reader.get_data_field::<u16>(6)),
+            n => return Err(unknown("Unknown field ordinal kind.", n)),
+        };
+        Ok(proto::Field {
+            name : try!(text(reader.get_pointer_field(0))),
+            code_order : reader.get_data_field::<u16>(0),
+            annotations : try!(annotations(reader.get_pointer_field(1))),
+            discriminant_value : reader.get_data_field_mask::<u16>(1, proto::NO_DISCRIMINANT),
+            kind : kind,
+            ordinal : ordinal,
+        })
+    }
+
+    const ANNOTATION_TARGETS : [proto::AnnotationTarget; 12] = [
+        proto::AnnotationTarget::File,
+        proto::AnnotationTarget::Const,
+        proto::AnnotationTarget::Enum,
+        proto::AnnotationTarget::Enumerant,
+        proto::AnnotationTarget::Struct,
+        proto::AnnotationTarget::Field,
+        proto::AnnotationTarget::Union,
+        proto::AnnotationTarget::Group,
+        proto::AnnotationTarget::Interface,
+        proto::AnnotationTarget::Method,
+        proto::AnnotationTarget::Param,
+        proto::AnnotationTarget::Annotation,
+    ];
+
+    pub fn node(reader : StructReader) -> Result<proto::Node> {
+        let kind = match reader.get_data_field::<u16>(6) {
+            0 => proto::NodeKind::File,
+            1 => proto::NodeKind::Struct {
+                data_word_count : reader.get_data_field::<u16>(7),
+                pointer_count : reader.get_data_field::<u16>(12),
+                preferred_list_encoding : reader.get_data_field::<u16>(13),
+                is_group : reader.get_bool_field(224),
+                discriminant_count : reader.get_data_field::<u16>(15),
+                discriminant_offset : reader.get_data_field::<u32>(8),
+                fields : try!(list_of(reader.get_pointer_field(3), field)),
+            },
+            2 => proto::NodeKind::Enum {
+                enumerants : try!(list_of(reader.get_pointer_field(3), |e| {
+                    Ok(proto::Enumerant {
+                        name : try!(text(e.get_pointer_field(0))),
+                        code_order : e.get_data_field::<u16>(0),
+                        annotations : try!(annotations(e.get_pointer_field(1))),
+                    })
+                })),
+            },
+            3 => proto::NodeKind::Interface {
+                methods : try!(list_of(reader.get_pointer_field(3), |m| {
+                    Ok(proto::Method {
+                        name : try!(text(m.get_pointer_field(0))),
+                        code_order : m.get_data_field::<u16>(0),
+                        param_struct_type : m.get_data_field::<u64>(1),
+                        result_struct_type : m.get_data_field::<u64>(2),
+                        annotations : try!(annotations(m.get_pointer_field(1))),
+                    })
+                })),
+                superclasses : try!(list_of(reader.get_pointer_field(4),
+                                            |s| Ok(s.get_data_field::<u64>(0)))),
+            },
+            4 => proto::NodeKind::Const {
+                type_ : try!(type_(try!(struct_(reader.get_pointer_field(3))))),
+                value : try!(value(try!(struct_(reader.get_pointer_field(4))))),
+            },
+            5 => proto::NodeKind::Annotation {
+                type_ : try!(type_(try!(struct_(reader.get_pointer_field(3))))),
+                targets : (0..ANNOTATION_TARGETS.len())
+                    .filter(|&i| reader.get_bool_field(112 + i))
+                    .map(|i| ANNOTATION_TARGETS[i])
+                    .collect(),
+            },
+            n => return Err(unknown("Unknown schema node kind.", n)),
+        };
+        Ok(proto::Node {
+            id : reader.get_data_field::<u64>(0),
+            display_name : try!(text(reader.get_pointer_field(0))),
+            display_name_prefix_length : reader.get_data_field::<u32>(2),
+            scope_id : reader.get_data_field::<u64>(2),
+            nested_nodes : try!(list_of(reader.get_pointer_field(1), |n| {
+                Ok(proto::NestedNode {
+                    name : try!(text(n.get_pointer_field(0))),
+                    id : n.get_data_field::<u64>(0),
+                })
+            })),
+            annotations : try!(annotations(reader.get_pointer_field(2))),
+            kind : kind,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use message::{MallocMessageBuilder, MessageBuilder};
+    use private::layout::{PointerBuilder, StructBuilder, StructSize};
+    use traits::FromPointerBuilder;
+    use Result;
+    use super::{SchemaLoader, Type, Value};
+
+    struct NodeBuilder<'a>(StructBuilder<'a>);
+
+    impl <'a> FromPointerBuilder<'a> for NodeBuilder<'a> {
+        fn init_pointer(builder : PointerBuilder<'a>, _size : u32) -> NodeBuilder<'a> {
+            NodeBuilder(builder.init_struct(StructSize { data : 5, pointers : 6 }))
+        }
+        fn get_from_pointer(builder : PointerBuilder<'a>) -> Result<NodeBuilder<'a>> {
+            Ok(NodeBuilder(try!(builder.get_struct(StructSize { data : 5, pointers : 6 },
+                                                   ::std::ptr::null()))))
+        }
+    }
+
+    #[test]
+    fn load_struct_and_enum_nodes() {
+        let mut struct_message = MallocMessageBuilder::new_default();
+        {
+            let node : NodeBuilder = struct_message.get_root_internal().init_as();
+            let node = node.0;
+            node.set_data_field::<u64>(0, 0x1234);
+            node.get_pointer_field(0).set_text("foo.capnp:Bar");
+            node.set_data_field::<u32>(2, 10);
+            node.set_data_field::<u16>(6, 1);
+            node.set_data_field::<u16>(7, 1);
+            node.set_data_field::<u16>(12, 1);
+
+            let fields = node.get_pointer_field(3).init_struct_list(
+                2, StructSize { data : 3, pointers : 4 });
+
+            let count = fields.get_struct_element(0);
+            count.get_pointer_field(0).set_text("count");
+            count.set_data_field_mask::<u16>(1, 0xffff, 0xffff);
+            count.set_data_field::<u32>(1, 1);
+            let type_ = count.get_pointer_field(2).init_struct(StructSize { data : 3, pointers : 1 });
+            type_.set_data_field::<u16>(0, 8);
+            let default = count.get_pointer_field(3).init_struct(StructSize { data : 2, pointers : 1 });
+            default.set_data_field::<u16>(0, 8);
+            default.set_data_field::<u32>(1, 7);
+
+            let color = fields.get_struct_element(1);
+            color.get_pointer_field(0).set_text("color");
+            color.set_data_field_mask::<u16>(1, 0xffff, 0xffff);
+            let type_ = color.get_pointer_field(2).init_struct(StructSize { data : 3, pointers : 1 });
+            type_.set_data_field::<u16>(0, 15);
+            type_.set_data_field::<u64>(1, 0x5678);
+        }
+
+        let mut enum_message = MallocMessageBuilder::new_default();
+        {
+            let node : NodeBuilder = enum_message.get_root_internal().init_as();
+            let node = node.0;
+            node.set_data_field::<u64>(0, 0x5678);
+            node.get_pointer_field(0).set_text("foo.capnp:Color");
+            node.set_data_field::<u32>(2, 10);
+            node.set_data_field::<u16>(6, 2);
+            let enumerants = node.get_pointer_field(3).init_struct_list(
+                2, StructSize { data : 1, pointers : 2 });
+            enumerants.get_struct_element(0).get_pointer_field(0).set_text("red");
+            enumerants.get_struct_element(1).get_pointer_field(0).set_text("green");
+        }
+
+        let mut loader = SchemaLoader::new();
+        assert_eq!(loader.load_encoded_node(struct_message.get_segments_for_output()[0]).unwrap(),
+                   0x1234);
+        let schema = loader.get(0x1234).unwrap();
+        assert_eq!(schema.get_short_name(), "Bar");
+        let bar = schema.as_struct().unwrap();
+        assert_eq!(bar.get_struct_size(), (1, 1));
+
+        let count = bar.find_field_by_name("count").unwrap();
+        assert_eq!(count.get_type(), Type::UInt32);
+        assert_eq!(count.get_offset(), Some(1));
+        assert_eq!(count.get_discriminant_value(), None);
+        match count.get_default_value() {
+            Some(&Value::UInt32(7)) => (),
+            _ => panic!("wrong default value"),
+        }
+
+        let color = bar.find_field_by_name("color").unwrap();
+        assert_eq!(color.get_type(), Type::Enum(0x5678));
+        assert!(loader.get_enum_schema(0x5678).is_err());
+
+        assert_eq!(loader.load_encoded_node(enum_message.get_segments_for_output()[0]).unwrap(),
+                   0x5678);
+        let color = loader.get_enum_schema(0x5678).unwrap();
+        assert_eq!(color.get_enumerant(1).unwrap().get_name(), "green");
+        assert_eq!(color.find_enumerant_by_name("red").unwrap().get_ordinal(), 0);
+        assert!(color.get_enumerant(2).is_none());
+    }
+}
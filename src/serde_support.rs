@@ -0,0 +1,503 @@
+// Copyright (c) 2013-2015 Sandstorm Development Group, Inc. and contributors
+// Licensed under the MIT License:
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Integration with [serde](https://serde.rs), enabled by the `serde` cargo feature.
+//!
+//! The dynamic readers implement `Serialize`, so a message can be written to any serde format
+//! given its schema. Structs become maps keyed by field name, with the same choice of fields as
+//! `stringify`; enums become enumerant names and `Data` becomes bytes.
+//!
+//! In the other direction, `StructSeed` is a `DeserializeSeed` that populates a
+//! `dynamic_struct::Builder` as the deserializer walks its input. Field values are requested
+//! with type hints taken from the schema. Unknown fields are skipped, a null pointer field is
+//! left unset, and lists are grown as their elements arrive.
+//!
+//! That is all there is: the module has no `Deserializer` that reads a message, and generated
+//! types do not implement `Serialize` or `Deserialize`. To use serde with generated code, go
+//! through the dynamic reader or builder for the struct's schema.
+
+use std::fmt;
+
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::ser::{self, Serialize, SerializeMap, SerializeSeq, Serializer};
+
+use dynamic_list;
+use dynamic_struct;
+use dynamic_value;
+use schema::{Field, Type};
+
+impl <'a> Serialize for dynamic_value::Reader<'a> {
+    fn serialize<S : Serializer>(&self, serializer : S) -> Result<S::Ok, S::Error> {
+        match *self {
+            dynamic_value::Reader::Void => serializer.serialize_unit(),
+            dynamic_value::Reader::Bool(v) => serializer.serialize_bool(v),
+            dynamic_value::Reader::Int8(v) => serializer.serialize_i8(v),
+            dynamic_value::Reader::Int16(v) => serializer.serialize_i16(v),
+            dynamic_value::Reader::Int32(v) => serializer.serialize_i32(v),
+            dynamic_value::Reader::Int64(v) => serializer.serialize_i64(v),
+            dynamic_value::Reader::UInt8(v) => serializer.serialize_u8(v),
+            dynamic_value::Reader::UInt16(v) => serializer.serialize_u16(v),
+            dynamic_value::Reader::UInt32(v) => serializer.serialize_u32(v),
+            dynamic_value::Reader::UInt64(v) => serializer.serialize_u64(v),
+            dynamic_value::Reader::Float32(v) => serializer.serialize_f32(v),
+            dynamic_value::Reader::Float64(v) => serializer.serialize_f64(v),
+            dynamic_value::Reader::Enum(e) => match e.get_enumerant() {
+                Some(enumerant) => serializer.serialize_str(enumerant.get_name()),
+                None => serializer.serialize_u16(e.get_value()),
+            },
+            dynamic_value::Reader::Text(t) => serializer.serialize_str(t),
+            dynamic_value::Reader::Data(d) => serializer.serialize_bytes(d),
+            dynamic_value::Reader::List(l) => l.serialize(serializer),
+            dynamic_value::Reader::Struct(s) => s.serialize(serializer),
+            dynamic_value::Reader::AnyPointer(_) =>
+                Err(ser::Error::custom("AnyPointer fields cannot be serialized")),
+        }
+    }
+}
+
+impl <'a> Serialize for dynamic_list::Reader<'a> {
+    fn serialize<S : Serializer>(&self, serializer : S) -> Result<S::Ok, S::Error> {
+        let mut seq = try!(serializer.serialize_seq(Some(self.len() as usize)));
+        for i in 0..self.len() {
+            let element = try!(self.get(i).map_err(ser::Error::custom));
+            try!(seq.serialize_element(&element));
+        }
+        seq.end()
+    }
+}
+
+impl <'a> Serialize for dynamic_struct::Reader<'a> {
+    fn serialize<S : Serializer>(&self, serializer : S) -> Result<S::Ok, S::Error> {
+        let fields = try!(self.get_present_fields().map_err(ser::Error::custom));
+        let mut map = try!(serializer.serialize_map(Some(fields.len())));
+        for field in fields.into_iter() {
+            let value = try!(self.get(field).map_err(ser::Error::custom));
+            try!(map.serialize_entry(field.get_name(), &value));
+        }
+        map.end()
+    }
+}
+
+/// Populates the struct that `builder` points at from `deserializer`.
+pub fn deserialize_into<'de, D>(deserializer : D, builder : &dynamic_struct::Builder)
+                                -> Result<(), D::Error>
+    where D : Deserializer<'de>
+{
+    StructSeed::new(builder).deserialize(deserializer)
+}
+
+/// Populates a struct builder from a map of field names to values.
+pub struct StructSeed<'b, 'a : 'b> {
+    builder : &'b dynamic_struct::Builder<'a>,
+}
+
+impl <'b, 'a> StructSeed<'b, 'a> {
+    pub fn new(builder : &'b dynamic_struct::Builder<'a>) -> StructSeed<'b, 'a> {
+        StructSeed { builder : builder }
+    }
+}
+
+impl <'de, 'b, 'a> DeserializeSeed<'de> for StructSeed<'b, 'a> {
+    type Value = ();
+
+    fn deserialize<D : Deserializer<'de>>(self, deserializer : D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl <'de, 'b, 'a> Visitor<'de> for StructSeed<'b, 'a> {
+    type Value = ();
+
+    fn expecting(&self, fmt : &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "the fields of struct `{}`", self.builder.get_schema().get_schema().get_short_name())
+    }
+
+    fn visit_map<M : MapAccess<'de>>(self, mut map : M) -> Result<(), M::Error> {
+        let schema = self.builder.get_schema();
+        while let Some(name) = try!(map.next_key::<String>()) {
+            let field = match schema.find_field_by_name(&name) {
+                Some(field) => field,
+                None => {
+                    try!(map.next_value::<IgnoredAny>());
+                    continue;
+                }
+            };
+            let seed = ValueSeed { target : Target::Field(self.builder, field),
+                                   type_ : field.get_type() };
+            if field.get_type().is_pointer() {
+                try!(map.next_value_seed(Nullable(seed)));
+            } else {
+                try!(map.next_value_seed(seed));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Where a deserialized value goes.
+enum Target<'b, 'a : 'b> {
+    Field(&'b dynamic_struct::Builder<'a>, Field<'a>),
+    Element(&'b dynamic_list::Builder<'a>, u32),
+}
+
+impl <'b, 'a> Target<'b, 'a> {
+    fn init_struct(&self) -> ::Result<dynamic_value::Builder<'b>> {
+        match *self {
+            Target::Field(builder, field) => builder.init(field),
+            Target::Element(list, index) => list.get(index),
+        }
+    }
+
+    fn init_list(&self) -> ::Result<dynamic_value::Builder<'b>> {
+        match *self {
+            Target::Field(builder, field) => builder.init_sized(field, 0),
+            Target::Element(list, index) => list.init(index, 0),
+        }
+    }
+
+    fn set(&self, value : dynamic_value::Reader) -> ::Result<()> {
+        match *self {
+            Target::Field(builder, field) => builder.set(field, value),
+            Target::Element(list, index) => list.set(index, value),
+        }
+    }
+}
+
+struct ValueSeed<'b, 'a : 'b> {
+    target : Target<'b, 'a>,
+    type_ : Type,
+}
+
+impl <'de, 'b, 'a> DeserializeSeed<'de> for ValueSeed<'b, 'a> {
+    type Value = ();
+
+    fn deserialize<D : Deserializer<'de>>(self, deserializer : D) -> Result<(), D::Error> {
+        match self.type_ {
+            Type::Struct(_) => match try!(self.target.init_struct().map_err(de::Error::custom)) {
+                dynamic_value::Builder::Struct(s) => StructSeed::new(&s).deserialize(deserializer),
+                _ => unreachable!(),
+            },
+            Type::List(_) => match try!(self.target.init_list().map_err(de::Error::custom)) {
+                dynamic_value::Builder::List(l) => deserializer.deserialize_seq(ListVisitor { list : l }),
+                _ => unreachable!(),
+            },
+            ref type_ => {
+                let value = try!(ScalarSeed(type_).deserialize(deserializer));
+                self.target.set(value.as_reader()).map_err(de::Error::custom)
+            }
+        }
+    }
+}
+
+/// Treats a null as an unset pointer.
+struct Nullable<S>(S);
+
+impl <'de, S> DeserializeSeed<'de> for Nullable<S> where S : DeserializeSeed<'de, Value = ()> {
+    type Value = ();
+
+    fn deserialize<D : Deserializer<'de>>(self, deserializer : D) -> Result<(), D::Error> {
+        deserializer.deserialize_option(self)
+    }
+}
+
+impl <'de, S> Visitor<'de> for Nullable<S> where S : DeserializeSeed<'de, Value = ()> {
+    type Value = ();
+
+    fn expecting(&self, fmt : &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str("a value or null")
+    }
+
+    fn visit_none<E : de::Error>(self) -> Result<(), E> { Ok(()) }
+
+    fn visit_unit<E : de::Error>(self) -> Result<(), E> { Ok(()) }
+
+    fn visit_some<D : Deserializer<'de>>(self, deserializer : D) -> Result<(), D::Error> {
+        self.0.deserialize(deserializer)
+    }
+}
+
+struct ListVisitor<'a> {
+    list : dynamic_list::Builder<'a>,
+}
+
+impl <'de, 'a> Visitor<'de> for ListVisitor<'a> {
+    type Value = ();
+
+    fn expecting(&self, fmt : &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str("a list")
+    }
+
+    fn visit_seq<A : SeqAccess<'de>>(self, mut seq : A) -> Result<(), A::Error> {
+        // Resizing is sound here because `self.list` came from `Target::init_list()` and is the
+        // only handle on the list, and each element seed borrows it only while that element is
+        // read.
+        let mut list = self.list;
+        // Don't trust the hint too far; it may come from untrusted input.
+        if let Some(hint) = seq.size_hint() {
+            try!(unsafe { list.grow(::std::cmp::min(hint, 4096) as u32) }
+                 .map_err(de::Error::custom));
+        }
+        let mut len = 0;
+        loop {
+            if len == list.len() {
+                let new_len = ::std::cmp::max(8, len * 2);
+                try!(unsafe { list.grow(new_len) }.map_err(de::Error::custom));
+            }
+            let seed = ValueSeed { target : Target::Element(&list, len),
+                                   type_ : list.get_element_type().clone() };
+            match try!(seq.next_element_seed(seed)) {
+                Some(()) => len += 1,
+                None => break,
+            }
+        }
+        unsafe { list.truncate(len) }.map_err(de::Error::custom)
+    }
+}
+
+/// A value that is neither a struct nor a list, as it came out of the deserializer.
+enum Scalar {
+    Void,
+    Bool(bool),
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    Text(String),
+    Data(Vec<u8>),
+}
+
+impl Scalar {
+    fn as_reader<'a>(&'a self) -> dynamic_value::Reader<'a> {
+        match *self {
+            Scalar::Void => dynamic_value::Reader::Void,
+            Scalar::Bool(v) => dynamic_value::Reader::Bool(v),
+            Scalar::Int(v) => dynamic_value::Reader::Int64(v),
+            Scalar::UInt(v) => dynamic_value::Reader::UInt64(v),
+            Scalar::Float(v) => dynamic_value::Reader::Float64(v),
+            Scalar::Text(ref v) => dynamic_value::Reader::Text(v),
+            Scalar::Data(ref v) => dynamic_value::Reader::Data(v),
+        }
+    }
+}
+
+struct ScalarSeed<'t>(&'t Type);
+
+impl <'de, 't> DeserializeSeed<'de> for ScalarSeed<'t> {
+    type Value = Scalar;
+
+    fn deserialize<D : Deserializer<'de>>(self, deserializer : D) -> Result<Scalar, D::Error> {
+        match *self.0 {
+            Type::Void => deserializer.deserialize_unit(ScalarVisitor),
+            Type::Bool => deserializer.deserialize_bool(ScalarVisitor),
+            Type::Int8 => deserializer.deserialize_i8(ScalarVisitor),
+            Type::Int16 => deserializer.deserialize_i16(ScalarVisitor),
+            Type::Int32 => deserializer.deserialize_i32(ScalarVisitor),
+            Type::Int64 => deserializer.deserialize_i64(ScalarVisitor),
+            Type::UInt8 => deserializer.deserialize_u8(ScalarVisitor),
+            Type::UInt16 => deserializer.deserialize_u16(ScalarVisitor),
+            Type::UInt32 => deserializer.deserialize_u32(ScalarVisitor),
+            Type::UInt64 => deserializer.deserialize_u64(ScalarVisitor),
+            Type::Float32 => deserializer.deserialize_f32(ScalarVisitor),
+            Type::Float64 => deserializer.deserialize_f64(ScalarVisitor),
+            Type::Text => deserializer.deserialize_string(ScalarVisitor),
+            Type::Data => deserializer.deserialize_byte_buf(ScalarVisitor),
+            // Either a name or a number.
+            Type::Enum(_) => deserializer.deserialize_any(ScalarVisitor),
+            Type::AnyPointer | Type::Interface(_) =>
+                Err(de::Error::custom("AnyPointer and interface fields cannot be deserialized")),
+            Type::Struct(_) | Type::List(_) => unreachable!(),
+        }
+    }
+}
+
+struct ScalarVisitor;
+
+impl <'de> Visitor<'de> for ScalarVisitor {
+    type Value = Scalar;
+
+    fn expecting(&self, fmt : &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str("a number, boolean, string, byte array or unit")
+    }
+
+    fn visit_unit<E : de::Error>(self) -> Result<Scalar, E> { Ok(Scalar::Void) }
+
+    fn visit_bool<E : de::Error>(self, v : bool) -> Result<Scalar, E> { Ok(Scalar::Bool(v)) }
+
+    fn visit_i64<E : de::Error>(self, v : i64) -> Result<Scalar, E> { Ok(Scalar::Int(v)) }
+
+    fn visit_u64<E : de::Error>(self, v : u64) -> Result<Scalar, E> { Ok(Scalar::UInt(v)) }
+
+    fn visit_f64<E : de::Error>(self, v : f64) -> Result<Scalar, E> { Ok(Scalar::Float(v)) }
+
+    fn visit_str<E : de::Error>(self, v : &str) -> Result<Scalar, E> {
+        Ok(Scalar::Text(v.to_string()))
+    }
+
+    fn visit_string<E : de::Error>(self, v : String) -> Result<Scalar, E> { Ok(Scalar::Text(v)) }
+
+    fn visit_bytes<E : de::Error>(self, v : &[u8]) -> Result<Scalar, E> {
+        Ok(Scalar::Data(v.to_vec()))
+    }
+
+    fn visit_byte_buf<E : de::Error>(self, v : Vec<u8>) -> Result<Scalar, E> {
+        Ok(Scalar::Data(v))
+    }
+
+    /// Formats without a bytes type, such as JSON, write `Data` as a sequence of numbers.
+    fn visit_seq<A : SeqAccess<'de>>(self, mut seq : A) -> Result<Scalar, A::Error> {
+        let mut bytes = Vec::new();
+        while let Some(b) = try!(seq.next_element::<u8>()) {
+            bytes.push(b);
+        }
+        Ok(Scalar::Data(bytes))
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use serde::de::DeserializeSeed;
+    use serde::de::value::{self, SeqDeserializer};
+    use serde_json;
+
+    use dynamic_struct;
+    use dynamic_value;
+    use message::{MallocMessageBuilder, MessageBuilder, MessageReader, ReaderOptions,
+                  SegmentArrayMessageReader};
+    use parse_text::parse_message;
+    use private::layout::{PointerBuilder, PointerReader};
+    use private::test_schema::{test_schema, FOO};
+    use schema::StructSchema;
+    use super::{deserialize_into, Target, ValueSeed};
+
+    fn init_root<'a>(message : &'a mut MallocMessageBuilder,
+                     schema : StructSchema<'a>) -> dynamic_struct::Builder<'a> {
+        let root : PointerBuilder = message.get_root_internal().init_as();
+        dynamic_struct::Builder::new(root.init_struct(dynamic_value::struct_size(schema)), schema)
+    }
+
+    fn show(builder : &dynamic_struct::Builder) -> String {
+        format!("{}", dynamic_value::Reader::Struct(builder.as_reader()))
+    }
+
+    fn from_json(schema : StructSchema, json : &str) -> String {
+        let mut message = MallocMessageBuilder::new_default();
+        let foo = init_root(&mut message, schema);
+        deserialize_into(&mut serde_json::Deserializer::from_str(json), &foo).unwrap();
+        show(&foo)
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let loader = test_schema();
+        let schema = loader.get_struct_schema(FOO).unwrap();
+        let mut message = MallocMessageBuilder::new_default();
+        let text = "(a = 7, b = true, e = blue, name = \"x\", \
+                    items = [(id = 1, label = \"one\"), (id = 2)], nums = [1, -2, 300], \
+                    uText = \"u\", grp = (x = -3, y = 1.5))";
+        parse_message(text, schema, &mut message).unwrap();
+        let segments = message.get_segments_for_output();
+        let reader = SegmentArrayMessageReader::new(segments, ReaderOptions::new());
+        let root : PointerReader = reader.get_root().unwrap();
+        let foo = dynamic_value::Reader::Struct(dynamic_struct::Reader::new(
+            root.get_struct(::std::ptr::null()).unwrap(), schema));
+        let json = serde_json::to_string(&foo).unwrap();
+        assert!(json.contains("\"e\":\"blue\""));
+        assert_eq!(from_json(schema, &json), format!("{}", foo));
+    }
+
+    #[test]
+    fn null_and_unknown_fields() {
+        let loader = test_schema();
+        let schema = loader.get_struct_schema(FOO).unwrap();
+        assert_eq!(from_json(schema, "{\"name\":null,\"items\":null,\"extra\":[1,{}]}"),
+                   from_json(schema, "{}"));
+
+        let mut message = MallocMessageBuilder::new_default();
+        let foo = init_root(&mut message, schema);
+        deserialize_into(&mut serde_json::Deserializer::from_str("{\"name\":null,\"items\":null}"),
+                         &foo).unwrap();
+        let foo = foo.as_reader();
+        assert!(!foo.has(schema.find_field_by_name("name").unwrap()).unwrap());
+        assert!(!foo.has(schema.find_field_by_name("items").unwrap()).unwrap());
+        assert_eq!(format!("{}", foo.get_named("name").unwrap()), "\"anon\"");
+
+        // Only pointer fields may be null.
+        let mut message = MallocMessageBuilder::new_default();
+        let foo = init_root(&mut message, schema);
+        assert!(deserialize_into(&mut serde_json::Deserializer::from_str("{\"a\":null}"),
+                                 &foo).is_err());
+    }
+
+    /// An iterator that claims to have `hint` elements, whatever it really holds.
+    struct Hinted<I> { inner : I, hint : usize }
+
+    impl <I : Iterator> Iterator for Hinted<I> {
+        type Item = I::Item;
+        fn next(&mut self) -> Option<I::Item> { self.inner.next() }
+        fn size_hint(&self) -> (usize, Option<usize>) { (self.hint, Some(self.hint)) }
+    }
+
+    fn nums_with_hint(values : Vec<i16>, hint : Option<usize>) -> String {
+        let loader = test_schema();
+        let schema = loader.get_struct_schema(FOO).unwrap();
+        let field = schema.find_field_by_name("nums").unwrap();
+        let mut message = MallocMessageBuilder::new_default();
+        let foo = init_root(&mut message, schema);
+        {
+            let seed = ValueSeed { target : Target::Field(&foo, field), type_ : field.get_type() };
+            let result = match hint {
+                Some(hint) => seed.deserialize(SeqDeserializer::<_, value::Error>::new(
+                    Hinted { inner : values.into_iter(), hint : hint })),
+                // `filter` makes the lower and upper bounds differ, so there is no hint.
+                None => seed.deserialize(SeqDeserializer::<_, value::Error>::new(
+                    values.into_iter().filter(|_| true))),
+            };
+            result.unwrap();
+        }
+        format!("{}", foo.as_reader().get(field).unwrap())
+    }
+
+    #[test]
+    fn list_growth_and_truncation() {
+        let twenty : Vec<i16> = (0..20).collect();
+        let expected = format!("{:?}", twenty);
+
+        // Grown from nothing, doubling past eight elements.
+        assert_eq!(nums_with_hint(twenty.clone(), None), expected);
+        assert_eq!(nums_with_hint(vec![], None), "[]");
+
+        // A hint that is exact, too small, too large or beyond the cap.
+        assert_eq!(nums_with_hint(twenty.clone(), Some(20)), expected);
+        assert_eq!(nums_with_hint(twenty.clone(), Some(3)), expected);
+        assert_eq!(nums_with_hint(vec![5, 6], Some(100)), "[5, 6]");
+        assert_eq!(nums_with_hint(vec![5, 6], Some(1 << 30)), "[5, 6]");
+        assert_eq!(nums_with_hint(vec![], Some(10)), "[]");
+    }
+
+    #[test]
+    fn struct_list_growth() {
+        let loader = test_schema();
+        let schema = loader.get_struct_schema(FOO).unwrap();
+        let items : Vec<String> = (0..10).map(|i| format!("{{\"id\":{}}}", i)).collect();
+        let shown = from_json(schema, &format!("{{\"items\":[{}]}}", items.join(",")));
+        assert!(shown.contains("(id = 9)]"));
+        assert_eq!(shown.matches("(id = ").count(), 10);
+    }
+}
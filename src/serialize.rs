@@ -47,6 +47,38 @@ impl MessageReader for OwnedSpaceMessageReader {
     }
 }
 
+impl OwnedSpaceMessageReader {
+    /// Constructs a reader over a copy of the given segments.
+    pub fn new(segments : &[&[Word]], options : ReaderOptions) -> OwnedSpaceMessageReader {
+        let total_words = segments.iter().fold(0, |total, segment| total + segment.len());
+        let mut owned_space : Vec<Word> = Vec::with_capacity(total_words);
+        let mut segment_slices : Vec<(usize, usize)> = Vec::with_capacity(segments.len());
+        for segment in segments.iter() {
+            let start = owned_space.len();
+            owned_space.extend(segment.iter().cloned());
+            segment_slices.push((start, owned_space.len()));
+        }
+        OwnedSpaceMessageReader::from_owned_space(owned_space, segment_slices, options)
+    }
+
+    fn from_owned_space(owned_space : Vec<Word>,
+                        segment_slices : Vec<(usize, usize)>,
+                        options : ReaderOptions) -> OwnedSpaceMessageReader {
+        let arena = {
+            let segments : Vec<&[Word]> =
+                segment_slices.iter().map(|&(a, b)| &owned_space[a .. b]).collect();
+            arena::ReaderArena::new(&segments, options)
+        };
+
+        OwnedSpaceMessageReader {
+            segment_slices : segment_slices,
+            owned_space : owned_space,
+            arena : arena,
+            options : options,
+        }
+    }
+}
+
 pub fn new_reader<U : InputStream>(
     input_stream : &mut U,
     options : ReaderOptions) -> Result<OwnedSpaceMessageReader> {
@@ -64,7 +96,7 @@ pub fn new_reader<U : InputStream>(
 
     let mut total_words = segment0_size;
 
-    if segment_count >= 512 {
+    if segment_count > options.segment_count_limit {
         return Err(Error::new_decode_error("Too many segments.", Some(format!("{}", segment_count))));
     }
 
@@ -105,38 +137,130 @@ pub fn new_reader<U : InputStream>(
 
     let mut segment_slices : Vec<(usize, usize)> = vec!((0, segment0_size as usize));
 
-    let arena = {
-        let segment0 : &[Word] = &owned_space[0 .. segment0_size as usize];
-        let mut segments : Vec<&[Word]> = vec!(segment0);
-
-        if segment_count > 1 {
-            let mut offset = segment0_size;
-
-            for ii in 0..(segment_count as usize - 1) {
-                segments.push(&owned_space[offset as usize ..
-                                           (offset + more_sizes[ii].get()) as usize]);
-                segment_slices.push((offset as usize,
-                                     (offset + more_sizes[ii].get()) as usize));
-                offset += more_sizes[ii].get();
+    if segment_count > 1 {
+        let mut offset = segment0_size;
+
+        for ii in 0..(segment_count as usize - 1) {
+            segment_slices.push((offset as usize,
+                                 (offset + more_sizes[ii].get()) as usize));
+            offset += more_sizes[ii].get();
+        }
+    }
+
+    Ok(OwnedSpaceMessageReader::from_owned_space(owned_space, segment_slices, options))
+}
+
+/// A message reader over a byte buffer. The buffer is read in place when it is aligned to a word
+/// boundary, and is copied into an aligned buffer only when it is not.
+pub struct FlatArrayMessageReader<'a> {
+    options : ReaderOptions,
+    arena : Box<arena::ReaderArena>,
+    segment_slices : Vec<(usize, usize)>,
+    space : ::std::borrow::Cow<'a, [Word]>,
+}
+
+impl <'a> MessageReader for FlatArrayMessageReader<'a> {
+    fn get_segment(&self, id : usize) -> &[Word] {
+        let (a,b) = self.segment_slices[id];
+        &self.space[a .. b]
+    }
+
+    fn arena(&self) -> &arena::ReaderArena { &*self.arena }
+    fn arena_mut(&mut self) -> &mut arena::ReaderArena { &mut *self.arena }
+
+    fn get_options(&self) -> &ReaderOptions {
+        return &self.options;
+    }
+}
+
+#[inline]
+fn read_u32_le(bytes : &[u8], index : usize) -> u32 {
+    let b = &bytes[index * 4 .. (index + 1) * 4];
+    (b[0] as u32) | ((b[1] as u32) << 8) | ((b[2] as u32) << 16) | ((b[3] as u32) << 24)
+}
+
+/// Reads a message from the front of `slice` and advances `slice` past it, so that a buffer
+/// holding several consecutive messages can be read one message at a time. The buffer does not
+/// need to be aligned; if it is not, the message is copied into an aligned buffer.
+pub fn read_message_from_bytes<'a>(slice : &mut &'a [u8],
+                                   options : ReaderOptions) -> Result<FlatArrayMessageReader<'a>> {
+    let bytes = *slice;
+    if bytes.len() < BYTES_PER_WORD {
+        return Err(Error::new_decode_error("Message ends prematurely in segment table.", None));
+    }
+
+    let segment_count = read_u32_le(bytes, 0) as u64 + 1;
+    if segment_count > options.segment_count_limit as u64 {
+        return Err(Error::new_decode_error("Too many segments.", Some(format!("{}", segment_count))));
+    }
+
+    // The segment table is padded to a whole number of words.
+    let table_bytes = ((segment_count as usize + 2) & !1) * 4;
+    if bytes.len() < table_bytes {
+        return Err(Error::new_decode_error("Message ends prematurely in segment table.", None));
+    }
+
+    let mut segment_slices : Vec<(usize, usize)> = Vec::with_capacity(segment_count as usize);
+    let mut total_words : u64 = 0;
+    for ii in 0..(segment_count as usize) {
+        let size = read_u32_le(bytes, ii + 1) as u64;
+        segment_slices.push((total_words as usize, (total_words + size) as usize));
+        total_words += size;
+    }
+
+    // See the comment in new_reader().
+    if ! (total_words <= options.traversal_limit_in_words)  {
+        return Err(Error::new_decode_error(
+            "Message is too large. To increase the limit on the \
+             receiving end, see capnp::ReaderOptions.", None));
+    }
+
+    let body_end = table_bytes + total_words as usize * BYTES_PER_WORD;
+    if bytes.len() < body_end {
+        return Err(Error::new_decode_error("Message ends prematurely.", None));
+    }
+    let body = &bytes[table_bytes .. body_end];
+
+    let space : ::std::borrow::Cow<'a, [Word]> = match Word::try_bytes_to_words(body) {
+        Ok(words) => ::std::borrow::Cow::Borrowed(words),
+        Err(_) => {
+            let mut words = Word::allocate_zeroed_vec(total_words as usize);
+            unsafe {
+                ::std::ptr::copy_nonoverlapping(body.as_ptr(),
+                                                ::std::mem::transmute(words.as_mut_ptr()),
+                                                body.len());
             }
+            ::std::borrow::Cow::Owned(words)
         }
+    };
+
+    let arena = {
+        let segments : Vec<&[Word]> =
+            segment_slices.iter().map(|&(a, b)| &space[a .. b]).collect();
         arena::ReaderArena::new(&segments, options)
     };
 
-    Ok(OwnedSpaceMessageReader {
-        segment_slices : segment_slices,
-        owned_space : owned_space,
-        arena : arena,
+    *slice = &bytes[body_end ..];
+
+    Ok(FlatArrayMessageReader {
         options : options,
+        arena : arena,
+        segment_slices : segment_slices,
+        space : space,
     })
 }
 
-
 pub fn write_message<T : OutputStream, U : MessageBuilder>(
     output_stream : &mut T,
     message : &mut U) -> ::std::io::Result<()> {
+    write_message_segments(output_stream, message.get_segments_for_output())
+}
+
+/// Writes a message made up of the given segments, e.g. those of a message that was read in.
+pub fn write_message_segments<T : OutputStream>(
+    output_stream : &mut T,
+    segments : &[&[Word]]) -> ::std::io::Result<()> {
 
-    let segments = message.get_segments_for_output();
     let table_size : usize = (segments.len() + 2) & (!1);
 
     let mut table : Vec<WireValue<u32>> = Vec::with_capacity(table_size);
@@ -167,3 +291,58 @@ pub fn write_message<T : OutputStream, U : MessageBuilder>(
     }
     output_stream.flush()
 }
+
+#[cfg(test)]
+mod tests {
+    use message::{AllocationStrategy, BuilderOptions, MallocMessageBuilder, MessageBuilder,
+                  MessageReader, ReaderOptions};
+    use serialize::{new_reader, read_message_from_bytes, write_message};
+    use {text, text_list, Word};
+
+    #[test]
+    pub fn read_unaligned_messages() {
+        // Start at an odd offset so that the messages are not aligned to a word boundary.
+        let mut bytes : Vec<u8> = vec![0];
+        for value in ["hello", "world!"].iter() {
+            let mut message = MallocMessageBuilder::new_default();
+            message.set_root(*value).unwrap();
+            write_message(&mut bytes, &mut message).unwrap();
+        }
+
+        let mut slice = &bytes[1 ..];
+        assert!(Word::try_bytes_to_words(slice).is_err());
+
+        let first = read_message_from_bytes(&mut slice, ReaderOptions::new()).unwrap();
+        assert_eq!(first.get_root::<text::Reader>().unwrap(), "hello");
+        let second = read_message_from_bytes(&mut slice, ReaderOptions::new()).unwrap();
+        assert_eq!(second.get_root::<text::Reader>().unwrap(), "world!");
+        assert_eq!(slice.len(), 0);
+        assert!(read_message_from_bytes(&mut slice, ReaderOptions::new()).is_err());
+    }
+
+    #[test]
+    pub fn segment_count_limit() {
+        // One-word segments put each of the texts in a segment of its own.
+        let mut message = MallocMessageBuilder::new(
+            *BuilderOptions::new().first_segment_words(1)
+                .allocation_strategy(AllocationStrategy::FixedSize));
+        {
+            let mut list : text_list::Builder = message.get_root_internal().init_as_sized(2);
+            list.set(0, "a");
+            list.set(1, "b");
+        }
+        let segment_count = message.get_segments_for_output().len() as u32;
+        assert!(segment_count > 2);
+        let mut bytes = Vec::new();
+        write_message(&mut bytes, &mut message).unwrap();
+
+        let mut options = ReaderOptions::new();
+        options.segment_count_limit(segment_count);
+        assert!(read_message_from_bytes(&mut &bytes[..], options).is_ok());
+        assert!(new_reader(&mut &bytes[..], options).is_ok());
+
+        options.segment_count_limit(segment_count - 1);
+        assert!(read_message_from_bytes(&mut &bytes[..], options).is_err());
+        assert!(new_reader(&mut &bytes[..], options).is_err());
+    }
+}
@@ -24,7 +24,7 @@ use io::{BufferedInputStream, BufferedOutputStream,
          InputStream, OutputStream};
 use message::*;
 use serialize;
-use Result;
+use {Result, Word};
 
 trait PtrUsize<T> {
     /// TODO: delete this function once a stable Rust release no longer demands PhantonFn bounds.
@@ -403,6 +403,11 @@ pub fn write_packed_message<T: BufferedOutputStream, U: MessageBuilder>(
     serialize::write_message(&mut packed_output_stream, message)
 }
 
+pub fn write_packed_message_segments<T : BufferedOutputStream>(
+    output : &mut T, segments : &[&[Word]]) -> ::std::io::Result<()> {
+    let mut packed_output_stream = PackedOutputStream {inner : output};
+    serialize::write_message_segments(&mut packed_output_stream, segments)
+}
 
 pub fn write_packed_message_unbuffered<T: OutputStream, U: MessageBuilder>(
     output : &mut T, message : &mut U) -> ::std::io::Result<()> {
@@ -0,0 +1,342 @@
+// Copyright (c) 2013-2015 Sandstorm Development Group, Inc. and contributors
+// Licensed under the MIT License:
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Printing of messages in the Cap'n Proto text format, e.g. `(name = "x", items = [(id = 1)])`.
+//!
+//! The dynamic readers implement `Display` and `Debug` using the default options. With the
+//! alternate flag (`{:#}`) they are pretty-printed across several lines.
+
+use std::fmt;
+
+use dynamic_list;
+use dynamic_struct;
+use dynamic_value;
+use message::MessageReader;
+use private::layout::PointerReader;
+use schema::{StructSchema, Type};
+use Result;
+
+/// Options controlling how values are printed.
+#[derive(Clone, Copy)]
+pub struct PrintOptions {
+    /// Number of spaces to indent each level of nesting. If zero, the value is printed on a
+    /// single line.
+    pub indent : u32,
+
+    /// Structs and lists nested more deeply than this are printed as `(...)` and `[...]`.
+    pub max_depth : u32,
+
+    /// Text and data longer than this many bytes are cut short and followed by `...`.
+    pub max_blob_len : u32,
+
+    /// Lists with more elements than this are cut short and followed by `...`.
+    pub max_list_len : u32,
+}
+
+pub const DEFAULT_PRINT_OPTIONS : PrintOptions =
+    PrintOptions { indent : 0, max_depth : 64,
+                   max_blob_len : ::std::u32::MAX, max_list_len : ::std::u32::MAX };
+
+impl PrintOptions {
+    pub fn new() -> PrintOptions { DEFAULT_PRINT_OPTIONS }
+
+    pub fn indent<'a>(&'a mut self, value : u32) -> &'a mut PrintOptions {
+        self.indent = value;
+        return self;
+    }
+
+    pub fn max_depth<'a>(&'a mut self, value : u32) -> &'a mut PrintOptions {
+        self.max_depth = value;
+        return self;
+    }
+
+    pub fn max_blob_len<'a>(&'a mut self, value : u32) -> &'a mut PrintOptions {
+        self.max_blob_len = value;
+        return self;
+    }
+
+    pub fn max_list_len<'a>(&'a mut self, value : u32) -> &'a mut PrintOptions {
+        self.max_list_len = value;
+        return self;
+    }
+}
+
+/// Writes `value` in the text format. Values that fail to decode are printed as
+/// `<error: ...>` rather than aborting the output, since printing is mostly used to look at
+/// data that is suspected to be broken.
+pub fn print<W : fmt::Write>(out : &mut W, value : dynamic_value::Reader,
+                             options : &PrintOptions) -> fmt::Result {
+    Printer { out : out, options : options, first_error : None }.value(value, 0)
+}
+
+/// Returns `value` in the text format.
+pub fn to_string(value : dynamic_value::Reader, options : &PrintOptions) -> String {
+    let mut result = String::new();
+    print(&mut result, value, options).unwrap();
+    result
+}
+
+/// Returns the root of `message`, which must be a struct of type `schema`, in the text format.
+/// Unlike `print()`, fails if any part of the message fails to decode, so that a successful result
+/// holds all of the message's data when the options do not cut it short.
+pub fn message_to_string<M : MessageReader>(message : &M, schema : StructSchema,
+                                            options : &PrintOptions) -> Result<String> {
+    let root : PointerReader = try!(message.get_root());
+    let reader = dynamic_struct::Reader::new(try!(root.get_struct(::std::ptr::null())), schema);
+    let mut result = String::new();
+    let first_error = {
+        let mut printer = Printer { out : &mut result, options : options, first_error : None };
+        printer.value(dynamic_value::Reader::Struct(reader), 0).unwrap();
+        printer.first_error
+    };
+    match first_error {
+        Some(e) => Err(e),
+        None => Ok(result),
+    }
+}
+
+/// Wraps a value so that it is displayed with the given options.
+pub struct Display<'a> {
+    value : dynamic_value::Reader<'a>,
+    options : PrintOptions,
+}
+
+pub fn display<'a>(value : dynamic_value::Reader<'a>, options : PrintOptions) -> Display<'a> {
+    Display { value : value, options : options }
+}
+
+impl <'a> fmt::Display for Display<'a> {
+    fn fmt(&self, fmt : &mut fmt::Formatter) -> fmt::Result {
+        print(fmt, self.value, &self.options)
+    }
+}
+
+fn format_default(value : dynamic_value::Reader, fmt : &mut fmt::Formatter) -> fmt::Result {
+    let mut options = PrintOptions::new();
+    if fmt.alternate() {
+        options.indent(2);
+    }
+    print(fmt, value, &options)
+}
+
+impl <'a> fmt::Display for dynamic_value::Reader<'a> {
+    fn fmt(&self, fmt : &mut fmt::Formatter) -> fmt::Result {
+        format_default(*self, fmt)
+    }
+}
+
+impl <'a> fmt::Debug for dynamic_value::Reader<'a> {
+    fn fmt(&self, fmt : &mut fmt::Formatter) -> fmt::Result {
+        format_default(*self, fmt)
+    }
+}
+
+impl <'a> fmt::Display for dynamic_struct::Reader<'a> {
+    fn fmt(&self, fmt : &mut fmt::Formatter) -> fmt::Result {
+        format_default(dynamic_value::Reader::Struct(*self), fmt)
+    }
+}
+
+impl <'a> fmt::Debug for dynamic_struct::Reader<'a> {
+    fn fmt(&self, fmt : &mut fmt::Formatter) -> fmt::Result {
+        format_default(dynamic_value::Reader::Struct(*self), fmt)
+    }
+}
+
+impl <'a> fmt::Display for dynamic_list::Reader<'a> {
+    fn fmt(&self, fmt : &mut fmt::Formatter) -> fmt::Result {
+        format_default(dynamic_value::Reader::List(*self), fmt)
+    }
+}
+
+impl <'a> fmt::Debug for dynamic_list::Reader<'a> {
+    fn fmt(&self, fmt : &mut fmt::Formatter) -> fmt::Result {
+        format_default(dynamic_value::Reader::List(*self), fmt)
+    }
+}
+
+fn is_compound(element_type : &Type) -> bool {
+    match *element_type {
+        Type::Struct(_) | Type::List(_) | Type::AnyPointer => true,
+        _ => false,
+    }
+}
+
+struct Printer<'o, W : 'o> {
+    out : &'o mut W,
+    options : &'o PrintOptions,
+
+    // The first decode failure, which is otherwise only visible in the output.
+    first_error : Option<::Error>,
+}
+
+impl <'o, W : fmt::Write> Printer<'o, W> {
+    fn newline(&mut self, depth : u32) -> fmt::Result {
+        if self.options.indent > 0 {
+            try!(self.out.write_char('\n'));
+            for _ in 0..(depth * self.options.indent) {
+                try!(self.out.write_char(' '));
+            }
+        }
+        Ok(())
+    }
+
+    fn error(&mut self, e : ::Error) -> fmt::Result {
+        try!(write!(self.out, "<error: {}>", e));
+        if self.first_error.is_none() {
+            self.first_error = Some(e);
+        }
+        Ok(())
+    }
+
+    fn value(&mut self, value : dynamic_value::Reader, depth : u32) -> fmt::Result {
+        match value {
+            dynamic_value::Reader::Void => self.out.write_str("void"),
+            dynamic_value::Reader::Bool(b) => write!(self.out, "{}", b),
+            dynamic_value::Reader::Int8(v) => write!(self.out, "{}", v),
+            dynamic_value::Reader::Int16(v) => write!(self.out, "{}", v),
+            dynamic_value::Reader::Int32(v) => write!(self.out, "{}", v),
+            dynamic_value::Reader::Int64(v) => write!(self.out, "{}", v),
+            dynamic_value::Reader::UInt8(v) => write!(self.out, "{}", v),
+            dynamic_value::Reader::UInt16(v) => write!(self.out, "{}", v),
+            dynamic_value::Reader::UInt32(v) => write!(self.out, "{}", v),
+            dynamic_value::Reader::UInt64(v) => write!(self.out, "{}", v),
+            dynamic_value::Reader::Float32(v) => self.float(v as f64),
+            dynamic_value::Reader::Float64(v) => self.float(v),
+            dynamic_value::Reader::Enum(e) => match e.get_enumerant() {
+                Some(enumerant) => self.out.write_str(enumerant.get_name()),
+                None => write!(self.out, "{}", e.get_value()),
+            },
+            dynamic_value::Reader::Text(t) => self.text(t),
+            dynamic_value::Reader::Data(d) => self.data(d),
+            dynamic_value::Reader::List(l) => self.list(l, depth),
+            dynamic_value::Reader::Struct(s) => self.struct_(s, depth),
+            dynamic_value::Reader::AnyPointer(_) => self.out.write_str("<opaque pointer>"),
+        }
+    }
+
+    fn float(&mut self, v : f64) -> fmt::Result {
+        if v.is_nan() {
+            self.out.write_str("nan")
+        } else if v.is_infinite() {
+            self.out.write_str(if v > 0.0 { "inf" } else { "-inf" })
+        } else {
+            write!(self.out, "{}", v)
+        }
+    }
+
+    fn text(&mut self, t : &str) -> fmt::Result {
+        let mut end = ::std::cmp::min(t.len(), self.options.max_blob_len as usize);
+        while !t.is_char_boundary(end) {
+            end -= 1;
+        }
+        try!(self.out.write_char('"'));
+        for c in t[..end].chars() {
+            match c {
+                '"' => try!(self.out.write_str("\\\"")),
+                '\\' => try!(self.out.write_str("\\\\")),
+                '\n' => try!(self.out.write_str("\\n")),
+                '\r' => try!(self.out.write_str("\\r")),
+                '\t' => try!(self.out.write_str("\\t")),
+                c if (c as u32) < 0x20 || c as u32 == 0x7f =>
+                    try!(write!(self.out, "\\x{:02x}", c as u32)),
+                c => try!(self.out.write_char(c)),
+            }
+        }
+        try!(self.out.write_char('"'));
+        if end < t.len() {
+            try!(self.out.write_str("..."));
+        }
+        Ok(())
+    }
+
+    fn data(&mut self, d : &[u8]) -> fmt::Result {
+        let end = ::std::cmp::min(d.len(), self.options.max_blob_len as usize);
+        try!(self.out.write_str("0x\""));
+        for b in d[..end].iter() {
+            try!(write!(self.out, "{:02x}", b));
+        }
+        try!(self.out.write_char('"'));
+        if end < d.len() {
+            try!(self.out.write_str("..."));
+        }
+        Ok(())
+    }
+
+    fn list(&mut self, list : dynamic_list::Reader, depth : u32) -> fmt::Result {
+        if list.len() == 0 {
+            return self.out.write_str("[]");
+        }
+        if depth >= self.options.max_depth {
+            return self.out.write_str("[...]");
+        }
+        // Lists of numbers and such stay on one line even when pretty-printing.
+        let multiline = is_compound(list.get_element_type());
+        let len = ::std::cmp::min(list.len(), self.options.max_list_len);
+        try!(self.out.write_char('['));
+        for i in 0..len {
+            if i > 0 {
+                try!(self.out.write_str(if multiline && self.options.indent > 0 { "," } else { ", " }));
+            }
+            if multiline {
+                try!(self.newline(depth + 1));
+            }
+            match list.get(i) {
+                Ok(element) => try!(self.value(element, depth + 1)),
+                Err(e) => try!(self.error(e)),
+            }
+        }
+        if len < list.len() {
+            try!(self.out.write_str(", ..."));
+        }
+        if multiline {
+            try!(self.newline(depth));
+        }
+        self.out.write_char(']')
+    }
+
+    fn struct_(&mut self, value : dynamic_struct::Reader, depth : u32) -> fmt::Result {
+        let fields = match value.get_present_fields() {
+            Ok(fields) => fields,
+            Err(e) => return self.error(e),
+        };
+        if fields.is_empty() {
+            return self.out.write_str("()");
+        }
+        if depth >= self.options.max_depth {
+            return self.out.write_str("(...)");
+        }
+        try!(self.out.write_char('('));
+        for (i, field) in fields.into_iter().enumerate() {
+            if i > 0 {
+                try!(self.out.write_str(if self.options.indent > 0 { "," } else { ", " }));
+            }
+            try!(self.newline(depth + 1));
+            try!(write!(self.out, "{} = ", field.get_name()));
+            match value.get(field) {
+                Ok(v) => try!(self.value(v, depth + 1)),
+                Err(e) => try!(self.error(e)),
+            }
+        }
+        try!(self.newline(depth));
+        self.out.write_char(')')
+    }
+}
@@ -24,9 +24,22 @@
 use private::layout::{ListReader, ListBuilder, PointerReader, PointerBuilder, InlineComposite};
 use traits::{FromPointerReader, FromPointerBuilder,
              FromStructBuilder, FromStructReader, HasStructSize,
-             IndexMove, ListIter};
+             IndexMove, IntoInternalStructReader, ListAppender, ListIter, ResizableList};
 use Result;
 
+pub struct Owned<T> {
+    marker : ::std::marker::PhantomData<T>,
+}
+
+impl <'a, T> ::traits::Owned<'a> for Owned<T>
+    where T : ::traits::Owned<'a>,
+          <T as ::traits::Owned<'a>>::Reader : FromStructReader<'a>,
+          <T as ::traits::Owned<'a>>::Builder : FromStructBuilder<'a> + HasStructSize
+{
+    type Reader = Reader<'a, <T as ::traits::Owned<'a>>::Reader>;
+    type Builder = Builder<'a, <T as ::traits::Owned<'a>>::Builder>;
+}
+
 pub struct Reader<'a, T> {
     marker : ::std::marker::PhantomData<T>,
     reader : ListReader<'a>
@@ -77,6 +90,24 @@ impl <'a, T : FromStructReader<'a>> Reader<'a, T> {
         assert!(index < self.len());
         FromStructReader::new(self.reader.get_struct_element(index))
     }
+
+    /// Like `get()`, but returns `None` instead of panicking if `index` is out of bounds.
+    pub fn try_get(self, index : u32) -> Option<T> {
+        if index < self.len() {
+            Some(self.get(index))
+        } else {
+            None
+        }
+    }
+}
+
+impl <'a, T : FromStructReader<'a>> ::std::iter::IntoIterator for Reader<'a, T> {
+    type Item = T;
+    type IntoIter = ListIter<Reader<'a, T>, T>;
+
+    fn into_iter(self) -> ListIter<Reader<'a, T>, T> {
+        self.iter()
+    }
 }
 
 pub struct Builder<'a, T> {
@@ -91,9 +122,20 @@ impl <'a, T : FromStructBuilder<'a>> Builder<'a, T> {
 
     pub fn len(&self) -> u32 { self.builder.len() }
 
-    //        pub fn set(&self, index : uint, value : T) {
-    //        }
+    pub fn iter(self) -> ListIter<Builder<'a, T>, T> {
+        let l = self.len();
+        ListIter::new(self, l)
+    }
+
+    /// Shrinks the list to `new_len` elements. See `ListBuilder::truncate()`.
+    pub fn truncate(&mut self, new_len : u32) -> Result<()> {
+        unsafe { self.builder.truncate(new_len) }
+    }
 
+    /// Extends the list to `new_len` elements. See `ListBuilder::grow()`.
+    pub fn grow(&mut self, new_len : u32) -> Result<()> {
+        unsafe { self.builder.grow(new_len) }
+    }
 }
 
 impl <'a, T> Builder<'a, T> {
@@ -125,6 +167,63 @@ impl <'a, T : FromStructBuilder<'a>> Builder<'a, T> {
         result
 
     }
+
+    /// Like `get()`, but returns `None` instead of panicking if `index` is out of bounds.
+    pub fn try_get(self, index : u32) -> Option<T> {
+        if index < self.len() {
+            Some(self.get(index))
+        } else {
+            None
+        }
+    }
+}
+
+impl <'a, T> Builder<'a, T> {
+    /// Sets the list element at `index` to a deep copy of `value`. As in the C++
+    /// implementation, if `value` is of a newer version of the struct than the list was
+    /// allocated for, the fields that don't fit are silently dropped.
+    pub fn set_with_caveats<'b, R>(&self, index : u32, value : R) -> Result<()>
+        where R : IntoInternalStructReader<'b>
+    {
+        assert!(index < self.builder.len());
+        self.builder.get_struct_element(index).copy_content_from(&value.into_internal_struct_reader())
+    }
+}
+
+impl <'a, T : FromStructBuilder<'a>>  IndexMove<u32, T> for Builder<'a, T> {
+    fn index_move(&self, index : u32) -> T {
+        assert!(index < self.len());
+        FromStructBuilder::new(self.builder.get_struct_element(index))
+    }
+}
+
+impl <'a, T : FromStructBuilder<'a>> ResizableList for Builder<'a, T> {
+    fn len(&self) -> u32 { self.builder.len() }
+    fn truncate(&mut self, new_len : u32) -> Result<()> {
+        unsafe { self.builder.truncate(new_len) }
+    }
+    fn grow(&mut self, new_len : u32) -> Result<()> {
+        unsafe { self.builder.grow(new_len) }
+    }
+}
+
+impl <'a, T : FromStructBuilder<'a>> ListAppender<Builder<'a, T>> {
+    /// Appends a deep copy of `value`. See `Builder::set_with_caveats()`.
+    pub fn push<'b, R>(&mut self, value : R) -> Result<()>
+        where R : IntoInternalStructReader<'b>
+    {
+        let index = try!(self.next_index());
+        self.get_list().set_with_caveats(index, value)
+    }
+}
+
+impl <'a, T : FromStructBuilder<'a>> ::std::iter::IntoIterator for Builder<'a, T> {
+    type Item = T;
+    type IntoIter = ListIter<Builder<'a, T>, T>;
+
+    fn into_iter(self) -> ListIter<Builder<'a, T>, T> {
+        self.iter()
+    }
 }
 
 impl <'a, T> ::traits::SetPointerBuilder<Builder<'a, T>> for Reader<'a, T> {
@@ -22,6 +22,14 @@
 //! UTF-8 encoded text.
 
 use {Error, Result};
+use private::layout::ListBuilder;
+
+pub struct Owned;
+
+impl <'a> ::traits::Owned<'a> for Owned {
+    type Reader = Reader<'a>;
+    type Builder = Builder<'a>;
+}
 
 pub type Reader<'a> = &'a str;
 
@@ -42,10 +50,25 @@ impl <'a> ::traits::FromPointerReader<'a> for Reader<'a> {
 pub struct Builder<'a> {
     bytes : &'a mut [u8],
     pos : usize,
+
+    // Length of the text. Less than `bytes.len()` if the list has spare capacity.
+    len : usize,
+
+    // The byte list, including NUL terminator, that holds `bytes`. If present, it is grown
+    // geometrically when a push does not fit, and its spare capacity is trimmed on drop.
+    list : Option<ListBuilder<'a>>,
+}
+
+// The text in a byte list, without the NUL terminator.
+fn text_bytes<'b>(list : ListBuilder<'b>) -> &'b mut [u8] {
+    let bytes = list.into_raw_bytes();
+    let len = bytes.len();
+    if len == 0 { bytes } else { &mut bytes[..len - 1] }
 }
 
 impl <'a> Builder <'a> {
 
+    /// Creates a builder over a fixed buffer. Pushing more than fits is an error.
     pub fn new<'b>(bytes : &'b mut [u8], pos : u32) -> Result<Builder<'b>> {
         if pos != 0 {
             match ::std::str::from_utf8(bytes) {
@@ -54,21 +77,67 @@ impl <'a> Builder <'a> {
                 _ => {}
             }
         }
-        Ok(Builder { bytes : bytes, pos : pos as usize })
+        let len = bytes.len();
+        Ok(Builder { bytes : bytes, pos : pos as usize, len : len, list : None })
+    }
+
+    /// Creates a builder over the text held in `list`, a byte list including the NUL terminator.
+    /// Pushing more than fits grows the list, moving it elsewhere in the message if needed. The
+    /// list is grown geometrically, and trimmed to the length of the text when the builder is
+    /// dropped.
+    pub fn new_growable<'b>(list : ListBuilder<'b>, pos : u32) -> Result<Builder<'b>> {
+        let mut result = try!(Builder::new(text_bytes(list), pos));
+        result.list = Some(list);
+        Ok(result)
+    }
+
+    // Makes sure that `additional` more bytes fit after `pos`, and extends the text to cover them.
+    fn reserve(&mut self, additional : usize) -> Result<()> {
+        let needed = self.pos + additional;
+        if needed > self.bytes.len() {
+            match self.list {
+                Some(ref mut list) => {
+                    if needed >= (1 << 29) - 1 {
+                        return Err(Error::new_decode_error("Text is too long.", None));
+                    }
+                    let capacity = ::std::cmp::min(::std::cmp::max(needed, 2 * self.bytes.len()),
+                                                   (1 << 29) - 2);
+                    try!(unsafe { list.grow(capacity as u32 + 1) });
+                    self.bytes = text_bytes(*list);
+                }
+                None => return Err(Error::new_decode_error("Text does not fit in its buffer.", None)),
+            }
+        }
+        self.len = ::std::cmp::max(self.len, needed);
+        Ok(())
     }
 
     pub fn push_ascii(&mut self, ascii : u8) {
         assert!(ascii < 128);
+        match self.reserve(1) {
+            Ok(()) => {}
+            Err(e) => panic!("{}", e),
+        }
         self.bytes[self.pos] = ascii;
         self.pos += 1;
     }
 
     pub fn push_str(&mut self, string : &str) {
+        match self.try_push_str(string) {
+            Ok(()) => {}
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Like `push_str()`, but returns an error instead of panicking if the text can't grow.
+    pub fn try_push_str(&mut self, string : &str) -> Result<()> {
         let bytes = string.as_bytes();
+        try!(self.reserve(bytes.len()));
         for ii in 0..bytes.len() {
             self.bytes[self.pos + ii] = bytes[ii];
         }
         self.pos += bytes.len();
+        Ok(())
     }
 
     pub fn clear(&mut self) {
@@ -79,16 +148,35 @@ impl <'a> Builder <'a> {
     }
 }
 
+impl <'a> ::std::fmt::Write for Builder<'a> {
+    fn write_str(&mut self, s : &str) -> ::std::fmt::Result {
+        self.try_push_str(s).map_err(|_| ::std::fmt::Error)
+    }
+}
+
+impl <'a> Drop for Builder<'a> {
+    fn drop(&mut self) {
+        match self.list {
+            Some(ref mut list) if list.len() as usize > self.len + 1 => {
+                // The bytes past the text are all zero, so this only fails if the message is
+                // already broken, and there is nowhere to report that from here.
+                let _ = unsafe { list.truncate(self.len as u32 + 1) };
+            }
+            _ => {}
+        }
+    }
+}
+
 impl <'a> ::std::ops::Deref for Builder <'a> {
     type Target = str;
     fn deref<'b>(&'b self) -> &'b str {
-        ::std::str::from_utf8(self.bytes).unwrap()
+        ::std::str::from_utf8(&self.bytes[..self.len]).unwrap()
     }
 }
 
 impl <'a> ::std::convert::AsRef<str> for Builder<'a> {
     fn as_ref<'b>(&'b self) -> &'b str {
-        ::std::str::from_utf8(self.bytes).unwrap()
+        ::std::str::from_utf8(&self.bytes[..self.len]).unwrap()
     }
 }
 
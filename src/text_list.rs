@@ -21,10 +21,17 @@
 
 //! List of strings containing UTF-8 encoded text.
 
-use traits::{FromPointerReader, FromPointerBuilder};
+use traits::{FromPointerReader, FromPointerBuilder, IndexMove, ListIter, ListAppender, ResizableList};
 use private::layout::{ListBuilder, ListReader, Pointer, PointerBuilder, PointerReader};
 use Result;
 
+pub struct Owned;
+
+impl <'a> ::traits::Owned<'a> for Owned {
+    type Reader = Reader<'a>;
+    type Builder = Builder<'a>;
+}
+
 #[derive(Clone, Copy)]
 pub struct Reader<'a> {
     reader : ListReader<'a>
@@ -36,6 +43,11 @@ impl <'a> Reader<'a> {
     }
 
     pub fn len(&self) -> u32 { self.reader.len() }
+
+    pub fn iter(self) -> ListIter<Reader<'a>, Result<::text::Reader<'a>>> {
+        let l = self.len();
+        ListIter::new(self, l)
+    }
 }
 
 impl <'a> FromPointerReader<'a> for Reader<'a> {
@@ -44,11 +56,59 @@ impl <'a> FromPointerReader<'a> for Reader<'a> {
     }
 }
 
+impl <'a>  IndexMove<u32, Result<::text::Reader<'a>>> for Reader<'a>{
+    fn index_move(&self, index : u32) -> Result<::text::Reader<'a>> {
+        self.get(index)
+    }
+}
+
+impl <'a> ::std::ops::Index<u32> for Reader<'a> {
+    type Output = str;
+
+    /// Like `get()`, but panics if the element is not valid text.
+    fn index<'b>(&'b self, index : u32) -> &'b str {
+        match self.get(index) {
+            Ok(v) => v,
+            Err(e) => panic!("{}", e),
+        }
+    }
+}
+
 impl <'a> Reader<'a> {
     pub fn get(self, index : u32) -> Result<::text::Reader<'a>> {
         assert!(index <  self.len());
         self.reader.get_pointer_element(index).get_text(::std::ptr::null(), 0)
     }
+
+    /// Like `get()`, but returns `None` instead of panicking if `index` is out of bounds.
+    pub fn try_get(self, index : u32) -> Option<Result<::text::Reader<'a>>> {
+        if index < self.len() {
+            Some(self.get(index))
+        } else {
+            None
+        }
+    }
+
+    /// Like `get()`, but does not check that the text is UTF-8.
+    pub fn get_as_bytes(self, index : u32) -> Result<&'a [u8]> {
+        assert!(index <  self.len());
+        self.reader.get_pointer_element(index).get_text_as_bytes(::std::ptr::null(), 0)
+    }
+
+    /// Like `get()`, but replaces any invalid UTF-8 sequences with U+FFFD.
+    pub fn get_lossy(self, index : u32) -> Result<::std::borrow::Cow<'a, str>> {
+        assert!(index <  self.len());
+        self.reader.get_pointer_element(index).get_text_lossy(::std::ptr::null(), 0)
+    }
+}
+
+impl <'a> ::std::iter::IntoIterator for Reader<'a> {
+    type Item = Result<::text::Reader<'a>>;
+    type IntoIter = ListIter<Reader<'a>, Self::Item>;
+
+    fn into_iter(self) -> ListIter<Reader<'a>, Self::Item> {
+        self.iter()
+    }
 }
 
 pub struct Builder<'a> {
@@ -70,6 +130,21 @@ impl <'a> Builder<'a> {
     pub fn borrow<'b>(&'b mut self) -> Builder<'b> {
         Builder {builder : self.builder}
     }
+
+    /// Shrinks the list to `new_len` elements. See `ListBuilder::truncate()`.
+    pub fn truncate(&mut self, new_len : u32) -> Result<()> {
+        unsafe { self.builder.truncate(new_len) }
+    }
+
+    /// Extends the list to `new_len` elements. See `ListBuilder::grow()`.
+    pub fn grow(&mut self, new_len : u32) -> Result<()> {
+        unsafe { self.builder.grow(new_len) }
+    }
+
+    pub fn iter(self) -> ListIter<Builder<'a>, ElementBuilder<'a>> {
+        let l = self.len();
+        ListIter::new(self, l)
+    }
 }
 
 
@@ -88,8 +163,68 @@ impl <'a> FromPointerBuilder<'a> for Builder<'a> {
 
 impl <'a> Builder<'a> {
     pub fn get(self, index : u32) -> Result<::text::Builder<'a>> {
+        assert!(index < self.len());
         self.builder.get_pointer_element(index).get_text(::std::ptr::null(), 0)
     }
+
+    /// Like `get()`, but returns `None` instead of panicking if `index` is out of bounds.
+    pub fn try_get(self, index : u32) -> Option<Result<::text::Builder<'a>>> {
+        if index < self.len() {
+            Some(self.get(index))
+        } else {
+            None
+        }
+    }
+}
+
+impl <'a>  IndexMove<u32, ElementBuilder<'a>> for Builder<'a> {
+    fn index_move(&self, index : u32) -> ElementBuilder<'a> {
+        assert!(index < self.len());
+        ::traits::ElementBuilder::new(Builder { builder : self.builder }, index)
+    }
+}
+
+impl <'a> ::std::iter::IntoIterator for Builder<'a> {
+    type Item = ElementBuilder<'a>;
+    type IntoIter = ListIter<Builder<'a>, ElementBuilder<'a>>;
+
+    fn into_iter(self) -> ListIter<Builder<'a>, ElementBuilder<'a>> {
+        self.iter()
+    }
+}
+
+impl <'a> ResizableList for Builder<'a> {
+    fn len(&self) -> u32 { self.builder.len() }
+    fn truncate(&mut self, new_len : u32) -> Result<()> {
+        unsafe { self.builder.truncate(new_len) }
+    }
+    fn grow(&mut self, new_len : u32) -> Result<()> {
+        unsafe { self.builder.grow(new_len) }
+    }
+}
+
+impl <'a> ListAppender<Builder<'a>> {
+    pub fn push(&mut self, value : ::text::Reader) -> Result<()> {
+        let index = try!(self.next_index());
+        self.get_list().set(index, value);
+        Ok(())
+    }
+}
+
+pub type ElementBuilder<'a> = ::traits::ElementBuilder<Builder<'a>>;
+
+impl <'a> ::traits::ElementBuilder<Builder<'a>> {
+    pub fn get(self) -> Result<::text::Builder<'a>> {
+        self.get_list().builder.get_pointer_element(self.index()).get_text(::std::ptr::null(), 0)
+    }
+
+    pub fn set(&mut self, value : ::text::Reader) {
+        self.get_list().builder.get_pointer_element(self.index()).set_text(value);
+    }
+
+    pub fn init(self, size : u32) -> ::text::Builder<'a> {
+        self.get_list().builder.get_pointer_element(self.index()).init_text(size)
+    }
 }
 
 impl <'a> ::traits::SetPointerBuilder<Builder<'a>> for Reader<'a> {
@@ -19,13 +19,21 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
 // THE SOFTWARE.
 
-use {Word, Result};
+use {Error, Word, Result};
 use private::layout::{StructReader, StructBuilder, StructSize, PointerBuilder, PointerReader};
 
 pub trait FromStructReader<'a> {
     fn new(reader : StructReader<'a>) -> Self;
 }
 
+pub trait IntoInternalStructReader<'a> {
+    fn into_internal_struct_reader(self) -> StructReader<'a>;
+}
+
+impl <'a> IntoInternalStructReader<'a> for StructReader<'a> {
+    fn into_internal_struct_reader(self) -> StructReader<'a> { self }
+}
+
 pub trait HasStructSize {
     fn struct_size(unused_self : Option<Self>) -> StructSize;
 }
@@ -38,6 +46,12 @@ pub trait FromPointerReader<'a> {
     fn get_from_pointer(reader : &PointerReader<'a>) -> Result<Self>;
 }
 
+impl <'a> FromPointerReader<'a> for PointerReader<'a> {
+    fn get_from_pointer(reader : &PointerReader<'a>) -> Result<PointerReader<'a>> {
+        Ok(*reader)
+    }
+}
+
 pub trait FromPointerReaderRefDefault<'a> {
     fn get_from_pointer(reader : &PointerReader<'a>, default_value : *const Word) -> Self;
 }
@@ -47,10 +61,27 @@ pub trait FromPointerBuilder<'a> {
     fn get_from_pointer(builder : PointerBuilder<'a>) -> Result<Self>;
 }
 
+impl <'a> FromPointerBuilder<'a> for PointerBuilder<'a> {
+    fn init_pointer(builder : PointerBuilder<'a>, _size : u32) -> PointerBuilder<'a> {
+        builder.clear();
+        builder
+    }
+    fn get_from_pointer(builder : PointerBuilder<'a>) -> Result<PointerBuilder<'a>> {
+        Ok(builder)
+    }
+}
+
 pub trait FromPointerBuilderRefDefault<'a> {
     fn get_from_pointer(builder : PointerBuilder<'a>, default_value : *const Word) -> Self;
 }
 
+/// Associates the reader and builder types of a value with a single lifetime-free marker type,
+/// so that the type of a message's root can be named without mentioning a lifetime.
+pub trait Owned<'a> {
+    type Reader : FromPointerReader<'a>;
+    type Builder : FromPointerBuilder<'a>;
+}
+
 pub trait SetPointerBuilder<To> {
     /// TODO: delete this function once a stable Rust release no longer demands PhantonFn bounds.
     fn phantom(To) { panic!("This function only exists for variance.") }
@@ -74,6 +105,9 @@ pub trait FromU16 {
     fn from_u16(value : u16) -> ::std::result::Result<Self, ::NotInSchema>;
 }
 
+/// Indexing that returns the element by value. List elements are decoded on access, so most
+/// lists have nothing that `std::ops::Index` could return a reference to; only text and data
+/// lists, whose elements are borrowed from the message, implement it.
 pub trait IndexMove<I, T> {
     fn index_move(&self, index : I) -> T;
 }
@@ -102,4 +136,94 @@ impl <U, T : IndexMove<u32, U>> ::std::iter::Iterator for ListIter<T, U> {
             return None;
         }
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = (self.size - self.index) as usize;
+        (remaining, Some(remaining))
+    }
+}
+
+impl <U, T : IndexMove<u32, U>> ::std::iter::ExactSizeIterator for ListIter<T, U> {}
+
+impl <U, T : IndexMove<u32, U>> ::std::iter::DoubleEndedIterator for ListIter<T, U> {
+    fn next_back(&mut self) -> ::std::option::Option<U> {
+        if self.index < self.size {
+            self.size -= 1;
+            return Some(self.list.index_move(self.size));
+        } else {
+            return None;
+        }
+    }
+}
+
+/// A handle on element `index()` of the list builder `L`, as yielded by the list builders'
+/// `iter()`. Each list module adds the accessors that make sense for its element type.
+pub struct ElementBuilder<L> {
+    list : L,
+    index : u32,
+}
+
+impl <L : ResizableList> ElementBuilder<L> {
+    /// Panics if `index` is out of bounds, since the accessors do not check it again.
+    pub fn new(list : L, index : u32) -> ElementBuilder<L> {
+        assert!(index < list.len());
+        ElementBuilder { list : list, index : index }
+    }
+}
+
+impl <L> ElementBuilder<L> {
+    pub fn index(&self) -> u32 { self.index }
+
+    pub fn get_list<'b>(&'b self) -> &'b L { &self.list }
+}
+
+/// A list builder whose length can be changed after the list has been initialized. Resizing may
+/// move the list, so implementations must only hand out element builders that either borrow the
+/// list builder or consume it.
+pub trait ResizableList {
+    fn len(&self) -> u32;
+    fn truncate(&mut self, new_len : u32) -> Result<()>;
+    fn grow(&mut self, new_len : u32) -> Result<()>;
+}
+
+/// Builds a list whose final length is not known in advance. The list's capacity is doubled
+/// whenever it fills up, and the excess is trimmed off by `finish()`.
+pub struct ListAppender<L> {
+    list : L,
+    len : u32,
+}
+
+impl <L : ResizableList> ListAppender<L> {
+    /// Appends after the existing elements of `list`.
+    pub fn new(list : L) -> ListAppender<L> {
+        let len = list.len();
+        ListAppender { list : list, len : len }
+    }
+
+    pub fn len(&self) -> u32 { self.len }
+
+    /// Makes room for one more element and returns its index in `get_list()`. Fails if the list
+    /// already has the maximum number of elements.
+    pub fn next_index(&mut self) -> Result<u32> {
+        if self.len == self.list.len() {
+            if self.len >= (1 << 29) - 1 {
+                return Err(Error::new_decode_error("List is too long.", None));
+            }
+            let capacity = ::std::cmp::min(::std::cmp::max(8, self.len.saturating_mul(2)),
+                                           (1 << 29) - 1);
+            try!(self.list.grow(capacity));
+        }
+        let index = self.len;
+        self.len += 1;
+        Ok(index)
+    }
+
+    /// The list being appended to. Its length is the current capacity, not `len()`.
+    pub fn get_list<'b>(&'b mut self) -> &'b mut L { &mut self.list }
+
+    /// Trims the list to the elements that were appended and returns it.
+    pub fn finish(mut self) -> Result<L> {
+        try!(self.list.truncate(self.len));
+        Ok(self.list)
+    }
 }